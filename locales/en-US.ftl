@@ -0,0 +1,257 @@
+## General
+
+error = Error: { $error }
+press-enter-to-exit = Press enter to exit
+press-enter-to-continue = Press enter to continue, Ctrl+C to cancel
+preparing = Preparing...
+reading-databases = Reading osu!.db and osu!lazer's database ({ $lazer } beatmaps in osu!lazer so far)
+read-databases = Read { $stable } beatmaps from osu!.db and { $lazer } from osu!lazer
+stable-path = Stable path: { $path }
+stable-songs-path = Stable songs path: { $path }
+lazer-path = Lazer path: { $path }
+log-file = Logging to { $path }
+lazer-target = Linking into { $path }
+stable-beatmap-count = Stable beatmap count: { $count }
+lazer-beatmap-count = Lazer beatmap count: { $count }
+since-last-run = Importing only sets added or changed since the last run on { $date }
+since-last-run-none = No earlier run into this osu!lazer was recorded, importing everything
+sample-seed = Picking sets with the seed { $seed }
+server = Server: { $server }
+local-import = Beatmaps will be imported as local maps, without online IDs
+overridden-settings = { $count } beatmaps have per-beatmap settings in osu!stable (ignore skin/hitsounds, disable storyboard/video, visual overrides)
+overridden-settings-not-migrated = osu!lazer has no per-beatmap equivalent for these, so they will not be migrated
+close-games = Make sure both osu!stable and osu!lazer are closed!
+back-up-lazer = Also back up your osu!lazer folder before continuing!
+scanning-songs = Scanning { $path }, this may take a while...
+missing-folders = { $count ->
+        [one] 1 beatmapset is listed in osu!.db but not found on disk, it will be skipped:
+       *[other] { $count } beatmapsets are listed in osu!.db but not found on disk, they will be skipped:
+    }
+warning-stable-db-version = Warning: couldn't fully parse osu!.db (version { $version }), falling back to reading only what's needed: { $error }
+warning-stable-db-partial = Warning: only { $read } of the { $expected } beatmaps in osu!.db could be read
+pool-missing = Mappool entries not found in your osu!stable library: { $ids }
+prompt-stable-path = You will be prompted to select the path to your osu!stable directory, press enter to continue
+select-stable-path = Select the path to your osu!stable directory
+cross-filesystem = Warning: osu!stable's Songs folder is on another filesystem than osu!lazer, its links will break if that drive is unmounted. Pass --link-mode copy to copy the files instead, or move osu!lazer's data onto the same filesystem and pass --link-mode hardlink
+hardlink-fallback = Files that can't be hard linked will be symlinked instead
+diff-summary = { $beatmaps } beatmaps in { $sets } beatmapsets would be imported
+warning-save-config = Warning: couldn't remember the osu!stable directory: { $error }
+
+## Progress
+
+stage-processing-beatmaps = Processing beatmaps
+stage-inserting-beatmaps = Inserting beatmaps
+stage-processing-files = Processing files
+stage-inserting-files = Inserting files
+stage-database = Database
+stage-exporting = Exporting beatmapsets
+stage-checking = Checking beatmaps
+stage-packaging = Packaging beatmapsets
+stage-hashing-store = Hashing files
+stage-overall = Overall
+bar-overall = { stage-overall }:
+bar-processing-beatmaps = { stage-processing-beatmaps }:
+bar-inserting-beatmaps = { stage-inserting-beatmaps }:
+bar-processing-files = { stage-processing-files }:
+bar-inserting-files = { stage-inserting-files }:
+bar-database = { stage-database }:
+bar-exporting = { stage-exporting }:
+bar-checking = { stage-checking }:
+bar-packaging = { stage-packaging }:
+bar-hashing-store = { stage-hashing-store }:
+bar-eta = ETA
+bar-worker = Thread { $worker }:
+worker-idle = Idle
+waiting = Waiting...
+step = Step { $number } of { $total }: { $name }
+step-parsing = Parsing beatmaps
+step-hashing = Hashing files
+step-writing = Writing to the database
+step-committing = Committing
+relinking-replays = Checking which beatmaps replays belong to
+committing = Committing
+checking-database = Checking the database
+copying-staged = Writing the changes to osu!lazer's database
+populating-lookup-cache = Adding the beatmaps to osu!lazer's lookup cache
+done = Done.
+
+## Errors
+
+error-invalid-lazer = Not a valid osu!lazer directory? (missing client.db)
+error-lazer-not-found = Can't find lazer path, do you have the game installed?
+error-no-data-directory = No data directory?
+error-invalid-stable = Not a valid osu!stable directory? (missing osu!.db)
+error-select-directory = Failed to select the directory..?
+error-songs-not-found = Songs folder not found at { $path }
+error-online-db-not-found = online.db not found at { $path }
+error-missing-online-db = Missing osu!lazer online.db, try opening the game, closing it, and then rerunning this tool?
+error-genre-without-online-db = --genre and --language need an online.db to look sets up in, which is only used for bancho unless given with --online-db
+error-online-db-no-genres = This online.db has no genres or languages to filter by
+error-open-online-db = Failed to open online.db
+error-hard-link = Link test failed! On Windows, lazer and stable must either be on the same disk, or Developer Mode must be enabled (or osu-link run as administrator) to allow symlinks. Alternatively, pass --link-mode copy to copy the files instead.
+error-version-mismatch = Database version mismatch! Please make sure you have the latest versions of both osu! and osu-link
+error-missing-table = osu!lazer's database has no { $table } table, it may be from an unsupported version of osu!lazer
+error-missing-index = osu!lazer's database has no unique index on { $table }.{ $column }, it may be from an unsupported version of osu!lazer
+error-unknown-profile = No profile named { $profile } in the config file
+error-read-pool = Failed to read the mappool at { $path }
+error-read-bracket = Failed to read the tournament bracket at { $path }
+error-staged-integrity = The import failed the integrity checks, so nothing was written to osu!lazer's database
+error-staged-changed = osu!lazer's database was changed during the import, so nothing was written to it. Close osu!lazer and try again
+error-cancelled = The import was cancelled, and nothing was written to osu!lazer's database. Files linked before that are left for `osu-link gc`
+error-check-stable-songs = check-stable compares osu!.db with the Songs folder, so it can't be used with --songs
+error-no-backup = No backup of osu!lazer's database found, nothing to undo
+
+## Import
+
+error-processing = Error occurred while processing { $folder }/{ $file }
+error-importing = Error importing { $folder }/{ $file }
+error-linking = Error linking { $folder }/{ $file }: { $error }
+error-store-conflict = { $path } is already in osu!lazer's file store, but its contents don't match (they hash to { $actual }). Remove it and run osu-link again to link it anew
+error-scanning = Error scanning { $path }: { $error }
+warning-malformed = Warning: { $folder }/{ $file } is malformed, importing it with best-effort metadata
+warning-database-only = Warning: { $folder }/{ $file } could not be parsed, importing it with osu!.db data only
+warning-changed = Warning: { $folder }/{ $file } has changed since osu!stable last scanned it, using its current hash
+warning-star-rating = Warning: couldn't recalculate the star rating of { $folder }/{ $file }, keeping osu!.db's: { $error }
+warning-case-collision = Warning: { $folder } contains both { $kept } and { $dropped }, only the former will be imported
+warning-duplicate-id-skipped = Warning: osu!lazer already has a beatmap with the online ID of { $folder }/{ $file }, skipping it
+warning-duplicate-id-overwritten = Warning: osu!lazer already has a different beatmap with the online ID of { $folder }/{ $file }, replacing it
+warning-duplicate-id-duplicated = Warning: osu!lazer already has a different beatmap with the online ID of { $folder }/{ $file }, importing it as a local map
+warning-set-duplicated = Warning: osu!lazer already has the beatmapset of { $folder }, importing it again as a local map
+warning-file-conflict = Warning: { $folder }/{ $file } differs from the file osu!lazer already has by that name, keeping osu!lazer's
+replays-relinked = { $count } scores referred to another beatmap than their replay was played on, and now refer to the right one
+warning-lookup-cache = Warning: couldn't add the beatmaps to osu!lazer's lookup cache: { $error }
+warning-lookup-cache-private-server = Warning: not adding the beatmaps to osu!lazer's lookup cache, as their IDs are the private server's
+manifest-written = Wrote the manifest to { $path }
+via-lazer-started = Started osu!lazer with { $count } .osz files to import, which it deletes once imported
+via-lazer-drag = Packaged { $count } .osz files in { $path }, drag them into osu!lazer to import them
+warning-lazer-running = osu!lazer is running, and won't show the imported beatmaps until it's restarted
+lazer-launched = Started osu!lazer
+warning-lazer-not-found = Couldn't find osu!lazer to start, pass its executable with --lazer-exe
+
+## Failure breakdown
+
+failure-breakdown = Failures by category:
+failure-category = { $category } ({ $count }): { $sets }
+failure-more-sets = { $sets } and { $count } more sets
+failure-missing-file = Missing files (usually harmless, the set refers to a file it doesn't ship)
+failure-parse = Unreadable beatmaps
+failure-io = Read errors
+failure-link = Link failures
+failure-database = Database errors
+failure-integrity = Database check warnings
+
+## Database checks
+
+integrity-corrupt = Warning: osu!lazer's database is corrupt: { $details }
+integrity-foreign-key = Warning: a row of { $table } refers to a missing row of { $parent }
+integrity-beatmap-set = Warning: { $count } beatmaps belong to a missing beatmapset
+integrity-beatmap-difficulty = Warning: { $count } beatmaps have missing difficulty settings
+integrity-beatmap-metadata = Warning: { $count } beatmaps have missing metadata
+integrity-set-metadata = Warning: { $count } beatmapsets have missing metadata
+integrity-set-file = Warning: { $count } beatmapset files refer to a missing file or beatmapset
+
+## Export
+
+error-no-sets-selected = No beatmapsets selected to export
+error-exporting = Error exporting { $name }: { $error }
+export-stable-missing = Beatmapsets not found in your osu!stable library: { $ids }
+export-lazer-missing = Beatmapsets not found in your osu!lazer library: { $ids }
+error-no-lazer-collections = osu!lazer has no collections to export
+collection-missing = Not in osu!stable: { $hash } from the collection { $collection }
+export-scores-none = osu!lazer has no scores to export
+export-scores-summary = Exported { $exported } scores to { $path }. { $missing } were on beatmaps osu!stable doesn't have, and { $unrepresentable } had mods osu!stable can't show
+export-map-done = Wrote the locations of { $count } beatmaps to { $path }
+export-collections-summary = Wrote { $collections } collections to { $path }, adding { $beatmaps } beatmaps, { $missing } of which osu!stable doesn't have
+
+## Timings
+
+timing-stage = Stage
+timing-wall = Wall
+timing-busy = Busy
+timing-cpu = CPU
+timing-stable-db = Reading osu!.db
+timing-lazer-query = Querying osu!lazer
+timing-parsing = Parsing .osu files
+timing-walking = Walking set folders
+timing-reading = Reading files
+timing-hashing = Hashing files
+timing-inserting = Inserting rows
+timing-linking = Linking files
+timing-commit = Committing
+
+## Notifications
+
+notification-done = Imported { $beatmaps } beatmaps and { $files } files in { $elapsed }, { $failures } failed
+notification-failed = Import failed: { $error }
+warning-notification = Warning: couldn't send the notification: { $error }
+
+## Verify, check-stable, undo, dedupe, gc, clean, doctor and status
+
+verify-missing = Missing: { $set }/{ $file }
+verify-summary = Checked { $checked } files, { $missing } missing
+verify-changed = Changed: { $set }/{ $file } no longer matches its hash, it was likely edited in osu!stable
+verify-deep-summary = Hashed { $hashed } files, { $changed } changed
+undo-done = Restored osu!lazer's database from the backup. Files linked by the undone import are left in place, but are no longer used.
+check-stable-missing = Missing: { $folder }/{ $file }
+check-stable-changed = Changed since osu!stable last scanned it: { $folder }/{ $file }
+check-stable-orphan = Not in osu!.db: { $folder }
+check-stable-summary = { $orphans } folders not in osu!.db, { $missing } .osu files missing, { $changed } .osu files changed
+dedupe-none = No duplicate beatmapsets found
+dedupe-merged = Merged a duplicate of { $set }
+dedupe-summary = Merged { $count } duplicate beatmapsets
+gc-none = No unused files found
+gc-summary = Removed { $rows } unreferenced rows and { $files } files, reclaiming { $size }
+clean-none = Nothing left behind to clean
+clean-removed = Removed { $path }
+clean-summary = Removed { $count } leftovers
+error-clean = Error removing { $path }: { $error }
+doctor-lazer-found = osu!lazer found at { $path }
+doctor-lazer-db = osu!lazer's client.db exists
+doctor-lazer-version = osu!lazer's database version is supported
+doctor-online-db = online.db exists
+doctor-stable-found = osu!stable found at { $path }
+doctor-stable-not-found = osu!stable not found, pass --stable or --songs
+doctor-stable-db = osu!stable's osu!.db exists
+doctor-songs = Songs folder exists at { $path }
+doctor-songs-unknown = Couldn't find osu!stable's Songs folder
+doctor-link = Files can be linked from osu!stable into osu!lazer
+doctor-passed = Everything looks good!
+doctor-failed = Some checks failed
+status-not-running = No import is running
+status-progress = { $stage } ({ $percent }%), at { $set }, { $failures } failures so far
+
+## Interactive selection
+
+select-filter = Filter sets by ID, artist or title (leave empty to list all)
+select-no-match = No beatmapsets match the filter
+select-prompt = Select the sets to import (space to toggle, enter to confirm)
+select-item = { $set } { $artist } - { $title } ({ $count ->
+        [one] 1 difficulty
+       *[other] { $count } difficulties
+    }, { $size })
+
+## Dashboard
+
+dashboard-summary-title = Summary
+dashboard-summary =
+    Imported { $beatmaps } beatmaps and { $files } files in { $elapsed }
+    { $messages } warnings and errors
+    Total hashed: { $bytes }
+dashboard-throughput-title = Throughput
+dashboard-throughput = { $files } files/s, { $bytes }/s
+dashboard-messages-title = Warnings and errors ({ $count })
+dashboard-help-running = Up/Down/PgUp/PgDn: scroll, Ctrl+C: cancel
+dashboard-help-finished = Up/Down/PgUp/PgDn: scroll, q: exit
+
+## GUI
+
+gui-stable-path = osu!stable directory
+gui-lazer-path = osu!lazer directory
+gui-pool = Mappool file (optional)
+gui-none = (none)
+gui-browse = Browse...
+gui-recalculate-sr = Recalculate star ratings with rosu-pp
+gui-server = Server:
+gui-keep-online-ids = Keep online IDs (for servers mirroring bancho's beatmap IDs)
+gui-warning = Make sure both osu!stable and osu!lazer are closed, and back up your osu!lazer folder before importing!
+gui-import = Import
@@ -0,0 +1,95 @@
+use itertools::Itertools;
+use libosu::{
+    beatmap::Beatmap,
+    timing::{TimingPointKind, UninheritedTimingInfo},
+};
+use std::{collections::HashMap, fmt};
+
+pub struct Bpm {
+    pub min: f64,
+    pub max: f64,
+    // What lazer and stable display as "the" BPM of a map
+    pub most_common: f64,
+}
+
+impl fmt::Display for Bpm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if (self.max - self.min).abs() < f64::EPSILON {
+            write!(f, "{:.0}", self.most_common)
+        } else {
+            write!(
+                f,
+                "{:.0}-{:.0} ({:.0})",
+                self.min, self.max, self.most_common
+            )
+        }
+    }
+}
+
+// Mirrors lazer's Beatmap.GetMostCommonBeatLength: every uninherited timing point's beat length is
+// weighted by how long it lasts (up until the last hit object), and the heaviest one wins
+pub fn bpm(beatmap: &Beatmap) -> Bpm {
+    let timing_points = beatmap
+        .timing_points
+        .iter()
+        .filter_map(|tp| match tp.kind {
+            TimingPointKind::Uninherited(UninheritedTimingInfo { mpb, .. }) => {
+                Some((tp.time.0 as f64, mpb as f64))
+            }
+            _ => None,
+        })
+        .collect_vec();
+
+    if timing_points.is_empty() {
+        return Bpm {
+            min: 0.0,
+            max: 0.0,
+            most_common: 0.0,
+        };
+    }
+
+    let last_time = beatmap
+        .hit_objects
+        .last()
+        .map(|ho| ho.start_time.0 as f64)
+        .unwrap_or_else(|| timing_points.last().unwrap().0);
+
+    // Keyed on the beat length rounded to 3 decimals, the same as lazer does
+    let mut durations: HashMap<i64, f64> = HashMap::new();
+    for (i, (time, mpb)) in timing_points.iter().enumerate() {
+        let duration = if *time > last_time {
+            0.0
+        } else {
+            timing_points.get(i + 1).map_or(last_time, |next| next.0) - time
+        };
+
+        *durations.entry((mpb * 1000.0).round() as i64).or_default() += duration;
+    }
+
+    let most_common_mpb = durations
+        .into_iter()
+        .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+        .map(|(mpb, _)| mpb as f64 / 1000.0)
+        .unwrap();
+
+    let (min_mpb, max_mpb) = timing_points
+        .iter()
+        .map(|(_, mpb)| *mpb)
+        .minmax()
+        .into_option()
+        .unwrap();
+
+    Bpm {
+        min: 60_000.0 / max_mpb,
+        max: 60_000.0 / min_mpb,
+        most_common: 60_000.0 / most_common_mpb,
+    }
+}
+
+// Length in milliseconds from the first to the last hit object, for when osu!.db doesn't know
+pub fn length(beatmap: &Beatmap) -> Option<f64> {
+    let first = beatmap.hit_objects.first()?;
+    let last = beatmap.hit_objects.last()?;
+
+    Some((last.start_time.0 - first.start_time.0) as f64)
+}
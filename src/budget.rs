@@ -0,0 +1,47 @@
+use std::sync::{Arc, Condvar, Mutex};
+
+// Caps the bytes of files queued for or being hashed at once. Hashing reads whole files into
+// memory, so a few large videos hashed side by side could otherwise take gigabytes.
+#[derive(Clone, Default)]
+pub struct MemoryBudget {
+    // In bytes, 0 meaning no limit
+    limit: u64,
+    used: Arc<(Mutex<u64>, Condvar)>,
+}
+
+impl MemoryBudget {
+    pub fn new(limit: u64) -> Self {
+        Self {
+            limit,
+            ..Self::default()
+        }
+    }
+
+    // Blocks until `bytes` fit in the budget, and returns how much has to be released afterwards.
+    // A file larger than the whole budget waits until nothing else is in flight.
+    pub fn acquire(&self, bytes: u64) -> u64 {
+        if self.limit == 0 {
+            return 0;
+        }
+
+        let bytes = bytes.min(self.limit);
+        let (used, freed) = &*self.used;
+        let mut used = used.lock().unwrap();
+        while *used + bytes > self.limit {
+            used = freed.wait(used).unwrap();
+        }
+        *used += bytes;
+
+        bytes
+    }
+
+    pub fn release(&self, bytes: u64) {
+        if bytes == 0 {
+            return;
+        }
+
+        let (used, freed) = &*self.used;
+        *used.lock().unwrap() -= bytes;
+        freed.notify_all();
+    }
+}
@@ -0,0 +1,99 @@
+use anyhow::{anyhow, Result};
+use indicatif::ProgressBar;
+use md5::{Digest, Md5};
+use std::{collections::HashSet, fs};
+use unicode_normalization::UnicodeNormalization;
+
+use crate::{
+    exit::ExitCode, get_stable_installs, i18n::tr, options::Options, paths, read_stable_beatmaps,
+    theme,
+};
+
+// Compares osu!.db with the Songs folder it describes, so the stable library can be cleaned up
+// before it's imported: folders osu!.db doesn't know about, difficulties whose .osu is gone, and
+// .osu files edited since osu!stable last scanned them
+pub fn check_stable(options: &Options) -> Result<ExitCode> {
+    // A scanned Songs folder matches itself by definition
+    if options.songs.is_some() {
+        return Err(anyhow!(tr!("error-check-stable-songs")));
+    }
+
+    let (mut orphans, mut missing, mut changed) = (0, 0, 0);
+    for install in get_stable_installs(options)? {
+        println!(
+            "{}",
+            tr!(
+                "stable-songs-path",
+                path = format!("{:?}", install.songs_path)
+            )
+        );
+
+        let beatmaps = read_stable_beatmaps(options, &[install.clone()])?;
+        let songs_path = paths::extended(&install.songs_path);
+
+        let bar = ProgressBar::new(beatmaps.len() as u64)
+            .with_prefix(tr!("bar-checking"))
+            .with_style(theme::bar_style(
+                "{prefix} [{wide_bar}] {percent:>3}% {pos:>8}/{len:8}",
+            ));
+        for bm in &beatmaps {
+            bar.inc(1);
+
+            let set_path = paths::resolve(&songs_path, &bm.folder_name);
+            let osu_path = paths::resolve(&set_path, &bm.beatmap_file_name);
+            let folder = bm.folder_name.as_str();
+            let file = bm.beatmap_file_name.as_str();
+
+            match fs::read(&osu_path) {
+                Err(_) => {
+                    missing += 1;
+                    bar.println(tr!("check-stable-missing", folder = folder, file = file));
+                }
+                Ok(contents) if format!("{:x}", Md5::digest(&contents)) != bm.hash => {
+                    changed += 1;
+                    bar.println(tr!("check-stable-changed", folder = folder, file = file));
+                }
+                Ok(_) => {}
+            }
+        }
+        bar.finish_and_clear();
+
+        // Names can come back from the filesystem decomposed, see `paths::resolve`
+        let known: HashSet<String> = beatmaps
+            .iter()
+            .map(|bm| bm.folder_name.nfc().collect())
+            .collect();
+        let mut folders = fs::read_dir(&songs_path)?
+            .flatten()
+            .filter(|entry| entry.path().is_dir())
+            .map(|entry| {
+                entry
+                    .file_name()
+                    .to_string_lossy()
+                    .nfc()
+                    .collect::<String>()
+            })
+            .filter(|name| !known.contains(name))
+            .collect::<Vec<_>>();
+        folders.sort();
+        for folder in folders {
+            orphans += 1;
+            println!("{}", tr!("check-stable-orphan", folder = folder));
+        }
+    }
+
+    let summary = tr!(
+        "check-stable-summary",
+        orphans = orphans,
+        missing = missing,
+        changed = changed
+    );
+
+    if orphans + missing + changed > 0 {
+        println!("{}", theme::warning(&summary));
+        Ok(ExitCode::PartialSuccess)
+    } else {
+        println!("{}", theme::success(&summary));
+        Ok(ExitCode::Success)
+    }
+}
@@ -0,0 +1,106 @@
+use anyhow::Result;
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+use walkdir::WalkDir;
+
+use crate::{
+    exit::ExitCode, get_lazer_path, get_stable_installs, i18n::tr, options::Options, paths,
+    progress_file,
+};
+
+// What checking the link mode leaves behind when osu-link is killed halfway through it
+const TEST_FILES: [&str; 2] = ["_link_test", "_symlink_test"];
+
+// Removes what osu-link leaves behind when it crashes or is killed: the files it tests linking
+// with, the .osz files packaged for --via-lazer, a half-written progress file, and directories of
+// osu!lazer's file store that were created for a file that was never linked. Backups are kept, as
+// `undo` needs them.
+pub fn clean(options: &Options) -> Result<ExitCode> {
+    let lazer_path = paths::extended(&get_lazer_path(options)?);
+    let mut leftovers: Vec<PathBuf> = TEST_FILES
+        .iter()
+        .map(|name| lazer_path.join(name))
+        .collect();
+    // osu!stable is only needed for its Songs folder, so there's nothing to clean there without one
+    for install in get_stable_installs(options).unwrap_or_default() {
+        let songs_path = paths::extended(&install.songs_path);
+        leftovers.extend(TEST_FILES.iter().map(|name| songs_path.join(name)));
+    }
+    let temp_path = env::temp_dir().join("osu-link");
+    leftovers.push(temp_path.clone());
+    // Only there if osu-link was killed while writing the progress file
+    if let Some(path) = progress_file::path() {
+        leftovers.push(path.with_extension("json.tmp"));
+    }
+    leftovers.retain(|path| fs::symlink_metadata(path).is_ok());
+    leftovers.extend(empty_store_dirs(&lazer_path));
+
+    let mut removed = 0;
+    for path in &leftovers {
+        // Store directories are only removed while still empty, in case osu!lazer is running
+        let res = if *path == temp_path {
+            fs::remove_dir_all(path)
+        } else if path.is_dir() {
+            fs::remove_dir(path)
+        } else {
+            fs::remove_file(path)
+        };
+        match res {
+            Ok(()) => {
+                println!("{}", tr!("clean-removed", path = format!("{:?}", path)));
+                removed += 1;
+            }
+            Err(e) => eprintln!(
+                "{}",
+                tr!(
+                    "error-clean",
+                    path = format!("{:?}", path),
+                    error = e.to_string()
+                )
+            ),
+        }
+    }
+
+    if leftovers.is_empty() {
+        println!("{}", tr!("clean-none"));
+        Ok(ExitCode::Success)
+    } else {
+        println!("{}", tr!("clean-summary", count = removed));
+        Ok(if removed == leftovers.len() {
+            ExitCode::Success
+        } else {
+            ExitCode::PartialSuccess
+        })
+    }
+}
+
+// files/a/ab directories with nothing in them, deepest first so their parents empty out too
+fn empty_store_dirs(lazer_path: &Path) -> Vec<PathBuf> {
+    let files_path = lazer_path.join("files");
+    if !files_path.is_dir() {
+        return vec![];
+    }
+
+    let mut dirs = vec![];
+    for entry in WalkDir::new(&files_path)
+        .min_depth(1)
+        .max_depth(2)
+        .contents_first(true)
+        .into_iter()
+        .flatten()
+    {
+        let path = entry.path();
+        let emptied = |dir: &Path| {
+            fs::read_dir(dir).map_or(false, |mut entries| {
+                entries.all(|entry| entry.map_or(false, |entry| dirs.contains(&entry.path())))
+            })
+        };
+        if entry.file_type().is_dir() && emptied(path) {
+            dirs.push(path.to_path_buf());
+        }
+    }
+
+    dirs
+}
@@ -0,0 +1,72 @@
+use anyhow::{Context, Result};
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Write},
+    path::Path,
+};
+
+use crate::stable_db::{DbReader, DbWriter};
+
+// What osu!stable writes collection.db with when creating it
+pub const DEFAULT_VERSION: i32 = 20150203;
+
+// collection.db, which osu!lazer (as of LAST_MIGRATION_ID) keeps in osu!stable's format too
+pub struct CollectionDb {
+    pub version: i32,
+    pub collections: Vec<Collection>,
+}
+
+// A named list of beatmaps, by the MD5 of their .osu
+pub struct Collection {
+    pub name: String,
+    pub hashes: Vec<String>,
+}
+
+impl Default for CollectionDb {
+    fn default() -> Self {
+        Self {
+            version: DEFAULT_VERSION,
+            collections: vec![],
+        }
+    }
+}
+
+pub fn read(path: &Path) -> Result<CollectionDb> {
+    let fd = File::open(path)
+        .with_context(|| format!("Failed to open the collections at {:?}", path))?;
+    let mut reader = DbReader(BufReader::new(fd));
+
+    let version = reader.i32()?;
+    let mut collections = vec![];
+    for _ in 0..reader.count()? {
+        let name = reader.string()?;
+        let hashes = (0..reader.count()?)
+            .map(|_| reader.string())
+            .collect::<io::Result<_>>()?;
+        collections.push(Collection { name, hashes });
+    }
+
+    Ok(CollectionDb {
+        version,
+        collections,
+    })
+}
+
+pub fn write(path: &Path, db: &CollectionDb) -> Result<()> {
+    let fd = File::create(path)
+        .with_context(|| format!("Failed to write the collections at {:?}", path))?;
+    let mut writer = DbWriter(BufWriter::new(fd));
+
+    writer.i32(db.version)?;
+    writer.count(db.collections.len())?;
+    for collection in &db.collections {
+        writer.string(&collection.name)?;
+        writer.count(collection.hashes.len())?;
+        for hash in &collection.hashes {
+            writer.string(hash)?;
+        }
+    }
+    writer.0.flush()?;
+
+    Ok(())
+}
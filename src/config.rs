@@ -0,0 +1,106 @@
+use anyhow::{Context, Result};
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+// Settings that persist between runs, read from `osu-link.cfg` in the OS config directory. Same
+// `key = value` format as osu!'s own config files, with `#` starting a comment:
+//   language = ja
+//   notify = true
+//   webhook = https://discord.com/api/webhooks/...
+//   profile.test = /home/me/osu-test
+//   stable = C:\Games\osu!
+//   tick_chars = |/-\
+//   read_threads = 2
+#[derive(Default)]
+pub struct Config {
+    pub language: Option<String>,
+    pub notify: bool,
+    pub webhook: Option<String>,
+    // The osu!stable directory picked when it couldn't be detected
+    pub stable: Option<PathBuf>,
+    // osu!lazer data directories by name, for --profile
+    pub profiles: HashMap<String, PathBuf>,
+    // indicatif templates and characters for the progress bars, see `theme`
+    pub bar_template: Option<String>,
+    pub spinner_template: Option<String>,
+    pub bar_chars: Option<String>,
+    pub tick_chars: Option<String>,
+    // Defaults for --read-threads and --hash-threads
+    pub read_threads: Option<usize>,
+    pub hash_threads: Option<usize>,
+}
+
+impl Config {
+    pub fn path() -> Option<PathBuf> {
+        dirs::config_dir().map(|path| path.join("osu-link").join("osu-link.cfg"))
+    }
+
+    pub fn load() -> Result<Self> {
+        let mut config = Self::default();
+        let path = match Self::path() {
+            Some(path) if path.exists() => path,
+            _ => return Ok(config),
+        };
+
+        let contents = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read the config at {:?}", path))?;
+        for line in contents.lines().map(str::trim) {
+            if line.starts_with('#') {
+                continue;
+            }
+
+            if let Some((key, value)) = line.split_once('=') {
+                let value = value.trim().to_string();
+                match key.trim() {
+                    "language" => config.language = Some(value),
+                    "notify" => config.notify = value == "true",
+                    "webhook" => config.webhook = Some(value),
+                    "stable" => config.stable = Some(value.into()),
+                    "bar_template" => config.bar_template = Some(value),
+                    "spinner_template" => config.spinner_template = Some(value),
+                    "bar_chars" => config.bar_chars = Some(value),
+                    "tick_chars" => config.tick_chars = Some(value),
+                    "read_threads" => config.read_threads = value.parse().ok(),
+                    "hash_threads" => config.hash_threads = value.parse().ok(),
+                    key => {
+                        if let Some(name) = key.strip_prefix("profile.") {
+                            config.profiles.insert(name.to_string(), value.into());
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(config)
+    }
+
+    // Remembers the osu!stable directory, keeping the rest of the config file as it is
+    pub fn save_stable(stable_path: &Path) -> Result<()> {
+        let path = Self::path().context("No config directory?")?;
+        let contents = if path.exists() {
+            fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read the config at {:?}", path))?
+        } else {
+            String::new()
+        };
+
+        let mut lines = contents
+            .lines()
+            .filter(|line| {
+                line.split_once('=')
+                    .map_or(true, |(key, _)| key.trim() != "stable")
+            })
+            .map(str::to_string)
+            .collect::<Vec<_>>();
+        lines.push(format!("stable = {}", stable_path.display()));
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, lines.join("\n") + "\n")
+            .with_context(|| format!("Failed to write the config at {:?}", path))
+    }
+}
@@ -0,0 +1,319 @@
+use anyhow::{anyhow, Context, Result};
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::{sleep, spawn, JoinHandle},
+    time::Duration,
+};
+
+use crate::{
+    exit::ExitCode,
+    i18n::tr,
+    log::{log, Level},
+    progress_file::{Probe, Progress},
+    State,
+};
+
+// Lets a running import be asked how far it is, and cancelled, by `osu-link status` or a
+// supervisor. Each connection sends one command on a line, after the import's token and a space,
+// and gets one line back:
+// - `ping`: answered `pong`, to tell whether an import is listening
+// - `status`: the progress, as JSON in the same shape as progress.json
+// - `cancel`: stops starting on new beatmaps, and rolls the import back once the ones underway
+//   are done, answering `ok`
+pub struct Control {
+    done: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Control {
+    // None if another import is already listening, or the socket couldn't be created
+    pub fn start(state: &State) -> Option<Self> {
+        if request("ping").map_or(false, |reply| reply == "pong") {
+            log!(Level::Debug, "Another osu-link is listening for commands");
+            return None;
+        }
+        let listener = match transport::bind() {
+            Ok(listener) => listener,
+            Err(e) => {
+                log!(Level::Debug, "Couldn't listen for commands: {}", e);
+                return None;
+            }
+        };
+        let token = match token::create() {
+            Ok(token) => token,
+            Err(e) => {
+                log!(Level::Debug, "Couldn't write the control token: {}", e);
+                transport::unbind();
+                return None;
+            }
+        };
+
+        let probe = Probe::new(state);
+        let cancelled = state.cancelled.clone();
+        let done = Arc::new(AtomicBool::new(false));
+        let thread_done = done.clone();
+        let thread = spawn(move || {
+            while !thread_done.load(Ordering::Relaxed) {
+                match listener.accept() {
+                    Ok((stream, _)) => {
+                        if let Err(e) = answer(stream, &token, &probe, &cancelled) {
+                            log!(Level::Debug, "Couldn't answer a command: {}", e);
+                        }
+                    }
+                    Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                        sleep(Duration::from_millis(100))
+                    }
+                    Err(e) => {
+                        log!(Level::Debug, "Stopped listening for commands: {}", e);
+                        break;
+                    }
+                }
+            }
+            transport::unbind();
+            token::remove();
+        });
+
+        Some(Self {
+            done,
+            thread: Some(thread),
+        })
+    }
+
+    pub fn finish(mut self) {
+        self.join();
+    }
+
+    fn join(&mut self) {
+        self.done.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+// Stops listening if the import bails out early
+impl Drop for Control {
+    fn drop(&mut self) {
+        self.join();
+    }
+}
+
+fn answer(
+    stream: transport::Stream,
+    token: &str,
+    probe: &Probe,
+    cancelled: &AtomicBool,
+) -> Result<()> {
+    // Accepted from a non-blocking listener, which some platforms pass on
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(Duration::from_secs(1)))?;
+
+    let mut reader = BufReader::new(stream);
+    let mut command = String::new();
+    reader.read_line(&mut command)?;
+    let command = match command.trim().split_once(' ') {
+        Some((given, command)) if given == token => command,
+        _ => {
+            writeln!(reader.get_mut(), "error: wrong token")?;
+            return Ok(());
+        }
+    };
+    let reply = match command {
+        "ping" => "pong".to_string(),
+        "status" => serde_json::to_string(&probe.progress(false))?,
+        "cancel" => {
+            cancelled.store(true, Ordering::Relaxed);
+            "ok".to_string()
+        }
+        other => format!("error: unknown command {:?}", other),
+    };
+    writeln!(reader.get_mut(), "{}", reply)?;
+
+    Ok(())
+}
+
+// Sends a command to the running import, and returns its reply
+fn request(command: &str) -> Result<String> {
+    let token = token::read()?;
+    let mut stream = transport::connect()?;
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    writeln!(stream, "{} {}", token, command)?;
+
+    let mut reply = String::new();
+    BufReader::new(stream).read_line(&mut reply)?;
+    let reply = reply.trim();
+    match reply.strip_prefix("error: ") {
+        Some(error) => Err(anyhow!("{}", error)),
+        None => Ok(reply.to_string()),
+    }
+}
+
+// Asks the running import for its progress, or to cancel
+pub fn status(cancel: bool, json: bool) -> Result<ExitCode> {
+    if !request("ping").map_or(false, |reply| reply == "pong") {
+        println!("{}", tr!("status-not-running"));
+        return Ok(ExitCode::PathNotFound);
+    }
+    let reply = request(if cancel { "cancel" } else { "status" })?;
+    let reply = reply.as_str();
+    if cancel || json {
+        println!("{}", reply);
+        return Ok(ExitCode::Success);
+    }
+
+    let progress: Progress = serde_json::from_str(reply).context("Unexpected reply")?;
+    println!(
+        "{}",
+        tr!(
+            "status-progress",
+            stage = progress.stage,
+            percent = format!("{:.1}", progress.percent),
+            set = progress.current_set,
+            failures = progress.failures
+        )
+    );
+    for stage in &progress.stages {
+        println!(
+            "  {:<20} {:>8}/{:<8} {:>5.1}%",
+            stage.name, stage.position, stage.length, stage.percent
+        );
+    }
+
+    Ok(ExitCode::Success)
+}
+
+// A secret written to control.token in osu-link's data directory, readable only by the user who
+// started the import. The Windows transport's port can be connected to by anyone on the machine,
+// and after a crash may belong to another program by then, so commands without it are refused.
+mod token {
+    use rand::{thread_rng, Rng};
+    use std::{
+        fmt::Write as _,
+        fs::{self, OpenOptions},
+        io::{self, Write},
+        path::PathBuf,
+    };
+
+    fn path() -> io::Result<PathBuf> {
+        dirs::data_local_dir()
+            .map(|path| path.join("osu-link").join("control.token"))
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "No data directory?"))
+    }
+
+    pub fn create() -> io::Result<String> {
+        let mut bytes = [0u8; 16];
+        thread_rng().fill(&mut bytes);
+        let mut token = String::with_capacity(2 * bytes.len());
+        for byte in bytes {
+            write!(token, "{:02x}", byte).unwrap();
+        }
+
+        let path = path()?;
+        fs::create_dir_all(path.parent().unwrap())?;
+        let _ = fs::remove_file(&path);
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        // Inherits the data directory's per-user ACL on Windows
+        #[cfg(target_family = "unix")]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        options.open(&path)?.write_all(token.as_bytes())?;
+
+        Ok(token)
+    }
+
+    pub fn read() -> io::Result<String> {
+        Ok(fs::read_to_string(path()?)?.trim().to_string())
+    }
+
+    pub fn remove() {
+        if let Ok(path) = path() {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+// A Unix socket in osu-link's data directory
+#[cfg(target_family = "unix")]
+mod transport {
+    use std::{
+        fs, io,
+        os::unix::net::{UnixListener, UnixStream},
+        path::PathBuf,
+    };
+
+    pub type Stream = UnixStream;
+
+    fn path() -> io::Result<PathBuf> {
+        dirs::data_local_dir()
+            .map(|path| path.join("osu-link").join("control.sock"))
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "No data directory?"))
+    }
+
+    pub fn bind() -> io::Result<UnixListener> {
+        let path = path()?;
+        fs::create_dir_all(path.parent().unwrap())?;
+        // Left behind by an import that crashed, as nothing answered on it
+        let _ = fs::remove_file(&path);
+
+        let listener = UnixListener::bind(&path)?;
+        listener.set_nonblocking(true)?;
+        Ok(listener)
+    }
+
+    pub fn connect() -> io::Result<UnixStream> {
+        UnixStream::connect(path()?)
+    }
+
+    pub fn unbind() {
+        if let Ok(path) = path() {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+// std has no named pipes, so Windows gets a loopback TCP socket instead, on whichever port was
+// free, which is written to control.port in osu-link's data directory
+#[cfg(target_family = "windows")]
+mod transport {
+    use std::{
+        fs, io,
+        net::{Ipv4Addr, TcpListener, TcpStream},
+        path::PathBuf,
+    };
+
+    pub type Stream = TcpStream;
+
+    fn path() -> io::Result<PathBuf> {
+        dirs::data_local_dir()
+            .map(|path| path.join("osu-link").join("control.port"))
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "No data directory?"))
+    }
+
+    pub fn bind() -> io::Result<TcpListener> {
+        let path = path()?;
+        fs::create_dir_all(path.parent().unwrap())?;
+
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?;
+        listener.set_nonblocking(true)?;
+        fs::write(&path, listener.local_addr()?.port().to_string())?;
+        Ok(listener)
+    }
+
+    pub fn connect() -> io::Result<TcpStream> {
+        let port: u16 = fs::read_to_string(path()?)?
+            .trim()
+            .parse()
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Invalid port"))?;
+        TcpStream::connect((Ipv4Addr::LOCALHOST, port))
+    }
+
+    pub fn unbind() {
+        if let Ok(path) = path() {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
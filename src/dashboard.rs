@@ -0,0 +1,265 @@
+use anyhow::Result;
+use crossterm::{
+    event::{self, Event, KeyCode, KeyModifiers},
+    execute,
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use indicatif::{HumanBytes, ProgressBar};
+use itertools::Itertools;
+use std::{
+    io::{stdout, Stdout},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::{spawn, JoinHandle},
+    time::{Duration, Instant},
+};
+use tui::{
+    backend::CrosstermBackend,
+    layout::{Constraint, Direction, Layout},
+    style::{Color, Style},
+    widgets::{Block, Borders, Gauge, List, ListItem, Paragraph},
+    Terminal,
+};
+
+use crate::{exit::ExitCode, i18n::tr, report::Report, State};
+
+const STAGES: [&str; 4] = [
+    "stage-processing-beatmaps",
+    "stage-inserting-beatmaps",
+    "stage-processing-files",
+    "stage-inserting-files",
+];
+
+// Full-screen replacement for the stacked progress bars, which reads their progress instead of
+// letting them draw. Warnings and errors go to a scrollable pane rather than between the bars.
+pub struct Dashboard {
+    done: Arc<AtomicBool>,
+    thread: Option<JoinHandle<Result<()>>>,
+}
+
+impl Dashboard {
+    pub fn start(state: &State) -> Self {
+        let bars = [
+            state.progress_bars.beatmap.clone(),
+            state.progress_bars.beatmap_insert.clone(),
+            state.progress_bars.hash.clone(),
+            state.progress_bars.hash_insert.clone(),
+        ];
+        let report = state.report.clone();
+        report.set_quiet(true);
+
+        let done = Arc::new(AtomicBool::new(false));
+        let thread_done = done.clone();
+        let thread = spawn(move || {
+            enable_raw_mode()?;
+            execute!(stdout(), EnterAlternateScreen)?;
+            let mut terminal = Terminal::new(CrosstermBackend::new(stdout()))?;
+
+            let res = draw_loop(&mut terminal, &bars, &report, &thread_done);
+
+            disable_raw_mode()?;
+            execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+            terminal.show_cursor()?;
+            report.set_quiet(false);
+
+            res
+        });
+
+        Self {
+            done,
+            thread: Some(thread),
+        }
+    }
+
+    // Switches to the final summary, and returns once the user closes it
+    pub fn finish(mut self) -> Result<()> {
+        self.join()
+    }
+
+    fn join(&mut self) -> Result<()> {
+        self.done.store(true, Ordering::Relaxed);
+        match self.thread.take() {
+            Some(thread) => thread.join().unwrap(),
+            None => Ok(()),
+        }
+    }
+}
+
+// Leaves the terminal usable if the import bails out early
+impl Drop for Dashboard {
+    fn drop(&mut self) {
+        let _ = self.join();
+    }
+}
+
+struct Throughput {
+    since: Instant,
+    files: u64,
+    bytes: u64,
+    files_per_sec: f64,
+    bytes_per_sec: f64,
+}
+
+impl Throughput {
+    fn new() -> Self {
+        Self {
+            since: Instant::now(),
+            files: 0,
+            bytes: 0,
+            files_per_sec: 0.0,
+            bytes_per_sec: 0.0,
+        }
+    }
+
+    // Averaged over the last second or so, so the numbers don't jump around every frame
+    fn update(&mut self, files: u64, bytes: u64) {
+        let elapsed = self.since.elapsed().as_secs_f64();
+        if elapsed < 1.0 {
+            return;
+        }
+
+        self.files_per_sec = files.saturating_sub(self.files) as f64 / elapsed;
+        self.bytes_per_sec = bytes.saturating_sub(self.bytes) as f64 / elapsed;
+        self.since = Instant::now();
+        self.files = files;
+        self.bytes = bytes;
+    }
+}
+
+fn draw_loop(
+    terminal: &mut Terminal<CrosstermBackend<Stdout>>,
+    bars: &[ProgressBar; 4],
+    report: &Report,
+    done: &AtomicBool,
+) -> Result<()> {
+    let started = Instant::now();
+    let mut throughput = Throughput::new();
+    // Lines scrolled up from the newest message
+    let mut scroll = 0;
+
+    loop {
+        let finished = done.load(Ordering::Relaxed);
+        let messages = report.messages();
+        throughput.update(bars[3].position(), report.bytes_hashed());
+
+        terminal.draw(|f| {
+            let chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([
+                    Constraint::Length(12),
+                    Constraint::Length(3),
+                    Constraint::Min(3),
+                    Constraint::Length(1),
+                ])
+                .split(f.size());
+
+            if finished {
+                let elapsed = started.elapsed().as_secs();
+                let summary = tr!(
+                    "dashboard-summary",
+                    beatmaps = bars[1].position(),
+                    files = bars[3].position(),
+                    elapsed = format!("{}:{:02}", elapsed / 60, elapsed % 60),
+                    messages = messages.len(),
+                    bytes = HumanBytes(report.bytes_hashed()).to_string()
+                );
+                f.render_widget(
+                    Paragraph::new(summary).block(
+                        Block::default()
+                            .title(tr!("dashboard-summary-title"))
+                            .borders(Borders::ALL),
+                    ),
+                    chunks[0],
+                );
+            } else {
+                let rows = Layout::default()
+                    .direction(Direction::Vertical)
+                    .constraints([Constraint::Length(3); 4])
+                    .split(chunks[0]);
+
+                for ((stage, bar), row) in STAGES.iter().zip(bars).zip(rows) {
+                    let (position, length) = (bar.position(), bar.length());
+                    let ratio = if length > 0 {
+                        (position as f64 / length as f64).min(1.0)
+                    } else {
+                        0.0
+                    };
+
+                    f.render_widget(
+                        Gauge::default()
+                            .block(Block::default().title(tr!(stage)).borders(Borders::ALL))
+                            .gauge_style(Style::default().fg(Color::Cyan))
+                            .ratio(ratio)
+                            .label(format!("{}/{}", position, length)),
+                        row,
+                    );
+                }
+            }
+
+            f.render_widget(
+                Paragraph::new(tr!(
+                    "dashboard-throughput",
+                    files = format!("{:.0}", throughput.files_per_sec),
+                    bytes = HumanBytes(throughput.bytes_per_sec as u64).to_string()
+                ))
+                .block(
+                    Block::default()
+                        .title(tr!("dashboard-throughput-title"))
+                        .borders(Borders::ALL),
+                ),
+                chunks[1],
+            );
+
+            let height = chunks[2].height.saturating_sub(2) as usize;
+            let end = messages.len().saturating_sub(scroll);
+            let start = end.saturating_sub(height);
+            let items = messages[start..end]
+                .iter()
+                .map(|message| ListItem::new(message.as_str()))
+                .collect_vec();
+            f.render_widget(
+                List::new(items).block(
+                    Block::default()
+                        .title(tr!("dashboard-messages-title", count = messages.len()))
+                        .borders(Borders::ALL),
+                ),
+                chunks[2],
+            );
+
+            f.render_widget(
+                Paragraph::new(if finished {
+                    tr!("dashboard-help-finished")
+                } else {
+                    tr!("dashboard-help-running")
+                }),
+                chunks[3],
+            );
+        })?;
+
+        if !event::poll(Duration::from_millis(250))? {
+            continue;
+        }
+
+        if let Event::Key(key) = event::read()? {
+            let max_scroll = messages.len().saturating_sub(1);
+            match key.code {
+                KeyCode::Up => scroll = (scroll + 1).min(max_scroll),
+                KeyCode::Down => scroll = scroll.saturating_sub(1),
+                KeyCode::PageUp => scroll = (scroll + 10).min(max_scroll),
+                KeyCode::PageDown => scroll = scroll.saturating_sub(10),
+                KeyCode::Char('q') | KeyCode::Esc if finished => return Ok(()),
+                // Raw mode swallows the signal, so cancelling has to be done by hand. Nothing has
+                // been committed yet, so lazer's database is left untouched.
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    disable_raw_mode()?;
+                    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+                    terminal.show_cursor()?;
+                    std::process::exit(ExitCode::Aborted as i32);
+                }
+                _ => {}
+            }
+        }
+    }
+}
@@ -1,489 +1,1042 @@
-use anyhow::Result;
-use chrono::{TimeZone, Utc};
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
 use itertools::Itertools;
-use libosu::{
-    beatmap::Beatmap,
-    db::DbBeatmap,
-    events::Event,
-    prelude::{Mode, Mods},
-    timing::{TimingPointKind, UninheritedTimingInfo},
-};
-use rand::{thread_rng, Rng};
-use rusqlite::{params, Connection, Transaction};
+use rusqlite::{params, Connection, OptionalExtension, Params, Transaction};
+use sha2::{Digest, Sha256};
 use std::{
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
     convert::TryInto,
-    fmt::Write as FmtWrite,
-    sync::mpsc::{Receiver, Sender},
+    fs::{self, File},
+    io,
+    path::Path,
+    sync::mpsc::Receiver,
+};
+use unicode_normalization::UnicodeNormalization;
+
+use crate::processors::context::{BeatmapProcessed, HashProcessed, Processed};
+use crate::{
+    exit::{self, ExitCode},
+    i18n::tr,
+    library::{StableBeatmap, STATUS_NONE},
+    log::{log, Level},
+    manifest::{self, Entry},
+    options::{ConflictPolicy, Genre, Language},
+    paths,
+    report::FailureKind,
+    timings::Stage,
+    verify, State, WIN_TO_UNIX_EPOCH,
 };
-use walkdir::WalkDir;
 
-use crate::processors::context::{BeatmapProcessed, HashProcessed, HashRequest};
-use crate::{State, WIN_TO_UNIX_EPOCH};
+mod bulk;
+
+// The rows osu-link writes for each schema of osu!lazer's it supports, by the migration it's as of.
+// A migration changing them gets a module of its own, so the older schemas keep working.
+mod v20210912;
 
-pub fn insert_beatmaps(
+#[cfg(test)]
+mod tests;
+
+use bulk::Bulk;
+pub use v20210912::MIGRATION_ID;
+use v20210912::{
+    insert_beatmap_difficulty, insert_beatmap_info, insert_beatmap_metadata,
+    insert_beatmapset_info, load_metadata_ids, BeatmapInfoRefs, MetadataKey,
+};
+
+// The last stage of the pipeline, and the only one touching the database. Files can arrive before
+// the beatmap of their set has been inserted, in which case they wait for it.
+pub fn write(
     state: &State,
     transaction: &Transaction,
-    receiver: Receiver<BeatmapProcessed>,
-    hash_sender: Sender<HashRequest>,
+    receiver: Receiver<Processed>,
 ) -> Result<()> {
-    for beatmap in receiver {
-        state.progress_bars.beatmap_insert.set_message(format!(
-            "{: <7} - {: <7}",
-            beatmap.db_beatmap.beatmap_set_id, beatmap.db_beatmap.beatmap_id
-        ));
-        state.progress_bars.beatmap_insert.inc(1);
-
-        let res = insert_beatmap(state, transaction, &beatmap);
-        if let Err(err) = res {
-            state.progress_bars.beatmap_insert.println(format!(
-                "Error importing {}/{}",
-                beatmap.db_beatmap.folder_name, beatmap.db_beatmap.beatmap_file_name
-            ));
-            state
-                .progress_bars
-                .beatmap_insert
-                .println(format!("{}", err));
-        } else {
-            let res = res.unwrap();
+    // BeatmapSetInfo IDs of sets imported as local maps, by folder
+    let mut local_sets = HashMap::new();
+    // Every BeatmapMetadata row, so they can be deduplicated without scanning the table each time
+    let mut metadata_ids = load_metadata_ids(transaction)?;
+    // Online IDs of the sets lazer had before this import, which --on-conflict applies to
+    let existing_sets = load_online_set_ids(transaction)?;
+    // FileInfo IDs by hash, as shared audio and backgrounds come up again and again
+    let mut file_ids = load_file_ids(transaction)?;
+    // BeatmapSetInfo IDs of the sets whose folder is scanned, by folder
+    let mut scanned_sets = HashMap::new();
+    // Files of sets that haven't been inserted yet, by folder
+    let mut pending: HashMap<String, Vec<HashProcessed>> = HashMap::new();
+    // (BeatmapSetInfoID, path, hash) of each .osu, applied once every difficulty has been inserted
+    let mut beatmap_hashes = vec![];
+    // Hashes of the files already in the store that were checked to match
+    let mut verified = HashSet::new();
+    let mut bulk = if state.options.link.bulk_insert {
+        Some(Bulk::new(transaction)?)
+    } else {
+        None
+    };
 
-            if !beatmap.is_main {
-                continue;
-            }
+    // Beatmaps and files come down the same channel as they're processed, so rows of both kinds are
+    // written as they arrive, unless --deterministic. A file only waits for its set's row.
+    let processed: Box<dyn Iterator<Item = Processed>> = if state.options.link.deterministic {
+        Box::new(in_order(receiver).into_iter())
+    } else {
+        Box::new(receiver.into_iter())
+    };
+    for processed in processed {
+        match processed {
+            Processed::Beatmap(beatmap) => {
+                let beatmapset_info_id = match write_beatmap(
+                    state,
+                    transaction,
+                    bulk.as_mut(),
+                    &beatmap,
+                    &mut local_sets,
+                    &mut metadata_ids,
+                    &existing_sets,
+                )? {
+                    Some(id) if beatmap.is_main => id,
+                    _ => continue,
+                };
 
-            let mut bms_path = state.stable_songs_path.clone();
-            bms_path.push(&beatmap.db_beatmap.folder_name);
+                let folder_name = &beatmap.db_beatmap.folder_name;
+                scanned_sets.insert(folder_name.clone(), beatmapset_info_id);
+                for file in pending.remove(folder_name).unwrap_or_default() {
+                    write_file(
+                        state,
+                        transaction,
+                        beatmapset_info_id,
+                        &file,
+                        &mut file_ids,
+                        &mut beatmap_hashes,
+                        &mut verified,
+                    )?;
+                }
+            }
+            Processed::File(file) => match scanned_sets.get(&file.request.folder_name) {
+                Some(&beatmapset_info_id) => {
+                    write_file(
+                        state,
+                        transaction,
+                        beatmapset_info_id,
+                        &file,
+                        &mut file_ids,
+                        &mut beatmap_hashes,
+                        &mut verified,
+                    )?;
+                }
+                None => pending
+                    .entry(file.request.folder_name.clone())
+                    .or_default()
+                    .push(file),
+            },
+        }
+    }
 
-            for entry in WalkDir::new(&bms_path) {
-                let entry = entry?;
-                let path = entry.path();
+    // The hashes are set on the BeatmapInfo rows, which have to be in lazer's table by then
+    if let Some(bulk) = bulk {
+        state
+            .timings
+            .time(Stage::Inserting, || bulk.finish(transaction))?;
+    }
 
-                if !path.is_file() {
-                    continue;
-                }
+    // Whatever is still pending belongs to sets that failed to insert, which were reported already
+    for (beatmapset_info_id, path, hash) in beatmap_hashes {
+        state.timings.time(Stage::Inserting, || {
+            update_beatmap_hash(transaction, beatmapset_info_id, &path, &hash)
+        })?;
+    }
 
-                let clone = path.to_path_buf();
-                let stripped_path = clone.strip_prefix(&bms_path)?;
-
-                hash_sender
-                    .send(HashRequest {
-                        beatmap_id: beatmap.db_beatmap.beatmap_id,
-                        beatmapset_id: beatmap.db_beatmap.beatmap_set_id,
-                        folder_name: beatmap.db_beatmap.folder_name.clone(),
-                        file_name: beatmap.db_beatmap.beatmap_file_name.clone(),
-                        beatmapset_info_id: res,
-                        full_path: path.to_path_buf(),
-                        stripped_path: stripped_path.to_path_buf(),
-                    })
-                    .unwrap();
-
-                state.progress_bars.hash.inc_length(1);
-            }
-        };
+    // With every .osu registered, the sets' hashes can be computed like lazer does
+    for beatmapset_info_id in scanned_sets.into_values().unique() {
+        state.timings.time(Stage::Inserting, || {
+            update_beatmapset_hash(state, transaction, beatmapset_info_id)
+        })?;
     }
 
     Ok(())
 }
 
-pub fn insert_hashes(
+// --deterministic: waits for everything to be processed, then hands out the beatmaps sorted by path
+// followed by the files, so the rows get the same IDs whichever order the threads finished in
+fn in_order(receiver: Receiver<Processed>) -> Vec<Processed> {
+    let mut beatmaps = vec![];
+    let mut files = vec![];
+    for processed in receiver {
+        match processed {
+            Processed::Beatmap(beatmap) => beatmaps.push(beatmap),
+            Processed::File(file) => files.push(file),
+        }
+    }
+
+    beatmaps.sort_by(|a, b| {
+        let (a, b) = (&a.db_beatmap, &b.db_beatmap);
+        (&a.songs_path, &a.folder_name, &a.beatmap_file_name).cmp(&(
+            &b.songs_path,
+            &b.folder_name,
+            &b.beatmap_file_name,
+        ))
+    });
+    files.sort_by(|a, b| a.request.full_path.cmp(&b.request.full_path));
+
+    beatmaps
+        .into_iter()
+        .map(Processed::Beatmap)
+        .chain(files.into_iter().map(Processed::File))
+        .collect()
+}
+
+// Returns the BeatmapSetInfo ID of the beatmap's set, or None if it failed or was skipped, and was
+// reported
+fn write_beatmap(
     state: &State,
     transaction: &Transaction,
-    receiver: Receiver<HashProcessed>,
+    bulk: Option<&mut Bulk>,
+    beatmap: &BeatmapProcessed,
+    local_sets: &mut HashMap<String, i64>,
+    metadata_ids: &mut HashMap<MetadataKey, i64>,
+    existing_sets: &HashSet<u32>,
+) -> Result<Option<i64>> {
+    state.progress_bars.beatmap_insert.set_message(format!(
+        "{: <7} - {: <7}",
+        beatmap.db_beatmap.beatmap_set_id, beatmap.db_beatmap.beatmap_id
+    ));
+    state.progress_bars.beatmap_insert.inc(1);
+    if beatmap.is_main {
+        *state.current_set.lock().unwrap() = beatmap.db_beatmap.folder_name.clone();
+    }
+
+    let res = state.timings.time(Stage::Inserting, || {
+        insert_beatmap(
+            state,
+            transaction,
+            bulk,
+            beatmap,
+            local_sets,
+            metadata_ids,
+            existing_sets,
+        )
+    });
+    match res {
+        Ok(Some(id)) => {
+            log!(
+                Level::Debug,
+                "Imported {}/{} into BeatmapSetInfo {}",
+                beatmap.db_beatmap.folder_name,
+                beatmap.db_beatmap.beatmap_file_name,
+                id
+            );
+            Ok(Some(id))
+        }
+        Ok(None) => Ok(None),
+        // Every other beatmap would fail the same way
+        Err(err) if exit::code_of(&err) == ExitCode::DatabaseLocked => Err(err),
+        Err(err) => {
+            state.report.error(
+                &state.progress_bars.beatmap_insert,
+                tr!(
+                    "error-importing",
+                    folder = beatmap.db_beatmap.folder_name.as_str(),
+                    file = beatmap.db_beatmap.beatmap_file_name.as_str()
+                ),
+            );
+            state
+                .report
+                .error(&state.progress_bars.beatmap_insert, format!("{}", err));
+            state
+                .report
+                .add_failure(FailureKind::of(&err), Some(&beatmap.db_beatmap.folder_name));
+
+            Ok(None)
+        }
+    }
+}
+
+fn write_file(
+    state: &State,
+    transaction: &Transaction,
+    beatmapset_info_id: i64,
+    file: &HashProcessed,
+    file_ids: &mut HashMap<String, i64>,
+    beatmap_hashes: &mut Vec<(i64, String, String)>,
+    verified: &mut HashSet<String>,
 ) -> Result<()> {
-    for hash in receiver {
-        state.progress_bars.hash_insert.set_message(format!(
-            "{: <7} - {: <7}",
-            hash.request.beatmapset_id, hash.request.beatmap_id
-        ));
+    let bar = &state.progress_bars.hash_insert;
+    if bar.position() == 0 {
+        bar.disable_steady_tick();
+        bar.set_style(state.progress_styles.length_changing.clone());
+    }
+    bar.set_message(format!(
+        "{: <7} - {: <7}",
+        file.request.beatmapset_id, file.request.beatmap_id
+    ));
 
-        transaction.execute(
-            "INSERT OR IGNORE INTO FileInfo
-                 (Hash, ReferenceCount)
-             VALUES
-                 (?, ?)",
-            params![hash.hash, 0],
-        )?;
+    // Linked first, so a file that can't be isn't registered either
+    if let Err(err) = state
+        .timings
+        .time(Stage::Linking, || link_file(state, file, verified))
+    {
+        state.report.error(
+            bar,
+            tr!(
+                "error-linking",
+                folder = file.request.folder_name.as_str(),
+                file = file.request.file_name.as_str(),
+                error = err.to_string()
+            ),
+        );
+        state
+            .report
+            .add_failure(FailureKind::Link, Some(&file.request.folder_name));
+        bar.inc(1);
+        return Ok(());
+    }
+
+    log!(
+        Level::Debug,
+        "Linked {:?} as {}",
+        file.request.full_path,
+        file.hash
+    );
+
+    // Registered NFC-composed like osu!.db's names, which BeatmapInfo.Path is matched against, even
+    // where the filesystem hands them back decomposed (macOS)
+    let path: String = file.request.stripped_path.to_str().unwrap().nfc().collect();
+    let inserted = state.timings.time(Stage::Inserting, || {
+        insert_file(
+            transaction,
+            file_ids,
+            beatmapset_info_id,
+            &path,
+            &file.hash,
+            state.options.conflict_policy(),
+        )
+    })?;
+    if !inserted {
+        state.report.warn(
+            bar,
+            tr!(
+                "warning-file-conflict",
+                folder = file.request.folder_name.as_str(),
+                file = path.as_str()
+            ),
+        );
+        bar.inc(1);
+        return Ok(());
+    }
+    state.manifest.add(Entry {
+        set_id: state
+            .options
+            .uses_online_ids()
+            .then(|| file.request.beatmapset_id),
+        folder: file.request.folder_name.clone(),
+        file: path.clone(),
+        sha256: file.hash.clone(),
+        link_target: manifest::link_target(&file.hash),
+    });
+
+    if file
+        .request
+        .stripped_path
+        .extension()
+        .map_or(false, |ext| ext.eq_ignore_ascii_case("osu"))
+    {
+        beatmap_hashes.push((beatmapset_info_id, path, file.hash.clone()));
+    }
+
+    bar.inc(1);
+    Ok(())
+}
+
+fn insert_file(
+    transaction: &Transaction,
+    file_ids: &mut HashMap<String, i64>,
+    beatmapset_info_id: i64,
+    path: &str,
+    hash: &str,
+    policy: ConflictPolicy,
+) -> Result<bool> {
+    // A file of the same name the set already had in osu!lazer
+    let existing: Option<(i64, i64, String)> = transaction
+        .query_row(
+            "SELECT BeatmapSetFileInfo.ID, FileInfo.ID, FileInfo.Hash
+             FROM BeatmapSetFileInfo
+             JOIN FileInfo ON FileInfo.ID = BeatmapSetFileInfo.FileInfoID
+             WHERE BeatmapSetFileInfo.BeatmapSetInfoID = ? AND BeatmapSetFileInfo.Filename = ?",
+            params![beatmapset_info_id, path],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .optional()?;
+    if let Some((set_file_id, old_file_id, old_hash)) = existing {
+        if old_hash == hash {
+            return Ok(true);
+        }
+        if policy != ConflictPolicy::Overwrite {
+            return Ok(false);
+        }
 
         transaction.execute(
             "UPDATE FileInfo
-             SET ReferenceCount = ReferenceCount + 1
-             WHERE Hash = ?",
-            params![hash.hash],
-        )?;
-
-        let file_id: i64 = transaction.query_row(
-            "SELECT ID
-             FROM FileInfo
-             WHERE Hash = ?",
-            params![hash.hash],
-            |row| row.get(0),
+             SET ReferenceCount = ReferenceCount - 1
+             WHERE ID = ?",
+            params![old_file_id],
         )?;
-
         transaction.execute(
-            "INSERT INTO BeatmapSetFileInfo
-                 (BeatmapSetInfoID, FileInfoID, Filename)
-             VALUES
-                 (?, ?, ?)",
-            params![
-                hash.request.beatmapset_info_id,
-                file_id,
-                hash.request.stripped_path.to_str().unwrap()
-            ],
+            "DELETE FROM BeatmapSetFileInfo
+             WHERE ID = ?",
+            params![set_file_id],
         )?;
+        log!(
+            Level::Trace,
+            "DELETE BeatmapSetFileInfo ID={} Filename={:?}, UPDATE FileInfo ID={} Hash={} \
+             ReferenceCount-1",
+            set_file_id,
+            path,
+            old_file_id,
+            old_hash
+        );
+    }
 
-        if hash.request.stripped_path.to_str().unwrap() == hash.request.file_name {
+    let file_id = match file_ids.get(hash) {
+        Some(&file_id) => {
             transaction.execute(
-                "UPDATE BeatmapInfo
-                 SET Hash = ?
-                 WHERE OnlineBeatmapID = ?",
-                params![hash.hash, hash.request.beatmap_id],
+                "UPDATE FileInfo
+                 SET ReferenceCount = ReferenceCount + 1
+                 WHERE ID = ?",
+                params![file_id],
+            )?;
+            log!(
+                Level::Trace,
+                "UPDATE FileInfo ID={} Hash={} ReferenceCount+1",
+                file_id,
+                hash
+            );
+            file_id
+        }
+        None => {
+            let file_id = insert_returning_id(
+                transaction,
+                "INSERT INTO FileInfo
+                     (Hash, ReferenceCount)
+                 VALUES
+                     (?, ?)",
+                params![hash, 1],
             )?;
+            log!(
+                Level::Trace,
+                "INSERT FileInfo ID={} Hash={} ReferenceCount=1",
+                file_id,
+                hash
+            );
+            file_ids.insert(hash.to_string(), file_id);
+            file_id
         }
+    };
 
-        let mut path = state.lazer_path.clone();
-        path.push("files");
-        path.push(&hash.hash[..1]);
-        path.push(&hash.hash[..2]);
-        std::fs::create_dir_all(&path)?;
-        path.push(&hash.hash);
+    transaction.execute(
+        "INSERT INTO BeatmapSetFileInfo
+             (BeatmapSetInfoID, FileInfoID, Filename)
+         VALUES
+             (?, ?, ?)",
+        params![beatmapset_info_id, file_id, path],
+    )?;
+    log!(
+        Level::Trace,
+        "INSERT BeatmapSetFileInfo ID={} BeatmapSetInfoID={} FileInfoID={} Hash={} Filename={:?}",
+        transaction.last_insert_rowid(),
+        beatmapset_info_id,
+        file_id,
+        hash,
+        path
+    );
 
-        #[cfg(target_family = "unix")]
-        {
-            let read = std::fs::read_link(&path);
-            if read.is_err() && !path.exists() {
-                std::os::unix::fs::symlink(hash.request.full_path.clone(), path)?;
-            }
+    Ok(true)
+}
+
+fn update_beatmap_hash(
+    transaction: &Transaction,
+    beatmapset_info_id: i64,
+    path: &str,
+    hash: &str,
+) -> Result<()> {
+    transaction.execute(
+        "UPDATE BeatmapInfo
+         SET Hash = ?
+         WHERE BeatmapSetInfoID = ?
+           AND Path = ?",
+        params![hash, beatmapset_info_id, path],
+    )?;
+    log!(
+        Level::Trace,
+        "UPDATE BeatmapInfo BeatmapSetInfoID={} Path={:?} Hash={}",
+        beatmapset_info_id,
+        path,
+        hash
+    );
+
+    Ok(())
+}
+
+// lazer's hash of a set is the SHA-256 of its .osu files one after the other, ordered by name. Sets
+// without any keep the random hash they were inserted with, as lazer would hash the archive's name.
+fn update_beatmapset_hash(
+    state: &State,
+    transaction: &Transaction,
+    beatmapset_info_id: i64,
+) -> Result<()> {
+    let mut files = transaction
+        .prepare(
+            "SELECT sf.Filename, f.Hash
+             FROM BeatmapSetFileInfo sf
+             JOIN FileInfo f ON f.ID = sf.FileInfoID
+             WHERE sf.BeatmapSetInfoID = ?",
+        )?
+        .query_map([beatmapset_info_id], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    files.retain(|(filename, _)| filename.to_lowercase().ends_with(".osu"));
+    if files.is_empty() {
+        return Ok(());
+    }
+    files.sort_by(|(a, _), (b, _)| culture_cmp(a, b));
+
+    let lazer_path = paths::extended(&state.lazer_path);
+    let mut hasher = Sha256::new();
+    for (filename, hash) in &files {
+        let path = paths::store_file(&lazer_path, hash);
+        if let Err(e) = File::open(&path).and_then(|mut fd| io::copy(&mut fd, &mut hasher)) {
+            log!(
+                Level::Debug,
+                "Couldn't hash {:?} of set {}: {}",
+                filename,
+                beatmapset_info_id,
+                e
+            );
+            return Ok(());
         }
-        #[cfg(target_family = "windows")]
-        {
-            if !path.exists() {
-                std::fs::hard_link(hash.request.full_path.clone(), path)?;
-            }
+    }
+    let hash = format!("{:x}", hasher.finalize());
+
+    // Two folders with the same difficulties are one set to lazer, so the second keeps its random
+    // hash rather than breaking the unique index
+    let updated = transaction.execute(
+        "UPDATE OR IGNORE BeatmapSetInfo
+         SET Hash = ?
+         WHERE ID = ?",
+        params![hash, beatmapset_info_id],
+    )?;
+    log!(
+        Level::Trace,
+        "UPDATE BeatmapSetInfo ID={} Hash={} ({})",
+        beatmapset_info_id,
+        hash,
+        if updated > 0 { "updated" } else { "duplicate" }
+    );
+
+    Ok(())
+}
+
+// .NET's default string ordering, which lazer sorts the files with, closely enough for file names:
+// case only breaks ties, lower case first
+fn culture_cmp(a: &str, b: &str) -> Ordering {
+    a.to_lowercase()
+        .cmp(&b.to_lowercase())
+        .then_with(|| b.cmp(a))
+}
+
+fn link_file(state: &State, hash: &HashProcessed, verified: &mut HashSet<String>) -> Result<()> {
+    let path = paths::store_file(&paths::extended(&state.lazer_path), &hash.hash);
+    fs::create_dir_all(path.parent().unwrap())?;
+
+    match fs::symlink_metadata(&path) {
+        Err(_) => state.link_strategy.link(&hash.request.full_path, &path)?,
+        // Left behind by a beatmap since deleted or moved in osu!stable
+        Ok(metadata) if metadata.file_type().is_symlink() && !path.exists() => {
+            fs::remove_file(&path)?;
+            state.link_strategy.link(&hash.request.full_path, &path)?;
         }
+        Ok(_) => check_store_file(&path, hash, verified)?,
+    }
 
-        state.progress_bars.hash_insert.inc(1);
+    Ok(())
+}
+
+// A file already in the store is usually the same one, brought in for another set or an earlier
+// import. It's hashed once per run to make sure, since registering it again would leave lazer
+// using whatever corrupted or edited contents it has.
+fn check_store_file(
+    path: &Path,
+    hash: &HashProcessed,
+    verified: &mut HashSet<String>,
+) -> Result<()> {
+    if verified.contains(&hash.hash) {
+        return Ok(());
+    }
+    // A symlink to this very file can't differ
+    if fs::read_link(path).map_or(false, |target| target == hash.request.full_path) {
+        return Ok(());
     }
 
+    let actual = verify::hash_file(path)?;
+    if actual != hash.hash {
+        return Err(anyhow!(tr!(
+            "error-store-conflict",
+            path = format!("{:?}", path),
+            actual = actual
+        )));
+    }
+    verified.insert(hash.hash.clone());
+
     Ok(())
 }
 
 pub fn insert_beatmap(
     state: &State,
     transaction: &Transaction,
+    mut bulk: Option<&mut Bulk>,
     beatmap_context: &BeatmapProcessed,
-) -> Result<i64> {
-    let difficulty_id = insert_beatmap_difficulty(transaction, &beatmap_context.beatmap)?;
+    local_sets: &mut HashMap<String, i64>,
+    metadata_ids: &mut HashMap<MetadataKey, i64>,
+    existing_sets: &HashSet<u32>,
+) -> Result<Option<i64>> {
+    let db_beatmap = &beatmap_context.db_beatmap;
+    let policy = state.options.conflict_policy();
+    let mut online_ids = state.options.uses_online_ids();
+    let mut force = state.options.link.force;
+
+    // Another beatmap with the same online ID, from an earlier import or another folder in this one
+    if online_ids && !force {
+        if let Some(existing_hash) =
+            online_beatmap_hash(transaction, db_beatmap.beatmap_id, bulk.is_some())?
+        {
+            // The very same .osu, in another folder
+            let policy = if existing_hash.as_deref() == Some(beatmap_context.hash.as_str()) {
+                ConflictPolicy::Skip
+            } else {
+                policy
+            };
+            state.report.warn(
+                &state.progress_bars.beatmap_insert,
+                tr!(
+                    match policy {
+                        ConflictPolicy::Skip => "warning-duplicate-id-skipped",
+                        ConflictPolicy::Overwrite => "warning-duplicate-id-overwritten",
+                        ConflictPolicy::Duplicate => "warning-duplicate-id-duplicated",
+                    },
+                    folder = db_beatmap.folder_name.as_str(),
+                    file = db_beatmap.beatmap_file_name.as_str()
+                ),
+            );
+            match policy {
+                ConflictPolicy::Skip => return Ok(None),
+                ConflictPolicy::Overwrite => force = true,
+                ConflictPolicy::Duplicate => online_ids = false,
+            }
+        }
+    }
+
+    // A new difficulty for a set lazer already has. Skipping leaves the set as it is, only adding
+    // the difficulty to it.
+    let set_exists = online_ids && existing_sets.contains(&db_beatmap.beatmap_set_id);
+    if set_exists && policy == ConflictPolicy::Duplicate {
+        if beatmap_context.is_main {
+            state.report.warn(
+                &state.progress_bars.beatmap_insert,
+                tr!(
+                    "warning-set-duplicated",
+                    folder = db_beatmap.folder_name.as_str()
+                ),
+            );
+        }
+        online_ids = false;
+    }
+
+    // Removing it reads BeatmapInfo back, which staged rows would be missing from
+    let replaced_id = if force {
+        if let Some(bulk) = bulk.as_deref_mut() {
+            bulk.flush(transaction)?;
+        }
+        remove_beatmap_info(transaction, &beatmap_context.db_beatmap, online_ids)?
+    } else {
+        None
+    };
+
+    let difficulty_id =
+        insert_beatmap_difficulty(transaction, bulk.as_deref_mut(), &beatmap_context.beatmap)?;
     let metadata_id = insert_beatmap_metadata(
         transaction,
-        &state.db_online_connection,
+        bulk.as_deref_mut(),
+        metadata_ids,
+        state.db_online_connection.as_ref(),
         &beatmap_context.beatmap,
-    )?;
-    let beatmapset_info_id = insert_beatmapset_info(
-        transaction,
-        &beatmap_context.db_beatmap,
-        metadata_id,
-        beatmap_context.is_main,
+        &beatmap_context.event_files,
     )?;
 
+    let folder_name = &beatmap_context.db_beatmap.folder_name;
+    let beatmapset_info_id = match local_sets.get(folder_name) {
+        Some(id) if !online_ids => *id,
+        _ => {
+            let id = insert_beatmapset_info(
+                transaction,
+                &beatmap_context.db_beatmap,
+                metadata_id,
+                beatmap_context.is_main && (!set_exists || policy == ConflictPolicy::Overwrite),
+                online_ids,
+            )?;
+            if !online_ids {
+                local_sets.insert(folder_name.clone(), id);
+            }
+            id
+        }
+    };
+
+    // The set's files are all written again, now that its main beatmap is in
+    if beatmap_context.is_main && force {
+        remove_set_files(transaction, beatmapset_info_id)?;
+    }
+
+    if beatmap_context.is_main && state.set_dates_supported {
+        if let Some(online_db) = &state.db_online_connection {
+            update_beatmapset_dates(
+                transaction,
+                online_db,
+                beatmapset_info_id,
+                beatmap_context.db_beatmap.beatmap_set_id,
+            )?;
+        }
+    }
+
+    // Scores refer to beatmaps by ID, so the replacement takes over the old row's
     insert_beatmap_info(
         transaction,
-        &beatmap_context.beatmap,
-        &beatmap_context.db_beatmap,
-        beatmapset_info_id,
-        difficulty_id,
-        metadata_id,
+        bulk,
+        replaced_id,
+        beatmap_context,
+        BeatmapInfoRefs {
+            beatmapset_info_id,
+            difficulty_id,
+            metadata_id,
+        },
+        online_ids,
     )?;
 
-    Ok(beatmapset_info_id)
+    Ok(Some(beatmapset_info_id))
 }
 
-pub fn insert_beatmap_difficulty(tx: &Transaction, beatmap: &Beatmap) -> Result<i64> {
-    tx.execute(
-        "INSERT INTO BeatmapDifficulty
-             (ApproachRate,
-              CircleSize,
-              DrainRate,
-              OverallDifficulty,
-              SliderMultiplier,
-              SliderTickRate)
-         VALUES
-             (?, ?, ?, ?, ?, ?)",
-        params![
-            beatmap.difficulty.approach_rate,
-            beatmap.difficulty.circle_size,
-            beatmap.difficulty.hp_drain_rate,
-            beatmap.difficulty.overall_difficulty,
-            beatmap.difficulty.slider_multiplier,
-            beatmap.difficulty.slider_tick_rate,
-        ],
-    )?;
+// The MD5 hash of the beatmap lazer has with this online ID, if any, or that was staged with it
+fn online_beatmap_hash(
+    tx: &Transaction,
+    beatmap_id: u32,
+    staged: bool,
+) -> Result<Option<Option<String>>> {
+    let res = tx.query_row(
+        if staged {
+            "SELECT MD5Hash
+             FROM BeatmapInfo
+             WHERE OnlineBeatmapID = ?1
+             UNION ALL
+             SELECT MD5Hash
+             FROM temp.StagedBeatmapInfo
+             WHERE OnlineBeatmapID = ?1"
+        } else {
+            "SELECT MD5Hash
+             FROM BeatmapInfo
+             WHERE OnlineBeatmapID = ?"
+        },
+        [beatmap_id],
+        |row| row.get(0),
+    );
 
-    Ok(tx.last_insert_rowid())
+    match res {
+        Ok(hash) => Ok(Some(hash)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
 }
 
-pub fn insert_beatmap_metadata(
+// Removes the beatmap's existing row for --force, returning its ID. A set left without beatmaps
+// (always the case for local maps, which get a new set) is removed along with it.
+fn remove_beatmap_info(
     tx: &Transaction,
-    online_db: &Connection,
-    beatmap: &Beatmap,
-) -> Result<i64> {
-    let mapper_id: i64 = online_db
-        .query_row(
-            "SELECT user_id
-             FROM osu_beatmaps
-             WHERE beatmap_id = ?",
-            [beatmap.beatmap_id],
-            |row| row.get(0),
+    db_beatmap: &StableBeatmap,
+    online_ids: bool,
+) -> Result<Option<i64>> {
+    let res = if online_ids {
+        tx.query_row(
+            "SELECT ID, BaseDifficultyID, BeatmapSetInfoID
+             FROM BeatmapInfo
+             WHERE OnlineBeatmapID = ?",
+            [db_beatmap.beatmap_id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+    } else {
+        // By file name too, in case the .osu has changed since (see `update`)
+        tx.query_row(
+            "SELECT ID, BaseDifficultyID, BeatmapSetInfoID
+             FROM BeatmapInfo
+             WHERE MD5Hash = ?
+                OR Path = ?
+             LIMIT 1",
+            [&db_beatmap.hash, &db_beatmap.beatmap_file_name],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
         )
-        .unwrap_or(0);
+    };
+    let (id, difficulty_id, beatmapset_info_id): (i64, i64, i64) = match res {
+        Ok(row) => row,
+        Err(rusqlite::Error::QueryReturnedNoRows) => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
 
-    let mut background: Option<String> = None;
-    let mut video: Option<String> = None;
+    tx.execute("DELETE FROM BeatmapInfo WHERE ID = ?", [id])?;
+    tx.execute(
+        "DELETE FROM BeatmapDifficulty WHERE ID = ?",
+        [difficulty_id],
+    )?;
+    log!(
+        Level::Trace,
+        "DELETE BeatmapInfo ID={} BaseDifficultyID={} BeatmapSetInfoID={}",
+        id,
+        difficulty_id,
+        beatmapset_info_id
+    );
 
-    for event in &beatmap.events {
-        match event {
-            Event::Background(bg) => {
-                background = Some(bg.filename.clone());
-                break;
-            }
-            Event::Video(vid) => {
-                video = Some(vid.filename.clone());
-                break;
-            }
-            _ => {
-                continue;
-            }
-        }
+    let remaining: i64 = tx.query_row(
+        "SELECT COUNT(*)
+         FROM BeatmapInfo
+         WHERE BeatmapSetInfoID = ?",
+        [beatmapset_info_id],
+        |row| row.get(0),
+    )?;
+    if remaining == 0 {
+        remove_set_files(tx, beatmapset_info_id)?;
+        tx.execute(
+            "DELETE FROM BeatmapSetInfo WHERE ID = ?",
+            [beatmapset_info_id],
+        )?;
+        log!(
+            Level::Trace,
+            "DELETE BeatmapSetInfo ID={}",
+            beatmapset_info_id
+        );
     }
 
-    let params = params![
-        beatmap.artist,
-        beatmap.artist_unicode,
-        beatmap.audio_filename,
-        beatmap.creator,
-        background,
-        beatmap.preview_time.0,
-        beatmap.source,
-        beatmap.tags.join(" "),
-        beatmap.title,
-        beatmap.title_unicode,
-        video,
-        mapper_id
-    ];
+    Ok(Some(id))
+}
 
-    let res = tx.query_row(
-        "SELECT ID
-         FROM BeatmapMetadata
-         WHERE Artist = ?
-           AND ArtistUnicode = ?
-           AND AudioFile = ?
-           AND Author = ?
-           AND BackgroundFile = ?
-           AND PreviewTime = ?
-           AND Source = ?
-           AND Tags = ?
-           AND Title = ?
-           AND TitleUnicode = ?
-           AND VideoFile = ?
-           AND AuthorID = ?
-         LIMIT 1",
-        params,
-        |row| row.get(0),
+// Unregisters a set's files. lazer deletes files once nothing refers to them anymore.
+fn remove_set_files(tx: &Transaction, beatmapset_info_id: i64) -> Result<()> {
+    tx.execute(
+        "UPDATE FileInfo
+         SET ReferenceCount = ReferenceCount - (
+             SELECT COUNT(*)
+             FROM BeatmapSetFileInfo
+             WHERE FileInfoID = FileInfo.ID
+               AND BeatmapSetInfoID = ?1
+         )
+         WHERE ID IN (
+             SELECT FileInfoID
+             FROM BeatmapSetFileInfo
+             WHERE BeatmapSetInfoID = ?1
+         )",
+        [beatmapset_info_id],
+    )?;
+    let removed = tx.execute(
+        "DELETE FROM BeatmapSetFileInfo WHERE BeatmapSetInfoID = ?",
+        [beatmapset_info_id],
+    )?;
+    log!(
+        Level::Trace,
+        "DELETE BeatmapSetFileInfo BeatmapSetInfoID={} ({} rows)",
+        beatmapset_info_id,
+        removed
     );
 
-    if let Ok(res) = res {
-        Ok(res)
-    } else {
-        tx.execute(
-            "INSERT INTO BeatmapMetadata
-                 (Artist,
-                  ArtistUnicode,
-                  AudioFile,
-                  Author,
-                  BackgroundFile,
-                  PreviewTime,
-                  Source,
-                  Tags,
-                  Title,
-                  TitleUnicode,
-                  VideoFile,
-                  AuthorID)
-             VALUES
-                 (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
-            params,
-        )?;
+    Ok(())
+}
 
-        Ok(tx.last_insert_rowid())
+fn load_file_ids(tx: &Transaction) -> Result<HashMap<String, i64>> {
+    let mut query = tx.prepare(
+        "SELECT Hash, ID
+         FROM FileInfo
+         WHERE Hash NOT NULL",
+    )?;
+    let ids = query
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<rusqlite::Result<_>>()?;
+
+    Ok(ids)
+}
+
+fn load_online_set_ids(tx: &Transaction) -> Result<HashSet<u32>> {
+    let mut query = tx.prepare(
+        "SELECT OnlineBeatmapSetID
+         FROM BeatmapSetInfo
+         WHERE OnlineBeatmapSetID NOT NULL",
+    )?;
+    let ids = query
+        .query_map([], |row| row.get(0))?
+        .collect::<rusqlite::Result<_>>()?;
+
+    Ok(ids)
+}
+
+// Whether lazer's schema has columns for ranked/submitted dates, and online.db has them to offer
+pub fn supports_set_dates(lazer_db: &Connection, online_db: &Connection) -> Result<bool> {
+    Ok(
+        has_columns(lazer_db, "BeatmapSetInfo", &["DateRanked", "DateSubmitted"])?
+            && has_columns(
+                online_db,
+                "osu_beatmapsets",
+                &["approved_date", "submit_date"],
+            )?,
+    )
+}
+
+// Online IDs of the sets online.db has with any of the genres and any of the languages, where an
+// empty list matches everything
+pub fn sets_by_genre_and_language(
+    online_db: &Connection,
+    genres: &[Genre],
+    languages: &[Language],
+) -> Result<HashSet<u32>> {
+    if !has_columns(online_db, "osu_beatmapsets", &["genre_id", "language_id"])? {
+        return Err(anyhow!(tr!("error-online-db-no-genres")));
+    }
+
+    let genres = genres.iter().map(|&genre| genre as i64).join(", ");
+    let languages = languages.iter().map(|&language| language as i64).join(", ");
+    let mut conditions = vec![];
+    if !genres.is_empty() {
+        conditions.push(format!("genre_id IN ({})", genres));
     }
+    if !languages.is_empty() {
+        conditions.push(format!("language_id IN ({})", languages));
+    }
+
+    let mut query = online_db.prepare(&format!(
+        "SELECT beatmapset_id
+         FROM osu_beatmapsets
+         WHERE {}",
+        conditions.join(" AND ")
+    ))?;
+    let ids = query
+        .query_map([], |row| row.get(0))?
+        .collect::<rusqlite::Result<_>>()?;
+
+    Ok(ids)
 }
 
-pub fn insert_beatmapset_info(
-    tx: &Transaction,
-    db_beatmap: &DbBeatmap,
-    metadata_id: i64,
-    force: bool,
-) -> Result<i64> {
-    let res = tx.query_row(
-        "
-        SELECT ID
-        FROM BeatmapSetInfo
-        WHERE OnlineBeatmapSetID = ?
-        LIMIT 1
-    ",
-        [db_beatmap.beatmap_set_id],
+pub fn has_table(conn: &Connection, table: &str) -> Result<bool> {
+    let count: u32 = conn.query_row(
+        "SELECT COUNT(*)
+         FROM sqlite_master
+         WHERE type = 'table'
+           AND name = ?",
+        [table],
         |row| row.get(0),
-    );
+    )?;
 
-    if res.is_err() || force {
-        let mut random_hash: [u8; 32] = [0; 32];
-        thread_rng().fill(&mut random_hash);
+    Ok(count > 0)
+}
 
-        let mut hash = String::with_capacity(2 * random_hash.len());
-        for byte in random_hash {
-            write!(hash, "{:02x}", byte)?;
-        }
+fn has_columns(conn: &Connection, table: &str, columns: &[&str]) -> Result<bool> {
+    let mut query = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+    let existing = query
+        .query_map([], |row| row.get::<_, String>("name"))?
+        .collect::<rusqlite::Result<HashSet<_>>>()?;
+
+    Ok(columns.iter().all(|column| existing.contains(*column)))
+}
+
+pub fn update_beatmapset_dates(
+    tx: &Transaction,
+    online_db: &Connection,
+    beatmapset_info_id: i64,
+    beatmapset_id: u32,
+) -> Result<()> {
+    let dates = online_db.query_row(
+        "SELECT approved_date, submit_date
+         FROM osu_beatmapsets
+         WHERE beatmapset_id = ?",
+        [beatmapset_id],
+        |row| {
+            Ok((
+                row.get::<_, Option<NaiveDateTime>>(0)?,
+                row.get::<_, Option<NaiveDateTime>>(1)?,
+            ))
+        },
+    );
+
+    // Sets missing from online.db simply keep their dates empty
+    if let Ok((ranked, submitted)) = dates {
+        let to_utc = |date: NaiveDateTime| DateTime::<Utc>::from_utc(date, Utc);
 
         tx.execute(
-            "INSERT INTO BeatmapSetInfo
-                (DeletePending,
-                 Hash,
-                 MetadataID,
-                 OnlineBeatmapSetID,
-                 Protected,
-                 Status,
-                 DateAdded)
-             VALUES
-                 (?, ?, ?, ?, ?, ?, ?)
-             ON CONFLICT
-                 (OnlineBeatmapSetID)
-             DO UPDATE SET
-                 DeletePending=excluded.DeletePending,
-                 Hash=excluded.Hash,
-                 MetadataID=excluded.MetadataID,
-                 OnlineBeatmapSetID=excluded.OnlineBeatmapSetID,
-                 Protected=excluded.Protected,
-                 Status=excluded.Status,
-                 DateAdded=excluded.DateAdded",
+            "UPDATE BeatmapSetInfo
+             SET DateRanked = ?,
+                 DateSubmitted = ?
+             WHERE ID = ?",
             params![
-                false,
-                hash,
-                metadata_id,
-                db_beatmap.beatmap_set_id,
-                false,
-                db_beatmap.ranked_status as i8 - 3,
-                // TODO
-                // the params macro supports datetimes, but i haven't checked if it would be
-                // correct
-                Utc.timestamp_nanos(
-                    ((db_beatmap.modification_date - WIN_TO_UNIX_EPOCH) * 100).try_into()?
-                )
-                .to_rfc3339_opts(chrono::SecondsFormat::AutoSi, false)
-                .replace("T", " "),
+                ranked.map(to_utc),
+                submitted.map(to_utc),
+                beatmapset_info_id
             ],
         )?;
+        log!(
+            Level::Trace,
+            "UPDATE BeatmapSetInfo ID={} DateRanked={:?} DateSubmitted={:?}",
+            beatmapset_info_id,
+            ranked,
+            submitted
+        );
     }
 
-    if let Ok(res) = res {
-        Ok(res)
+    Ok(())
+}
+
+// RETURNING came with SQLite 3.35, which the system's library on Linux may predate
+fn supports_returning() -> bool {
+    rusqlite::version_number() >= 3_035_000
+}
+
+// Runs an INSERT, returning the ID of its row. Without RETURNING that's last_insert_rowid, which an
+// upsert that updates rather than inserts leaves as it was.
+fn insert_returning_id(tx: &Transaction, sql: &str, params: impl Params) -> Result<i64> {
+    if supports_returning() {
+        Ok(tx.query_row(&format!("{} RETURNING ID", sql), params, |row| row.get(0))?)
     } else {
+        tx.execute(sql, params)?;
         Ok(tx.last_insert_rowid())
     }
 }
 
-pub fn insert_beatmap_info(
-    tx: &Transaction,
-    beatmap: &Beatmap,
-    db_beatmap: &DbBeatmap,
-    beatmapset_info_id: i64,
-    difficulty_id: i64,
-    metadata_id: i64,
-) -> Result<()> {
-    let mut bpm: f64 = 0.0;
-
-    // HACK: should be average bpm i think
-    for tp in &beatmap.timing_points {
-        if let TimingPointKind::Uninherited(UninheritedTimingInfo { mpb, .. }) = tp.kind {
-            bpm = 60_000.0 / mpb as f64;
-            break;
+// Runs an INSERT with the ID `bulk::target` gave its row, if it's staged, returning the row's ID
+fn insert_row(tx: &Transaction, sql: &str, params: impl Params, id: Option<i64>) -> Result<i64> {
+    match id {
+        Some(id) => {
+            tx.execute(sql, params)?;
+            Ok(id)
         }
+        None => insert_returning_id(tx, sql, params),
     }
+}
 
-    let star_rating: &Vec<(Mods, f64)>;
-
-    match beatmap.mode {
-        Mode::Osu => star_rating = &db_beatmap.std_star_rating,
-        Mode::Taiko => star_rating = &db_beatmap.std_taiko_rating,
-        Mode::Catch => star_rating = &db_beatmap.std_ctb_rating,
-        Mode::Mania => star_rating = &db_beatmap.std_mania_rating,
+// Ranked status as lazer's BeatmapSetOnlineStatus; local maps have none
+fn lazer_status(db_beatmap: &StableBeatmap, online_ids: bool) -> i8 {
+    if online_ids {
+        db_beatmap.status
+    } else {
+        STATUS_NONE
     }
+}
 
-    let star_rating = star_rating
-        .iter()
-        .find(|t| t.0 == Mods::None)
-        .map_or(0.0, |o| o.1);
+// osu!.db dates are in windows ticks. Bound as a DateTime<Utc>, rusqlite writes them as
+// `yyyy-MM-dd HH:mm:ss.f+00:00`, which EF Core reads into lazer's DateTimeOffset columns as-is
+fn windows_ticks_to_datetime(ticks: u64) -> Result<DateTime<Utc>> {
+    let ticks = ticks
+        .checked_sub(WIN_TO_UNIX_EPOCH)
+        .context("Date is before the unix epoch")?;
+    let nanos = ticks
+        .checked_mul(100)
+        .context("Date is too far in the future")?;
 
-    tx.execute(
-        "INSERT INTO BeatmapInfo
-             (AudioLeadIn,
-              BaseDifficultyID,
-              BeatDivisor,
-              BeatmapSetInfoID,
-              Countdown,
-              DistanceSpacing,
-              GridSize,
-              Hidden,
-              LetterboxInBreaks,
-              MD5Hash,
-              MetadataID,
-              OnlineBeatmapID,
-              Path,
-              RulesetID,
-              SpecialStyle,
-              StackLeniency,
-              StarDifficulty,
-              StoredBookmarks,
-              TimelineZoom,
-              Version,
-              WidescreenStoryboard,
-              Status,
-              BPM,
-              Length,
-              EpilepsyWarning,
-              CountdownOffset,
-              SamplesMatchPlaybackRate)
-         VALUES
-             (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
-        params![
-            beatmap.audio_leadin.0,
-            difficulty_id,
-            beatmap.beat_divisor,
-            beatmapset_info_id,
-            beatmap.countdown,
-            beatmap.distance_spacing,
-            beatmap.grid_size,
-            false,
-            beatmap.letterbox_in_breaks,
-            db_beatmap.hash,
-            metadata_id,
-            db_beatmap.beatmap_id,
-            db_beatmap.beatmap_file_name,
-            beatmap.mode as i8,
-            // XXX: ???
-            false,
-            beatmap.stack_leniency,
-            star_rating,
-            beatmap.bookmarks.iter().join(","),
-            beatmap.timeline_zoom,
-            beatmap.difficulty_name,
-            beatmap.widescreen_storyboard,
-            db_beatmap.ranked_status as i8 - 3,
-            bpm,
-            db_beatmap.total_time.0,
-            beatmap.epilepsy_warning,
-            // XXX: ???
-            false,
-            // XXX: ???
-            false
-        ],
-    )?;
-
-    Ok(())
+    Ok(Utc.timestamp_nanos(nanos.try_into()?))
 }
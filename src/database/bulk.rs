@@ -0,0 +1,113 @@
+// --bulk-insert: the rows of beatmaps are written to temp tables as the import goes, and copied
+// into lazer's tables with one statement each at the end, rather than tens of thousands of single
+// row INSERTs into tables with half a dozen indexes. Their IDs are handed out here, so the rows
+// referring to them can be written before they're copied.
+use anyhow::Result;
+use rusqlite::{Connection, Transaction};
+use std::collections::HashMap;
+
+use crate::log::{log, Level};
+
+// Tables whose rows are staged. Only BeatmapInfo's are ever read back during an import, which is
+// why a forced re-import flushes them first.
+pub const TABLES: [&str; 3] = ["BeatmapDifficulty", "BeatmapMetadata", "BeatmapInfo"];
+
+pub struct Bulk {
+    next_ids: HashMap<&'static str, i64>,
+    staged: usize,
+}
+
+impl Bulk {
+    pub fn new(tx: &Transaction) -> Result<Self> {
+        let mut next_ids = HashMap::new();
+        for table in TABLES {
+            // Same columns in the same order, without the constraints
+            tx.execute_batch(&format!(
+                "DROP TABLE IF EXISTS temp.\"Staged{0}\";
+                 CREATE TEMP TABLE \"Staged{0}\" AS SELECT * FROM main.\"{0}\" WHERE 0;",
+                table
+            ))?;
+            next_ids.insert(table, last_id(tx, table)? + 1);
+        }
+        tx.execute(
+            "CREATE INDEX temp.IX_StagedBeatmapInfo_OnlineBeatmapID
+             ON StagedBeatmapInfo (OnlineBeatmapID)",
+            [],
+        )?;
+
+        Ok(Self {
+            next_ids,
+            staged: 0,
+        })
+    }
+
+    fn stage(&mut self, table: &'static str) -> (String, i64) {
+        let next_id = self.next_ids.get_mut(table).unwrap();
+        let id = *next_id;
+        *next_id += 1;
+        self.staged += 1;
+
+        (format!("temp.\"Staged{}\"", table), id)
+    }
+
+    // Copies the staged rows into lazer's tables, leaving the temp tables empty
+    pub fn flush(&mut self, tx: &Transaction) -> Result<()> {
+        if self.staged == 0 {
+            return Ok(());
+        }
+
+        for table in TABLES {
+            tx.execute_batch(&format!(
+                "INSERT INTO main.\"{0}\" SELECT * FROM temp.\"Staged{0}\";
+                 DELETE FROM temp.\"Staged{0}\";",
+                table
+            ))?;
+        }
+        log!(Level::Trace, "Copied {} staged rows", self.staged);
+        self.staged = 0;
+
+        Ok(())
+    }
+
+    pub fn finish(mut self, tx: &Transaction) -> Result<()> {
+        self.flush(tx)?;
+        for table in TABLES {
+            tx.execute(&format!("DROP TABLE temp.\"Staged{}\"", table), [])?;
+        }
+
+        Ok(())
+    }
+}
+
+// The table to write a row of `table` to, and the ID to give it, if it's staged. Otherwise lazer's
+// table gives it the next one.
+pub fn target(bulk: Option<&mut Bulk>, table: &'static str) -> (String, Option<i64>) {
+    match bulk {
+        Some(bulk) => {
+            let (staged_table, id) = bulk.stage(table);
+            (staged_table, Some(id))
+        }
+        None => (format!("\"{}\"", table), None),
+    }
+}
+
+// The highest ID the table has had, which AUTOINCREMENT never hands out again even once its row is
+// deleted
+fn last_id(conn: &Connection, table: &str) -> Result<i64> {
+    let max_id: Option<i64> = conn.query_row(
+        &format!("SELECT MAX(ID) FROM main.\"{}\"", table),
+        [],
+        |row| row.get(0),
+    )?;
+    let sequence: Option<i64> = if super::has_table(conn, "sqlite_sequence")? {
+        conn.query_row(
+            "SELECT MAX(seq) FROM main.sqlite_sequence WHERE name = ?",
+            [table],
+            |row| row.get(0),
+        )?
+    } else {
+        None
+    };
+
+    Ok(max_id.unwrap_or(0).max(sequence.unwrap_or(0)))
+}
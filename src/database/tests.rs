@@ -0,0 +1,669 @@
+use clap::Parser;
+use md5::{Digest, Md5};
+use rusqlite::types::Value;
+use std::{path::PathBuf, sync::mpsc};
+use tempfile::TempDir;
+
+use super::*;
+use crate::{analysis, integrity, options::Options, parsing, processors::context::HashRequest};
+
+const FIXTURE: &str =
+    include_str!("../../tests/fixtures/20210912144011_AddSamplesMatchPlaybackRate.sql");
+const BEATMAP: &[u8] = include_bytes!("../../tests/fixtures/beatmap.osu");
+const BEATMAP_HARD: &[u8] = include_bytes!("../../tests/fixtures/beatmap-hard.osu");
+// SHA-256 of [Normal] followed by [Hard], which is how lazer hashes a set with both .osu files
+// above. Worked out by hand, not taken from a set lazer imported.
+const SET_HASH: &str = "ffcf5fe74489c49b3984beea7bf8614e9fe5ea84c024fbe190db2ece34179d10";
+const STATUS_RANKED: i8 = 1;
+// (osu!.db ticks, what lazer writes to client.db for the same date)
+const LAZER_DATES: [(u64, &str); 3] = [
+    (637_670_544_111_234_567, "2021-09-12 14:40:11.1234567+00:00"),
+    (635_292_288_000_000_000, "2014-03-01 00:00:00+00:00"),
+    (633_272_895_915_000_000, "2007-10-06 17:46:31.5+00:00"),
+];
+
+// An empty client.db at the supported migration
+fn client_db() -> Connection {
+    let conn = Connection::open_in_memory().unwrap();
+    conn.execute_batch(FIXTURE).unwrap();
+    conn
+}
+
+fn processed(beatmap_id: u32, beatmap_set_id: u32, folder_name: &str) -> BeatmapProcessed {
+    let (beatmap, _) = parsing::parse_beatmap(BEATMAP).unwrap();
+
+    BeatmapProcessed {
+        db_beatmap: StableBeatmap {
+            beatmap_id,
+            beatmap_set_id,
+            songs_path: PathBuf::from("Songs"),
+            folder_name: folder_name.to_string(),
+            beatmap_file_name: "osu-link - Fixture (LavaDesu) [Normal].osu".to_string(),
+            artist: beatmap.artist.clone(),
+            title: beatmap.title.clone(),
+            creator: beatmap.creator.clone(),
+            source: beatmap.source.clone(),
+            tags: beatmap.tags.clone(),
+            hash: format!("{:x}", Md5::digest(BEATMAP)),
+            status: STATUS_RANKED,
+            modification_date: WIN_TO_UNIX_EPOCH + 16_000_000_000_000_000,
+            total_time: 1000.0,
+            star_ratings: [2.5, 0.0, 0.0, 0.0],
+            db_entry: None,
+        },
+        bpm: analysis::bpm(&beatmap),
+        length: analysis::length(&beatmap).unwrap_or(0.0),
+        general: parsing::General::parse(BEATMAP),
+        event_files: EventFiles::default(),
+        is_main: true,
+        hash: format!("{:x}", Md5::digest(BEATMAP)),
+        star_rating: None,
+        beatmap,
+    }
+}
+
+// An osu!lazer data directory with the fixture's client.db, next to an empty Songs folder, and the
+// State of an import from one into the other with `args` on top of the defaults. Files are copied
+// rather than linked, as symlinks may need privileges the tests don't have.
+fn import(args: &[&str]) -> (TempDir, State) {
+    let dir = tempfile::tempdir().unwrap();
+    let lazer_path = dir.path().join("osu");
+    let songs_path = dir.path().join("Songs");
+    fs::create_dir(&lazer_path).unwrap();
+    fs::create_dir(&songs_path).unwrap();
+    Connection::open(lazer_path.join("client.db"))
+        .unwrap()
+        .execute_batch(FIXTURE)
+        .unwrap();
+
+    let defaults = [
+        "osu-link",
+        "--lazer",
+        lazer_path.to_str().unwrap(),
+        "--songs",
+        songs_path.to_str().unwrap(),
+        "--server",
+        "test",
+        "--link-mode",
+        "copy",
+    ];
+    let options = Options::parse_from(defaults.iter().chain(args).copied());
+    let state = State::new(options).unwrap();
+
+    (dir, state)
+}
+
+// A file of the set in `folder`, written to the Songs folder and hashed as the hash stage would
+fn set_file(state: &State, set_id: u32, folder: &str, name: &str, contents: &[u8]) -> Processed {
+    let full_path = state
+        .options
+        .songs
+        .as_ref()
+        .unwrap()
+        .join(folder)
+        .join(name);
+    fs::create_dir_all(full_path.parent().unwrap()).unwrap();
+    fs::write(&full_path, contents).unwrap();
+
+    Processed::File(HashProcessed {
+        request: HashRequest {
+            beatmap_id: 0,
+            beatmapset_id: set_id,
+            folder_name: folder.to_string(),
+            file_name: name.to_string(),
+            stripped_path: PathBuf::from(name),
+            full_path,
+            size: contents.len() as u64,
+            reserved: 0,
+        },
+        hash: format!("{:x}", Sha256::digest(contents)),
+    })
+}
+
+// Runs the last stage of the import over `processed`, in the order given, and commits it
+fn write_all(state: &State, processed: Vec<Processed>) -> Connection {
+    let (sx, rx) = mpsc::channel();
+    for processed in processed {
+        sx.send(processed).unwrap();
+    }
+    drop(sx);
+
+    let mut conn = Connection::open(&state.lazer_db_path).unwrap();
+    let tx = conn.transaction().unwrap();
+    write(state, &tx, rx).unwrap();
+    tx.commit().unwrap();
+
+    conn
+}
+
+// The rows `insert_beatmap` writes for a new set, without the State it'd take its options from.
+// Returns the BeatmapSetInfo ID.
+fn insert(tx: &Transaction, beatmap: &BeatmapProcessed, online_ids: bool) -> i64 {
+    insert_into(tx, None, beatmap, online_ids)
+}
+
+fn insert_into(
+    tx: &Transaction,
+    mut bulk: Option<&mut Bulk>,
+    beatmap: &BeatmapProcessed,
+    online_ids: bool,
+) -> i64 {
+    let difficulty_id =
+        insert_beatmap_difficulty(tx, bulk.as_deref_mut(), &beatmap.beatmap).unwrap();
+    let metadata_id = insert_beatmap_metadata(
+        tx,
+        bulk.as_deref_mut(),
+        &mut HashMap::new(),
+        None,
+        &beatmap.beatmap,
+        &beatmap.event_files,
+    )
+    .unwrap();
+    let beatmapset_info_id =
+        insert_beatmapset_info(tx, &beatmap.db_beatmap, metadata_id, true, online_ids).unwrap();
+    insert_beatmap_info(
+        tx,
+        bulk,
+        None,
+        beatmap,
+        BeatmapInfoRefs {
+            beatmapset_info_id,
+            difficulty_id,
+            metadata_id,
+        },
+        online_ids,
+    )
+    .unwrap();
+
+    beatmapset_info_id
+}
+
+fn rows(conn: &Connection, table: &str) -> Vec<Vec<Value>> {
+    let mut query = conn
+        .prepare(&format!("SELECT * FROM {} ORDER BY ID", table))
+        .unwrap();
+    let columns = query.column_count();
+    query
+        .query_map([], |row| (0..columns).map(|i| row.get(i)).collect())
+        .unwrap()
+        .collect::<rusqlite::Result<_>>()
+        .unwrap()
+}
+
+fn count(conn: &Connection, table: &str) -> i64 {
+    conn.query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |row| {
+        row.get(0)
+    })
+    .unwrap()
+}
+
+#[test]
+fn fixture_is_supported() {
+    let conn = client_db();
+
+    assert!(crate::check_version(&conn).unwrap());
+    integrity::check_schema(&conn).unwrap();
+}
+
+#[test]
+fn inserts_online_beatmap() {
+    let mut conn = client_db();
+    let tx = conn.transaction().unwrap();
+    let beatmap = processed(100, 10, "10 osu-link - Fixture");
+
+    let beatmapset_info_id = insert(&tx, &beatmap, true);
+
+    let (online_id, md5, path, version, ruleset, status, set_id, difficulty_id, metadata_id) = tx
+        .query_row(
+            "SELECT OnlineBeatmapID, MD5Hash, Path, Version, RulesetID, Status, BeatmapSetInfoID,
+                    BaseDifficultyID, MetadataID
+             FROM BeatmapInfo",
+            [],
+            |row| {
+                Ok((
+                    row.get::<_, Option<u32>>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, String>(3)?,
+                    row.get::<_, i64>(4)?,
+                    row.get::<_, i8>(5)?,
+                    row.get::<_, i64>(6)?,
+                    row.get::<_, i64>(7)?,
+                    row.get::<_, i64>(8)?,
+                ))
+            },
+        )
+        .unwrap();
+    assert_eq!(online_id, Some(100));
+    assert_eq!(md5, beatmap.hash);
+    assert_eq!(path, beatmap.db_beatmap.beatmap_file_name);
+    assert_eq!(version, "Normal");
+    assert_eq!(ruleset, 0);
+    assert_eq!(status, STATUS_RANKED);
+    assert_eq!(set_id, beatmapset_info_id);
+
+    let (online_set_id, set_status, set_metadata_id) = tx
+        .query_row(
+            "SELECT OnlineBeatmapSetID, Status, MetadataID
+             FROM BeatmapSetInfo
+             WHERE ID = ?",
+            [beatmapset_info_id],
+            |row| Ok((row.get::<_, Option<u32>>(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .unwrap();
+    assert_eq!(online_set_id, Some(10));
+    assert_eq!(set_status, STATUS_RANKED);
+    assert_eq!(set_metadata_id, metadata_id);
+
+    let (artist, title, author, tags) = tx
+        .query_row(
+            "SELECT Artist, Title, Author, Tags
+             FROM BeatmapMetadata
+             WHERE ID = ?",
+            [metadata_id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+        )
+        .unwrap();
+    assert_eq!(
+        (artist, title, author, tags),
+        (
+            "osu-link".to_string(),
+            "Fixture".to_string(),
+            "LavaDesu".to_string(),
+            "test fixture".to_string()
+        )
+    );
+
+    let (circle_size, approach_rate) = tx
+        .query_row(
+            "SELECT CircleSize, ApproachRate
+             FROM BeatmapDifficulty
+             WHERE ID = ?",
+            [difficulty_id],
+            |row| Ok((row.get::<_, f64>(0)?, row.get::<_, f64>(1)?)),
+        )
+        .unwrap();
+    assert_eq!((circle_size, approach_rate), (4.0, 7.0));
+}
+
+#[test]
+fn local_beatmap_has_no_online_ids() {
+    let mut conn = client_db();
+    let tx = conn.transaction().unwrap();
+    let beatmapset_info_id = insert(&tx, &processed(100, 10, "osu-link - Fixture"), false);
+
+    let (online_id, status) = tx
+        .query_row(
+            "SELECT OnlineBeatmapID, Status FROM BeatmapInfo",
+            [],
+            |row| Ok((row.get::<_, Option<u32>>(0)?, row.get::<_, i8>(1)?)),
+        )
+        .unwrap();
+    assert_eq!((online_id, status), (None, STATUS_NONE));
+
+    let online_set_id = tx
+        .query_row(
+            "SELECT OnlineBeatmapSetID FROM BeatmapSetInfo WHERE ID = ?",
+            [beatmapset_info_id],
+            |row| row.get::<_, Option<u32>>(0),
+        )
+        .unwrap();
+    assert_eq!(online_set_id, None);
+}
+
+#[test]
+fn reinserted_set_keeps_its_row() {
+    let mut conn = client_db();
+    let tx = conn.transaction().unwrap();
+    let beatmap = processed(100, 10, "10 osu-link - Fixture");
+
+    let first = insert(&tx, &beatmap, true);
+    tx.execute("DELETE FROM BeatmapInfo", []).unwrap();
+    let second = insert(&tx, &beatmap, true);
+
+    assert_eq!(first, second);
+    assert_eq!(count(&tx, "BeatmapSetInfo"), 1);
+}
+
+#[test]
+fn metadata_is_deduplicated() {
+    let mut conn = client_db();
+    let tx = conn.transaction().unwrap();
+    let beatmap = processed(100, 10, "10 osu-link - Fixture");
+
+    let mut metadata_ids = load_metadata_ids(&tx).unwrap();
+    let mut insert = || {
+        insert_beatmap_metadata(
+            &tx,
+            None,
+            &mut metadata_ids,
+            None,
+            &beatmap.beatmap,
+            &beatmap.event_files,
+        )
+        .unwrap()
+    };
+    let first = insert();
+    let second = insert();
+
+    assert_eq!(first, second);
+    assert_eq!(count(&tx, "BeatmapMetadata"), 1);
+    // And across imports, read back from the table
+    assert_eq!(load_metadata_ids(&tx).unwrap().len(), 1);
+}
+
+#[test]
+fn shared_file_is_counted_once() {
+    let mut conn = client_db();
+    let tx = conn.transaction().unwrap();
+    let first = insert(&tx, &processed(100, 10, "10 osu-link - Fixture"), true);
+    let second = insert(&tx, &processed(101, 11, "11 osu-link - Fixture"), true);
+
+    let mut file_ids = load_file_ids(&tx).unwrap();
+    for id in [first, second] {
+        insert_file(
+            &tx,
+            &mut file_ids,
+            id,
+            "audio.mp3",
+            "abc",
+            ConflictPolicy::Skip,
+        )
+        .unwrap();
+    }
+    // And across imports, read back from the table
+    let mut file_ids = load_file_ids(&tx).unwrap();
+    insert_file(
+        &tx,
+        &mut file_ids,
+        first,
+        "bg.jpg",
+        "abc",
+        ConflictPolicy::Skip,
+    )
+    .unwrap();
+
+    let reference_count: i64 = tx
+        .query_row("SELECT ReferenceCount FROM FileInfo", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(count(&tx, "FileInfo"), 1);
+    assert_eq!(count(&tx, "BeatmapSetFileInfo"), 3);
+    assert_eq!(reference_count, 3);
+}
+
+#[test]
+fn bulk_rows_match_direct_ones() {
+    let first = processed(100, 10, "10 osu-link - Fixture");
+    let second = processed(101, 11, "11 osu-link - Fixture");
+
+    let mut direct = client_db();
+    let direct_tx = direct.transaction().unwrap();
+    insert(&direct_tx, &first, true);
+    insert(&direct_tx, &second, true);
+
+    let mut staged = client_db();
+    let staged_tx = staged.transaction().unwrap();
+    let mut bulk = Bulk::new(&staged_tx).unwrap();
+    insert_into(&staged_tx, Some(&mut bulk), &first, true);
+    // Flushed halfway, like a forced re-import does
+    bulk.flush(&staged_tx).unwrap();
+    insert_into(&staged_tx, Some(&mut bulk), &second, true);
+    assert_eq!(count(&staged_tx, "BeatmapInfo"), 1);
+    bulk.finish(&staged_tx).unwrap();
+
+    for table in bulk::TABLES {
+        assert_eq!(
+            rows(&direct_tx, table),
+            rows(&staged_tx, table),
+            "{}",
+            table
+        );
+    }
+}
+
+#[test]
+fn deterministic_order_is_sorted_by_path() {
+    let file = |folder: &str| {
+        Processed::File(HashProcessed {
+            request: HashRequest {
+                beatmap_id: 0,
+                beatmapset_id: 0,
+                folder_name: folder.to_string(),
+                file_name: "audio.mp3".to_string(),
+                stripped_path: PathBuf::from("audio.mp3"),
+                full_path: Path::new("Songs").join(folder).join("audio.mp3"),
+                size: 0,
+                reserved: 0,
+            },
+            hash: String::new(),
+        })
+    };
+
+    let beatmap = |beatmap_id, beatmap_set_id, folder: &str| {
+        Processed::Beatmap(Box::new(processed(beatmap_id, beatmap_set_id, folder)))
+    };
+
+    let (sx, rx) = mpsc::channel();
+    sx.send(file("11 osu-link - Fixture")).unwrap();
+    sx.send(beatmap(101, 11, "11 osu-link - Fixture")).unwrap();
+    sx.send(file("10 osu-link - Fixture")).unwrap();
+    sx.send(beatmap(100, 10, "10 osu-link - Fixture")).unwrap();
+    drop(sx);
+
+    let order: Vec<_> = in_order(rx)
+        .iter()
+        .map(|processed| match processed {
+            Processed::Beatmap(beatmap) => format!("beatmap {}", beatmap.db_beatmap.beatmap_id),
+            Processed::File(file) => format!("file {}", file.request.folder_name),
+        })
+        .collect();
+    assert_eq!(
+        order,
+        [
+            "beatmap 100",
+            "beatmap 101",
+            "file 10 osu-link - Fixture",
+            "file 11 osu-link - Fixture"
+        ]
+    );
+}
+
+#[test]
+fn files_arriving_before_their_set_are_written() {
+    let (_dir, state) = import(&["--keep-online-ids"]);
+    let folder = "10 osu-link - Fixture";
+    let beatmap = processed(100, 10, folder);
+    let osu_name = beatmap.db_beatmap.beatmap_file_name.clone();
+
+    let conn = write_all(
+        &state,
+        vec![
+            set_file(&state, 10, folder, &osu_name, BEATMAP),
+            set_file(&state, 10, folder, "audio.mp3", b"audio"),
+            Processed::Beatmap(Box::new(beatmap)),
+        ],
+    );
+    assert_eq!(count(&conn, "BeatmapSetInfo"), 1);
+    assert_eq!(count(&conn, "FileInfo"), 2);
+    assert_eq!(count(&conn, "BeatmapSetFileInfo"), 2);
+
+    // The set's only .osu, so the set's hash is the same as its own
+    let osu_hash = format!("{:x}", Sha256::digest(BEATMAP));
+    let (beatmap_hash, set_hash): (String, String) = conn
+        .query_row(
+            "SELECT BeatmapInfo.Hash, BeatmapSetInfo.Hash
+             FROM BeatmapInfo
+             JOIN BeatmapSetInfo ON BeatmapSetInfo.ID = BeatmapInfo.BeatmapSetInfoID",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .unwrap();
+    assert_eq!(beatmap_hash, osu_hash);
+    assert_eq!(set_hash, osu_hash);
+    assert!(paths::store_file(&state.lazer_path, &osu_hash).exists());
+}
+
+// The beatmap of `processed(100, 10, ..)` again, edited since, from another folder
+fn edited_beatmap() -> Processed {
+    let mut beatmap = processed(100, 10, "10 osu-link - Fixture (edited)");
+    beatmap.hash = "0".repeat(32);
+    Processed::Beatmap(Box::new(beatmap))
+}
+
+fn beatmap_ids_and_hashes(conn: &Connection) -> Vec<(i64, String)> {
+    conn.prepare("SELECT ID, MD5Hash FROM BeatmapInfo ORDER BY ID")
+        .unwrap()
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .unwrap()
+        .collect::<rusqlite::Result<_>>()
+        .unwrap()
+}
+
+#[test]
+fn conflicting_beatmap_is_skipped() {
+    let (_dir, state) = import(&["--keep-online-ids"]);
+    let beatmap = processed(100, 10, "10 osu-link - Fixture");
+    let hash = beatmap.hash.clone();
+    let conn = write_all(&state, vec![Processed::Beatmap(Box::new(beatmap))]);
+    let before = beatmap_ids_and_hashes(&conn);
+    assert_eq!(before[0].1, hash);
+
+    let conn = write_all(&state, vec![edited_beatmap()]);
+    assert_eq!(beatmap_ids_and_hashes(&conn), before);
+}
+
+#[test]
+fn conflicting_beatmap_is_overwritten_in_place() {
+    let (_dir, state) = import(&["--keep-online-ids", "--on-conflict", "overwrite"]);
+    let conn = write_all(
+        &state,
+        vec![Processed::Beatmap(Box::new(processed(
+            100,
+            10,
+            "10 osu-link - Fixture",
+        )))],
+    );
+    let before = beatmap_ids_and_hashes(&conn);
+
+    // Scores refer to the beatmap by ID, so the replacement keeps it
+    let conn = write_all(&state, vec![edited_beatmap()]);
+    assert_eq!(
+        beatmap_ids_and_hashes(&conn),
+        [(before[0].0, "0".repeat(32))]
+    );
+    assert_eq!(count(&conn, "BeatmapSetInfo"), 1);
+}
+
+#[test]
+fn local_difficulties_share_their_folders_set() {
+    let (_dir, state) = import(&[]);
+    let mut hard = processed(101, 10, "osu-link - Fixture");
+    hard.db_beatmap.beatmap_file_name = "osu-link - Fixture (LavaDesu) [Hard].osu".to_string();
+    hard.is_main = false;
+
+    let conn = write_all(
+        &state,
+        vec![
+            Processed::Beatmap(Box::new(processed(100, 10, "osu-link - Fixture"))),
+            Processed::Beatmap(Box::new(hard)),
+            Processed::Beatmap(Box::new(processed(102, 11, "osu-link - Fixture (2)"))),
+        ],
+    );
+    assert_eq!(count(&conn, "BeatmapInfo"), 3);
+    assert_eq!(count(&conn, "BeatmapSetInfo"), 2);
+}
+
+#[test]
+fn set_hash_matches_lazers() {
+    let (_dir, state) = import(&["--keep-online-ids"]);
+    let folder = "10 osu-link - Fixture";
+    let normal = processed(100, 10, folder);
+    let normal_name = normal.db_beatmap.beatmap_file_name.clone();
+
+    // Ordered by code point, the capital P would put [Hard] first
+    let conn = write_all(
+        &state,
+        vec![
+            Processed::Beatmap(Box::new(normal)),
+            set_file(
+                &state,
+                10,
+                folder,
+                "Prelude (LavaDesu) [Hard].osu",
+                BEATMAP_HARD,
+            ),
+            set_file(&state, 10, folder, &normal_name, BEATMAP),
+            set_file(&state, 10, folder, "audio.mp3", b"audio"),
+        ],
+    );
+    let hash: String = conn
+        .query_row("SELECT Hash FROM BeatmapSetInfo", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(hash, SET_HASH);
+}
+
+#[test]
+fn decomposed_file_names_are_registered_composed() {
+    let (_dir, state) = import(&["--keep-online-ids"]);
+    let folder = "10 osu-link - Fixture";
+    let mut beatmap = processed(100, 10, folder);
+    // As osu!.db has it, while macOS lists the file with the kana and its mark apart
+    let composed = "\u{30dd}\u{30c3}\u{30d7} [Normal].osu";
+    let decomposed: String = composed.nfd().collect();
+    assert_ne!(composed, decomposed);
+    beatmap.db_beatmap.beatmap_file_name = composed.to_string();
+
+    let conn = write_all(
+        &state,
+        vec![
+            Processed::Beatmap(Box::new(beatmap)),
+            set_file(&state, 10, folder, &decomposed, BEATMAP),
+        ],
+    );
+    let filename: String = conn
+        .query_row("SELECT Filename FROM BeatmapSetFileInfo", [], |row| {
+            row.get(0)
+        })
+        .unwrap();
+    assert_eq!(filename, composed);
+    let hash: Option<String> = conn
+        .query_row("SELECT Hash FROM BeatmapInfo", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(hash, Some(format!("{:x}", Sha256::digest(BEATMAP))));
+}
+
+#[test]
+fn set_files_are_ordered_like_lazer() {
+    let mut names = vec!["b.osu", "A.osu", "a.osu", "B.osu", "a (2).osu"];
+    names.sort_by(|a, b| culture_cmp(a, b));
+
+    assert_eq!(names, ["a (2).osu", "a.osu", "A.osu", "b.osu", "B.osu"]);
+}
+
+#[test]
+fn dates_match_lazer() {
+    let conn = client_db();
+    for (ticks, lazer) in LAZER_DATES {
+        let date = windows_ticks_to_datetime(ticks).unwrap();
+        let lazer: DateTime<Utc> = conn
+            .query_row("SELECT ?", [lazer], |row| row.get(0))
+            .unwrap();
+        assert_eq!(date, lazer, "{}", ticks);
+
+        // Read back from the database, as lazer would
+        let stored: DateTime<Utc> = conn
+            .query_row("SELECT ?", [date], |row| row.get(0))
+            .unwrap();
+        assert_eq!(
+            WIN_TO_UNIX_EPOCH + stored.timestamp_nanos() as u64 / 100,
+            ticks
+        );
+    }
+}
+
+#[test]
+fn bogus_dates_are_errors() {
+    assert!(windows_ticks_to_datetime(0).is_err());
+    assert!(windows_ticks_to_datetime(u64::MAX).is_err());
+    assert!(windows_ticks_to_datetime(WIN_TO_UNIX_EPOCH + u64::MAX / 100).is_err());
+}
@@ -0,0 +1,395 @@
+// Writes the rows of a beatmap as osu!lazer's schema has them as of this migration, the last one
+// touching the tables an import writes to
+use anyhow::Result;
+use itertools::Itertools;
+use libosu::{beatmap::Beatmap, prelude::Mode};
+use rand::{thread_rng, Rng};
+use rusqlite::{params, Connection, OptionalExtension, Transaction};
+use std::{collections::HashMap, fmt::Write as FmtWrite};
+
+use super::{
+    bulk::{self, Bulk},
+    insert_returning_id, insert_row, lazer_status, supports_returning, windows_ticks_to_datetime,
+};
+use crate::{
+    library::StableBeatmap,
+    log::{log, Level},
+    processors::context::BeatmapProcessed,
+    storyboard::EventFiles,
+};
+
+pub const MIGRATION_ID: &str = "20210912144011_AddSamplesMatchPlaybackRate";
+
+pub fn insert_beatmap_difficulty(
+    tx: &Transaction,
+    bulk: Option<&mut Bulk>,
+    beatmap: &Beatmap,
+) -> Result<i64> {
+    let (table, id) = bulk::target(bulk, "BeatmapDifficulty");
+    tx.execute(
+        &format!(
+            "INSERT INTO {}
+             (ID,
+              ApproachRate,
+              CircleSize,
+              DrainRate,
+              OverallDifficulty,
+              SliderMultiplier,
+              SliderTickRate)
+         VALUES
+             (?, ?, ?, ?, ?, ?, ?)",
+            table
+        ),
+        params![
+            id,
+            beatmap.difficulty.approach_rate,
+            beatmap.difficulty.circle_size,
+            beatmap.difficulty.hp_drain_rate,
+            beatmap.difficulty.overall_difficulty,
+            beatmap.difficulty.slider_multiplier,
+            beatmap.difficulty.slider_tick_rate,
+        ],
+    )?;
+
+    let id = id.unwrap_or_else(|| tx.last_insert_rowid());
+    log!(Level::Trace, "INSERT BeatmapDifficulty ID={}", id);
+    Ok(id)
+}
+
+// BeatmapMetadata's columns, which identify a row when deduplicating
+#[derive(PartialEq, Eq, Hash)]
+pub struct MetadataKey {
+    artist: Option<String>,
+    artist_unicode: Option<String>,
+    audio_file: Option<String>,
+    author: Option<String>,
+    background_file: Option<String>,
+    preview_time: Option<i64>,
+    source: Option<String>,
+    tags: Option<String>,
+    title: Option<String>,
+    title_unicode: Option<String>,
+    video_file: Option<String>,
+    author_id: Option<i64>,
+}
+
+pub fn load_metadata_ids(tx: &Transaction) -> Result<HashMap<MetadataKey, i64>> {
+    let mut query = tx.prepare(
+        "SELECT ID, Artist, ArtistUnicode, AudioFile, Author, BackgroundFile, PreviewTime, Source,
+                Tags, Title, TitleUnicode, VideoFile, AuthorID
+         FROM BeatmapMetadata",
+    )?;
+    let rows = query.query_map([], |row| {
+        let key = MetadataKey {
+            artist: row.get(1)?,
+            artist_unicode: row.get(2)?,
+            audio_file: row.get(3)?,
+            author: row.get(4)?,
+            background_file: row.get(5)?,
+            preview_time: row.get(6)?,
+            source: row.get(7)?,
+            tags: row.get(8)?,
+            title: row.get(9)?,
+            title_unicode: row.get(10)?,
+            video_file: row.get(11)?,
+            author_id: row.get(12)?,
+        };
+        Ok((key, row.get(0)?))
+    })?;
+
+    // The first of any duplicates wins, like the SELECT ... LIMIT 1 this replaces
+    let mut metadata_ids = HashMap::new();
+    for row in rows {
+        let (key, id) = row?;
+        metadata_ids.entry(key).or_insert(id);
+    }
+
+    Ok(metadata_ids)
+}
+
+pub fn insert_beatmap_metadata(
+    tx: &Transaction,
+    bulk: Option<&mut Bulk>,
+    metadata_ids: &mut HashMap<MetadataKey, i64>,
+    online_db: Option<&Connection>,
+    beatmap: &Beatmap,
+    event_files: &EventFiles,
+) -> Result<i64> {
+    let mapper_id: i64 = online_db
+        .and_then(|online_db| {
+            online_db
+                .query_row(
+                    "SELECT user_id
+                     FROM osu_beatmaps
+                     WHERE beatmap_id = ?",
+                    [beatmap.beatmap_id],
+                    |row| row.get(0),
+                )
+                .ok()
+        })
+        .unwrap_or(0);
+
+    let key = MetadataKey {
+        artist: Some(beatmap.artist.clone()),
+        artist_unicode: Some(beatmap.artist_unicode.clone()),
+        audio_file: Some(beatmap.audio_filename.clone()),
+        author: Some(beatmap.creator.clone()),
+        background_file: event_files.background.clone(),
+        preview_time: Some(beatmap.preview_time.0 as i64),
+        source: Some(beatmap.source.clone()),
+        tags: Some(beatmap.tags.join(" ")),
+        title: Some(beatmap.title.clone()),
+        title_unicode: Some(beatmap.title_unicode.clone()),
+        video_file: event_files.video.clone(),
+        author_id: Some(mapper_id),
+    };
+    if let Some(&id) = metadata_ids.get(&key) {
+        return Ok(id);
+    }
+
+    let (table, id) = bulk::target(bulk, "BeatmapMetadata");
+    let id = insert_row(
+        tx,
+        &format!(
+            "INSERT INTO {}
+             (ID,
+              Artist,
+              ArtistUnicode,
+              AudioFile,
+              Author,
+              BackgroundFile,
+              PreviewTime,
+              Source,
+              Tags,
+              Title,
+              TitleUnicode,
+              VideoFile,
+              AuthorID)
+         VALUES
+             (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            table
+        ),
+        params![
+            id,
+            key.artist,
+            key.artist_unicode,
+            key.audio_file,
+            key.author,
+            key.background_file,
+            key.preview_time,
+            key.source,
+            key.tags,
+            key.title,
+            key.title_unicode,
+            key.video_file,
+            key.author_id
+        ],
+        id,
+    )?;
+    log!(
+        Level::Trace,
+        "INSERT BeatmapMetadata ID={} Artist={:?} Title={:?} Author={:?}",
+        id,
+        key.artist.as_deref().unwrap_or_default(),
+        key.title.as_deref().unwrap_or_default(),
+        key.author.as_deref().unwrap_or_default()
+    );
+    metadata_ids.insert(key, id);
+    Ok(id)
+}
+
+pub fn insert_beatmapset_info(
+    tx: &Transaction,
+    db_beatmap: &StableBeatmap,
+    metadata_id: i64,
+    force: bool,
+    online_ids: bool,
+) -> Result<i64> {
+    // The upsert returns the ID of the row it updates, where RETURNING is supported, so a forced
+    // insert needn't look it up first
+    let existing: Option<i64> = if online_ids && !(force && supports_returning()) {
+        tx.query_row(
+            "
+            SELECT ID
+            FROM BeatmapSetInfo
+            WHERE OnlineBeatmapSetID = ?
+            LIMIT 1
+        ",
+            [db_beatmap.beatmap_set_id],
+            |row| row.get(0),
+        )
+        .optional()?
+    } else {
+        None
+    };
+    if let (Some(id), false) = (existing, force) {
+        return Ok(id);
+    }
+
+    // Only a placeholder, until the set's files are in and its real hash can be computed
+    let mut random_hash: [u8; 32] = [0; 32];
+    thread_rng().fill(&mut random_hash);
+
+    let mut hash = String::with_capacity(2 * random_hash.len());
+    for byte in random_hash {
+        write!(hash, "{:02x}", byte)?;
+    }
+
+    let id = insert_returning_id(
+        tx,
+        "INSERT INTO BeatmapSetInfo
+            (DeletePending,
+             Hash,
+             MetadataID,
+             OnlineBeatmapSetID,
+             Protected,
+             Status,
+             DateAdded)
+         VALUES
+             (?, ?, ?, ?, ?, ?, ?)
+         ON CONFLICT
+             (OnlineBeatmapSetID)
+         DO UPDATE SET
+             DeletePending=excluded.DeletePending,
+             Hash=excluded.Hash,
+             MetadataID=excluded.MetadataID,
+             OnlineBeatmapSetID=excluded.OnlineBeatmapSetID,
+             Protected=excluded.Protected,
+             Status=excluded.Status,
+             DateAdded=excluded.DateAdded",
+        params![
+            false,
+            hash,
+            metadata_id,
+            online_ids.then(|| db_beatmap.beatmap_set_id),
+            false,
+            lazer_status(db_beatmap, online_ids),
+            windows_ticks_to_datetime(db_beatmap.modification_date)?,
+        ],
+    )?;
+    // Without RETURNING, an update leaves last_insert_rowid as it was
+    let id = existing.unwrap_or(id);
+    log!(
+        Level::Trace,
+        "INSERT BeatmapSetInfo ID={} OnlineBeatmapSetID={:?} MetadataID={}",
+        id,
+        online_ids.then(|| db_beatmap.beatmap_set_id),
+        metadata_id
+    );
+
+    Ok(id)
+}
+
+// `id` is the ID of the row it replaces, if any, which scores refer to the beatmap by
+// The rows a BeatmapInfo row refers to
+pub struct BeatmapInfoRefs {
+    pub beatmapset_info_id: i64,
+    pub difficulty_id: i64,
+    pub metadata_id: i64,
+}
+
+pub fn insert_beatmap_info(
+    tx: &Transaction,
+    bulk: Option<&mut Bulk>,
+    id: Option<i64>,
+    beatmap_context: &BeatmapProcessed,
+    refs: BeatmapInfoRefs,
+    online_ids: bool,
+) -> Result<()> {
+    let BeatmapInfoRefs {
+        beatmapset_info_id,
+        difficulty_id,
+        metadata_id,
+    } = refs;
+    let beatmap = &beatmap_context.beatmap;
+    let db_beatmap = &beatmap_context.db_beatmap;
+
+    let star_rating = match beatmap_context.star_rating {
+        Some(star_rating) => star_rating,
+        None => db_beatmap.star_ratings[beatmap.mode as usize],
+    };
+
+    let (table, staged_id) = bulk::target(bulk, "BeatmapInfo");
+    let id = id.or(staged_id);
+    tx.execute(
+        &format!(
+            "INSERT INTO {}
+             (ID,
+              AudioLeadIn,
+              BaseDifficultyID,
+              BeatDivisor,
+              BeatmapSetInfoID,
+              Countdown,
+              DistanceSpacing,
+              GridSize,
+              Hidden,
+              LetterboxInBreaks,
+              MD5Hash,
+              MetadataID,
+              OnlineBeatmapID,
+              Path,
+              RulesetID,
+              SpecialStyle,
+              StackLeniency,
+              StarDifficulty,
+              StoredBookmarks,
+              TimelineZoom,
+              Version,
+              WidescreenStoryboard,
+              Status,
+              BPM,
+              Length,
+              EpilepsyWarning,
+              CountdownOffset,
+              SamplesMatchPlaybackRate)
+         VALUES
+             (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            table
+        ),
+        params![
+            id,
+            beatmap.audio_leadin.0,
+            difficulty_id,
+            beatmap.beat_divisor,
+            beatmapset_info_id,
+            beatmap.countdown,
+            beatmap.distance_spacing,
+            beatmap.grid_size,
+            false,
+            beatmap.letterbox_in_breaks,
+            beatmap_context.hash,
+            metadata_id,
+            online_ids.then(|| db_beatmap.beatmap_id),
+            db_beatmap.beatmap_file_name,
+            beatmap.mode as i8,
+            // N+1 key layouts only exist in mania
+            matches!(beatmap.mode, Mode::Mania) && beatmap_context.general.special_style,
+            beatmap.stack_leniency,
+            star_rating,
+            beatmap.bookmarks.iter().join(","),
+            beatmap.timeline_zoom,
+            beatmap.difficulty_name,
+            beatmap.widescreen_storyboard,
+            lazer_status(db_beatmap, online_ids),
+            beatmap_context.bpm.most_common,
+            beatmap_context.length,
+            beatmap.epilepsy_warning,
+            beatmap_context.general.countdown_offset,
+            beatmap_context.general.samples_match_playback_rate,
+        ],
+    )?;
+    log!(
+        Level::Trace,
+        "INSERT BeatmapInfo ID={} OnlineBeatmapID={:?} BeatmapSetInfoID={} MetadataID={} \
+         BaseDifficultyID={} MD5Hash={} Path={:?}",
+        id.unwrap_or_else(|| tx.last_insert_rowid()),
+        online_ids.then(|| db_beatmap.beatmap_id),
+        beatmapset_info_id,
+        metadata_id,
+        difficulty_id,
+        beatmap_context.hash,
+        db_beatmap.beatmap_file_name
+    );
+
+    Ok(())
+}
@@ -0,0 +1,205 @@
+use anyhow::Result;
+use itertools::Itertools;
+use rusqlite::{params, Connection, Transaction};
+use std::{
+    collections::HashMap,
+    io::{stdout, Write},
+};
+
+use crate::{
+    database, exit::ExitCode, get_lazer_path, i18n::tr, open_lazer_db, options::Options, undo,
+    wait_for_input,
+};
+
+// Merges beatmapsets that are in lazer more than once: sets sharing an online ID, and local copies
+// of a set (left by older osu-link versions, or importing from a private server) that share a
+// difficulty with another set. The set with an online ID, or else the oldest one, is kept.
+pub fn dedupe(options: &Options) -> Result<ExitCode> {
+    let lazer_db_path = get_lazer_path(options)?.join("client.db");
+    let mut db_connection = open_lazer_db(&lazer_db_path)?;
+
+    let duplicates = find_duplicates(&db_connection)?;
+    if duplicates.is_empty() {
+        println!("{}", tr!("dedupe-none"));
+        return Ok(ExitCode::Success);
+    }
+
+    println!("{}", tr!("close-games"));
+    print!("{}", tr!("press-enter-to-continue"));
+    stdout().flush()?;
+    wait_for_input()?;
+
+    undo::back_up(&lazer_db_path)?;
+
+    let transaction = db_connection.transaction()?;
+    let has_scores = database::has_table(&transaction, "ScoreInfo")?;
+    for (duplicate, kept) in &duplicates {
+        println!(
+            "{}",
+            tr!("dedupe-merged", set = set_name(&transaction, *kept)?)
+        );
+        merge_set(&transaction, *duplicate, *kept, has_scores)?;
+    }
+    transaction.commit()?;
+
+    println!("{}", tr!("dedupe-summary", count = duplicates.len()));
+
+    Ok(ExitCode::Success)
+}
+
+// (duplicate, kept) BeatmapSetInfo IDs
+fn find_duplicates(db_connection: &Connection) -> Result<Vec<(i64, i64)>> {
+    // lazer's unique index on OnlineBeatmapSetID should rule the first kind out, but it's cheap to
+    // look for anyway
+    let mut statement = db_connection.prepare(
+        "SELECT d.ID, k.ID
+         FROM BeatmapSetInfo d
+         JOIN BeatmapSetInfo k ON k.OnlineBeatmapSetID = d.OnlineBeatmapSetID
+         WHERE k.ID < d.ID
+           AND d.DeletePending = 0
+           AND k.DeletePending = 0
+         UNION
+         SELECT ds.ID, ks.ID
+         FROM BeatmapInfo d
+         JOIN BeatmapInfo k ON k.MD5Hash = d.MD5Hash
+         JOIN BeatmapSetInfo ds ON ds.ID = d.BeatmapSetInfoID
+         JOIN BeatmapSetInfo ks ON ks.ID = k.BeatmapSetInfoID
+         WHERE ds.ID <> ks.ID
+           AND ds.DeletePending = 0
+           AND ks.DeletePending = 0
+           AND (ds.OnlineBeatmapSetID IS NULL AND ks.OnlineBeatmapSetID NOT NULL
+                OR ds.OnlineBeatmapSetID IS ks.OnlineBeatmapSetID AND ks.ID < ds.ID)",
+    )?;
+    let rows = statement.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+
+    let mut kept_by: HashMap<i64, i64> = HashMap::new();
+    for row in rows {
+        let (duplicate, kept) = row?;
+        let entry = kept_by.entry(duplicate).or_insert(kept);
+        *entry = (*entry).min(kept);
+    }
+
+    // A kept set can be a duplicate itself. Sets are only ever kept in favour of one with an
+    // online ID or a lower ID, so this always ends.
+    let resolve = |mut id: i64| {
+        while let Some(&kept) = kept_by.get(&id) {
+            id = kept;
+        }
+        id
+    };
+
+    Ok(kept_by
+        .keys()
+        .sorted()
+        .map(|&duplicate| (duplicate, resolve(duplicate)))
+        .collect())
+}
+
+// Moves a duplicate set's difficulties and files into the kept set, dropping those it already has
+fn merge_set(tx: &Transaction, duplicate: i64, kept: i64, has_scores: bool) -> Result<()> {
+    let beatmaps = tx
+        .prepare(
+            "SELECT d.ID, d.BaseDifficultyID, k.ID
+             FROM BeatmapInfo d
+             LEFT JOIN BeatmapInfo k ON k.BeatmapSetInfoID = ?2 AND k.MD5Hash = d.MD5Hash
+             WHERE d.BeatmapSetInfoID = ?1",
+        )?
+        .query_map(params![duplicate, kept], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, i64>(1)?,
+                row.get::<_, Option<i64>>(2)?,
+            ))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    for (id, difficulty_id, existing_id) in beatmaps {
+        match existing_id {
+            // Scores set on the duplicate carry over to the kept difficulty
+            Some(existing_id) => {
+                if has_scores {
+                    tx.execute(
+                        "UPDATE ScoreInfo
+                         SET BeatmapInfoID = ?
+                         WHERE BeatmapInfoID = ?",
+                        params![existing_id, id],
+                    )?;
+                }
+                tx.execute("DELETE FROM BeatmapInfo WHERE ID = ?", [id])?;
+                tx.execute(
+                    "DELETE FROM BeatmapDifficulty WHERE ID = ?",
+                    [difficulty_id],
+                )?;
+            }
+            None => {
+                tx.execute(
+                    "UPDATE BeatmapInfo
+                     SET BeatmapSetInfoID = ?
+                     WHERE ID = ?",
+                    params![kept, id],
+                )?;
+            }
+        }
+    }
+
+    let files = tx
+        .prepare(
+            "SELECT d.ID, d.FileInfoID, EXISTS (
+                 SELECT 1
+                 FROM BeatmapSetFileInfo k
+                 WHERE k.BeatmapSetInfoID = ?2
+                   AND k.Filename = d.Filename
+             )
+             FROM BeatmapSetFileInfo d
+             WHERE d.BeatmapSetInfoID = ?1",
+        )?
+        .query_map(params![duplicate, kept], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, i64>(1)?,
+                row.get::<_, bool>(2)?,
+            ))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    for (id, file_id, exists) in files {
+        if exists {
+            tx.execute(
+                "UPDATE FileInfo
+                 SET ReferenceCount = ReferenceCount - 1
+                 WHERE ID = ?",
+                [file_id],
+            )?;
+            tx.execute("DELETE FROM BeatmapSetFileInfo WHERE ID = ?", [id])?;
+        } else {
+            tx.execute(
+                "UPDATE BeatmapSetFileInfo
+                 SET BeatmapSetInfoID = ?
+                 WHERE ID = ?",
+                params![kept, id],
+            )?;
+        }
+    }
+
+    tx.execute("DELETE FROM BeatmapSetInfo WHERE ID = ?", [duplicate])?;
+
+    Ok(())
+}
+
+// Same naming as osu!stable's Songs folders
+fn set_name(tx: &Transaction, beatmapset_info_id: i64) -> Result<String> {
+    let (online_id, artist, title): (Option<u32>, Option<String>, Option<String>) = tx.query_row(
+        "SELECT s.OnlineBeatmapSetID, m.Artist, m.Title
+         FROM BeatmapSetInfo s
+         LEFT JOIN BeatmapMetadata m ON m.ID = s.MetadataID
+         WHERE s.ID = ?",
+        [beatmapset_info_id],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+    )?;
+    let (artist, title) = (artist.unwrap_or_default(), title.unwrap_or_default());
+
+    Ok(match online_id {
+        Some(online_id) => format!("{} {} - {}", online_id, artist, title),
+        None => format!("{} - {}", artist, title),
+    })
+}
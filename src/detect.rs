@@ -0,0 +1,195 @@
+use std::{
+    net::{Ipv4Addr, SocketAddr, TcpStream},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+// The port osu!lazer listens on to hand files over to a running instance
+const LAZER_IPC_PORT: u16 = 45356;
+
+// Where osu!stable might be installed, most likely first
+// https://osu.ppy.sh/wiki/en/osu%21_Program_Files#installation-paths
+pub fn stable_candidates() -> Vec<PathBuf> {
+    #[cfg(target_os = "macos")]
+    let candidates = mac_prefixes()
+        .iter()
+        .flat_map(|prefix| in_prefix(prefix))
+        .collect();
+
+    // Most players run stable under Wine rather than natively
+    #[cfg(target_os = "linux")]
+    let candidates = dirs::data_local_dir()
+        .map(|path| path.join("osu!"))
+        .into_iter()
+        .chain(wine_prefixes().iter().flat_map(|prefix| in_prefix(prefix)))
+        .collect();
+
+    // Portable installs and repaired registries leave no registry key behind
+    #[cfg(target_os = "windows")]
+    let candidates = crate::get_stable_path_from_registry()
+        .ok()
+        .into_iter()
+        .chain(windows_locations())
+        .collect();
+
+    candidates
+}
+
+// Where osu!lazer's executable might be, for --via-lazer
+pub fn lazer_executable_candidates() -> Vec<PathBuf> {
+    #[cfg(target_os = "macos")]
+    let candidates = {
+        let mut apps = vec![PathBuf::from("/Applications/osu!.app")];
+        if let Some(home) = dirs::home_dir() {
+            apps.push(home.join("Applications").join("osu!.app"));
+        }
+        apps.into_iter()
+            .map(|app| app.join("Contents").join("MacOS").join("osu!"))
+            .collect()
+    };
+
+    // The AppImage is wherever it was downloaded to, though AppImageLauncher moves it here
+    #[cfg(target_os = "linux")]
+    let candidates = dirs::home_dir()
+        .map(|home| home.join("Applications").join("osu.AppImage"))
+        .into_iter()
+        .collect();
+
+    // The installer keeps the current version in `current`, older ones put it at the top
+    #[cfg(target_os = "windows")]
+    let candidates = dirs::data_local_dir()
+        .map(|path| path.join("osulazer"))
+        .into_iter()
+        .flat_map(|path| [path.join("current").join("osu!.exe"), path.join("osu!.exe")])
+        .collect();
+
+    candidates
+}
+
+// The given executable, or the first of the candidates that exists
+pub fn lazer_executable(configured: Option<&Path>) -> Option<PathBuf> {
+    match configured {
+        Some(exe) => Some(exe.to_path_buf()),
+        None => lazer_executable_candidates()
+            .into_iter()
+            .find(|path| path.is_file()),
+    }
+}
+
+// Whether osu!lazer is running, going by whether anything listens on its IPC port. A running
+// osu!lazer keeps what it read of the database in memory, so it won't see an import until
+// it's restarted.
+pub fn lazer_running() -> bool {
+    let addr = SocketAddr::from((Ipv4Addr::LOCALHOST, LAZER_IPC_PORT));
+    TcpStream::connect_timeout(&addr, Duration::from_millis(500)).is_ok()
+}
+
+// The usual homes of Wine prefixes: Wine's default, and those made by winetricks, Lutris and
+// Bottles (native and Flatpak)
+#[cfg(target_os = "linux")]
+fn wine_prefixes() -> Vec<PathBuf> {
+    let home = match dirs::home_dir() {
+        Some(home) => home,
+        None => return vec![],
+    };
+
+    let mut prefixes = vec![home.join(".wine")];
+    for parent in [
+        ".local/share/wineprefixes",
+        "Games",
+        ".local/share/bottles/bottles",
+        ".var/app/com.usebottles.bottles/data/bottles/bottles",
+    ] {
+        prefixes.extend(subdirectories(&home.join(parent)));
+    }
+
+    prefixes
+}
+
+// Wine prefixes bundled inside osu!.app wrappers, in either Applications folder, followed by
+// CrossOver and Whisky bottles
+#[cfg(target_os = "macos")]
+fn mac_prefixes() -> Vec<PathBuf> {
+    let home = dirs::home_dir();
+
+    let mut apps = vec![PathBuf::from("/Applications/osu!.app")];
+    if let Some(home) = &home {
+        apps.push(home.join("Applications").join("osu!.app"));
+    }
+
+    let mut prefixes = vec![];
+    for app in apps {
+        // The official wrapper keeps its prefix in Resources, Wineskin and Kegworks wrappers
+        // in SharedSupport
+        prefixes.push(app.join("Contents").join("Resources"));
+        prefixes.push(app.join("Contents").join("SharedSupport").join("prefix"));
+    }
+
+    if let Some(home) = &home {
+        for parent in [
+            "Library/Application Support/CrossOver/Bottles",
+            "Library/Containers/com.isaacmarovitz.Whisky/Bottles",
+        ] {
+            prefixes.extend(subdirectories(&home.join(parent)));
+        }
+    }
+
+    prefixes
+}
+
+// Where stable installs itself inside a Windows (or Wine) drive C:, for every user
+#[cfg(not(target_os = "windows"))]
+fn in_prefix(prefix: &Path) -> Vec<PathBuf> {
+    let drive_c = prefix.join("drive_c");
+    let mut candidates = vec![
+        drive_c.join("osu!"),
+        drive_c.join("Program Files").join("osu!"),
+        drive_c.join("Program Files (x86)").join("osu!"),
+    ];
+    for user in subdirectories(&drive_c.join("users")) {
+        candidates.push(user.join("AppData").join("Local").join("osu!"));
+        candidates.push(
+            user.join("Local Settings")
+                .join("Application Data")
+                .join("osu!"),
+        );
+    }
+
+    candidates
+}
+
+#[cfg(not(target_os = "windows"))]
+fn subdirectories(path: &Path) -> Vec<PathBuf> {
+    match path.read_dir() {
+        Ok(entries) => entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.is_dir())
+            .collect(),
+        Err(_) => vec![],
+    }
+}
+
+// The conventional places to install stable on Windows: the installer's default, then the root,
+// a Games folder or Program Files of any drive
+#[cfg(target_os = "windows")]
+fn windows_locations() -> Vec<PathBuf> {
+    let mut candidates: Vec<PathBuf> = dirs::data_local_dir()
+        .map(|path| path.join("osu!"))
+        .into_iter()
+        .collect();
+
+    for drive in 'C'..='Z' {
+        let root = PathBuf::from(format!("{}:\\", drive));
+        if !root.exists() {
+            continue;
+        }
+
+        candidates.push(root.join("osu!"));
+        candidates.push(root.join("Games").join("osu!"));
+        candidates.push(root.join("Program Files").join("osu!"));
+        candidates.push(root.join("Program Files (x86)").join("osu!"));
+    }
+
+    candidates
+}
@@ -0,0 +1,104 @@
+use anyhow::{anyhow, Result};
+use std::path::{Path, PathBuf};
+
+use crate::{
+    check_stable_path, detect_stable_path, get_lazer_path, get_songs_directory, i18n::tr, link,
+    open_lazer_db, options::Options,
+};
+
+// Runs every check `link` depends on and reports each one, instead of stopping at the first
+// failure. Nothing is prompted for or written, apart from a temporary file for the link test.
+pub fn doctor(options: &Options) -> Result<()> {
+    let mut failed = false;
+    let mut report = |ok: bool, message: String| {
+        println!("{} {}", if ok { "[ok]" } else { "[!!]" }, message);
+        failed |= !ok;
+        ok
+    };
+
+    let lazer_path = match get_lazer_path(options) {
+        Ok(path) => {
+            report(
+                true,
+                tr!("doctor-lazer-found", path = format!("{:?}", path)),
+            );
+            Some(path)
+        }
+        Err(e) => {
+            report(false, e.to_string());
+            None
+        }
+    };
+
+    if let Some(lazer_path) = &lazer_path {
+        let lazer_db_path = lazer_path.join("client.db");
+        if report(lazer_db_path.exists(), tr!("doctor-lazer-db")) {
+            if let Err(e) = open_lazer_db(&lazer_db_path) {
+                report(false, e.to_string());
+            } else {
+                report(true, tr!("doctor-lazer-version"));
+            }
+        }
+
+        if options.is_bancho() && options.online_db.is_none() {
+            report(
+                lazer_path.join("online.db").exists(),
+                tr!("doctor-online-db"),
+            );
+        }
+    }
+    if let Some(online_db) = &options.online_db {
+        report(online_db.exists(), tr!("doctor-online-db"));
+    }
+
+    let stable_paths: Vec<PathBuf> = match &options.songs {
+        Some(songs) => songs.parent().map(Path::to_path_buf).into_iter().collect(),
+        None if !options.stable.is_empty() => options.stable.clone(),
+        None => detect_stable_path().into_iter().collect(),
+    };
+    if stable_paths.is_empty() {
+        report(false, tr!("doctor-stable-not-found"));
+    }
+
+    for stable_path in &stable_paths {
+        report(
+            true,
+            tr!("doctor-stable-found", path = format!("{:?}", stable_path)),
+        );
+
+        let songs_path = match &options.songs {
+            Some(songs) => Some(songs.clone()),
+            None => {
+                report(check_stable_path(stable_path), tr!("doctor-stable-db"));
+                get_songs_directory(stable_path).ok()
+            }
+        };
+        match &songs_path {
+            Some(songs_path) => {
+                report(
+                    songs_path.is_dir(),
+                    tr!("doctor-songs", path = format!("{:?}", songs_path)),
+                );
+            }
+            None => {
+                report(false, tr!("doctor-songs-unknown"));
+            }
+        }
+
+        // Files are linked from the Songs folder, wherever BeatmapDirectory puts it
+        if let Some(lazer_path) = &lazer_path {
+            let link_path = songs_path.as_deref().unwrap_or(stable_path);
+            report(
+                link::check(lazer_path, link_path).is_ok(),
+                tr!("doctor-link"),
+            );
+        }
+    }
+
+    if failed {
+        Err(anyhow!(tr!("doctor-failed")))
+    } else {
+        println!("{}", tr!("doctor-passed"));
+        Ok(())
+    }
+}
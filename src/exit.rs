@@ -0,0 +1,52 @@
+use rusqlite::ErrorCode;
+use std::fmt;
+
+// What the process exits with, so scripts can tell failures apart
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExitCode {
+    Success = 0,
+    Error = 1,
+    // Finished, but some beatmapsets or files failed along the way
+    PartialSuccess = 2,
+    VersionMismatch = 3,
+    PathNotFound = 4,
+    DatabaseLocked = 5,
+    // Same as being killed by SIGINT
+    Aborted = 130,
+}
+
+// An error that exits with a specific code. Anything else exits with `ExitCode::Error`.
+#[derive(Debug)]
+struct Failure {
+    code: ExitCode,
+    message: String,
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for Failure {}
+
+pub fn fail(code: ExitCode, message: String) -> anyhow::Error {
+    anyhow::Error::new(Failure { code, message })
+}
+
+pub fn code_of(error: &anyhow::Error) -> ExitCode {
+    for cause in error.chain() {
+        if let Some(failure) = cause.downcast_ref::<Failure>() {
+            return failure.code;
+        }
+
+        // osu!lazer holds a lock on client.db while it's running
+        if let Some(rusqlite::Error::SqliteFailure(e, _)) = cause.downcast_ref() {
+            if e.code == ErrorCode::DatabaseBusy || e.code == ErrorCode::DatabaseLocked {
+                return ExitCode::DatabaseLocked;
+            }
+        }
+    }
+
+    ExitCode::Error
+}
@@ -0,0 +1,293 @@
+use anyhow::{anyhow, Result};
+use indicatif::ProgressBar;
+use itertools::Itertools;
+use rusqlite::Connection;
+use std::{
+    collections::HashSet,
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+};
+use walkdir::WalkDir;
+use zip::{write::FileOptions, CompressionMethod, ZipWriter};
+
+use crate::{
+    exit::ExitCode,
+    get_lazer_path, get_stable_installs,
+    i18n::tr,
+    library::StableBeatmap,
+    open_lazer_db,
+    options::{ExportSource, Options},
+    paths, pool, read_stable_beatmaps, theme,
+};
+
+pub fn export(options: &Options, source: ExportSource) -> Result<ExitCode> {
+    match source {
+        // Exporting only reads from osu!stable, so it shouldn't require lazer to be installed
+        ExportSource::Stable { sets, pool, output } => {
+            let installs = get_stable_installs(options)?;
+            let beatmaps = read_stable_beatmaps(options, &installs)?;
+
+            let mut set_ids: HashSet<u32> = sets.into_iter().collect();
+            if let Some(pool) = pool {
+                let pool: HashSet<u32> = pool::read_pool(&pool)?.into_iter().collect();
+                set_ids.extend(
+                    beatmaps
+                        .iter()
+                        .filter(|bm| pool.contains(&bm.beatmap_id))
+                        .map(|bm| bm.beatmap_set_id),
+                );
+            }
+
+            export_sets(&beatmaps, &set_ids, &output)
+        }
+        // Likewise, exporting from lazer doesn't need osu!stable
+        ExportSource::Lazer { sets, output } => {
+            let lazer_path = get_lazer_path(options)?;
+            let db_connection = open_lazer_db(&lazer_path.join("client.db"))?;
+
+            let set_ids: HashSet<u32> = sets.into_iter().collect();
+            export_lazer_sets(
+                &paths::extended(&lazer_path),
+                &db_connection,
+                &set_ids,
+                &output,
+            )
+        }
+    }
+}
+
+// Packages each selected set folder into `<folder name>.osz` inside `output`
+fn export_sets(
+    beatmaps: &[StableBeatmap],
+    set_ids: &HashSet<u32>,
+    output: &Path,
+) -> Result<ExitCode> {
+    if set_ids.is_empty() {
+        return Err(anyhow!(tr!("error-no-sets-selected")));
+    }
+
+    // Difficulties of the same set may be split across folders (e.g. after a partial update),
+    // every one of them is exported. A folder in several installs is exported once.
+    let folders = beatmaps
+        .iter()
+        .filter(|bm| set_ids.contains(&bm.beatmap_set_id))
+        .map(|bm| {
+            (
+                bm.beatmap_set_id,
+                bm.songs_path.as_path(),
+                bm.folder_name.as_str(),
+            )
+        })
+        .unique_by(|(set_id, _, folder_name)| (*set_id, *folder_name))
+        .collect_vec();
+
+    let found: HashSet<u32> = folders.iter().map(|(set_id, _, _)| *set_id).collect();
+    let missing = set_ids.difference(&found).sorted().collect_vec();
+    if !missing.is_empty() {
+        println!(
+            "{}",
+            tr!("export-stable-missing", ids = missing.iter().join(", "))
+        );
+    }
+
+    fs::create_dir_all(output)?;
+
+    let mut failures = 0;
+    let bar = progress_bar(folders.len());
+    for (_, songs_path, folder_name) in folders {
+        let set_path = paths::resolve(&paths::extended(songs_path), folder_name);
+        let osz_path = output.join(format!("{}.osz", folder_name));
+
+        if let Err(e) = package_stable_folder(&set_path, &osz_path) {
+            bar.println(tr!(
+                "error-exporting",
+                name = folder_name,
+                error = e.to_string()
+            ));
+            let _ = fs::remove_file(&osz_path);
+            failures += 1;
+        }
+        bar.inc(1);
+    }
+    bar.finish();
+
+    Ok(if failures > 0 {
+        ExitCode::PartialSuccess
+    } else {
+        ExitCode::Success
+    })
+}
+
+// Rebuilds lazer beatmapsets from its hashed file store, exporting every set when `set_ids` is
+// empty. Sets are selected by their online ID, so local maps can only be exported all at once.
+fn export_lazer_sets(
+    lazer_path: &Path,
+    db_connection: &Connection,
+    set_ids: &HashSet<u32>,
+    output: &Path,
+) -> Result<ExitCode> {
+    let mut query = db_connection.prepare(
+        "SELECT s.ID, s.OnlineBeatmapSetID, m.Artist, m.Title
+         FROM BeatmapSetInfo s
+         JOIN BeatmapMetadata m ON m.ID = s.MetadataID
+         WHERE s.DeletePending = 0",
+    )?;
+    let sets = query
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, Option<u32>>(1)?,
+                row.get::<_, Option<String>>(2)?.unwrap_or_default(),
+                row.get::<_, Option<String>>(3)?.unwrap_or_default(),
+            ))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?
+        .into_iter()
+        .filter(|(_, online_id, ..)| {
+            set_ids.is_empty() || online_id.map_or(false, |id| set_ids.contains(&id))
+        })
+        .collect_vec();
+
+    let found: HashSet<u32> = sets
+        .iter()
+        .filter_map(|(_, online_id, ..)| *online_id)
+        .collect();
+    let missing = set_ids.difference(&found).sorted().collect_vec();
+    if !missing.is_empty() {
+        println!(
+            "{}",
+            tr!("export-lazer-missing", ids = missing.iter().join(", "))
+        );
+    }
+
+    fs::create_dir_all(output)?;
+
+    let mut files_query = db_connection.prepare(
+        "SELECT f.Filename, i.Hash
+         FROM BeatmapSetFileInfo f
+         JOIN FileInfo i ON i.ID = f.FileInfoID
+         WHERE f.BeatmapSetInfoID = ?",
+    )?;
+
+    let mut failures = 0;
+    // .osz names given so far, lower-cased for case-insensitive filesystems
+    let mut used_names = HashSet::new();
+    let bar = progress_bar(sets.len());
+    for (id, online_id, artist, title) in sets {
+        // Same naming as osu!stable's Songs folders
+        let name = match online_id {
+            Some(online_id) => format!("{} {} - {}", online_id, artist, title),
+            None => format!("{} - {}", artist, title),
+        };
+        let osz_name = unique_name(&mut used_names, &sanitize_filename(&name));
+        let osz_path = output.join(format!("{}.osz", osz_name));
+
+        let files = files_query
+            .query_map([id], |row| {
+                let path = paths::store_file(lazer_path, &row.get::<_, String>(1)?);
+
+                // Sets imported on Windows store their file names with backslashes
+                let name: String = row.get(0)?;
+                Ok((name.replace('\\', "/"), path))
+            })
+            .and_then(|rows| rows.collect::<rusqlite::Result<Vec<_>>>());
+
+        let res = files
+            .map_err(anyhow::Error::from)
+            .and_then(|files| write_osz(&osz_path, files));
+        if let Err(e) = res {
+            bar.println(tr!(
+                "error-exporting",
+                name = name.as_str(),
+                error = e.to_string()
+            ));
+            let _ = fs::remove_file(&osz_path);
+            failures += 1;
+        }
+        bar.inc(1);
+    }
+    bar.finish();
+
+    Ok(if failures > 0 {
+        ExitCode::PartialSuccess
+    } else {
+        ExitCode::Success
+    })
+}
+
+fn progress_bar(len: usize) -> ProgressBar {
+    ProgressBar::new(len as u64)
+        .with_prefix(tr!("bar-exporting"))
+        .with_style(theme::bar_style(
+            "{prefix} [{wide_bar}] {percent:>3}% {pos:>8}/{len:8}",
+        ))
+}
+
+pub fn package_stable_folder(set_path: &Path, osz_path: &Path) -> Result<()> {
+    write_osz(osz_path, stable_files(set_path)?)
+}
+
+// Every file in a set folder, along with its name inside the .osz
+fn stable_files(set_path: &Path) -> Result<Vec<(String, PathBuf)>> {
+    let mut files = vec![];
+    for entry in WalkDir::new(set_path) {
+        let entry = entry?;
+        let path = entry.path();
+
+        if !path.is_file() {
+            continue;
+        }
+
+        // Zip entries always use forward slashes, regardless of platform
+        let name = path
+            .strip_prefix(set_path)?
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .join("/");
+
+        files.push((name, path.to_path_buf()));
+    }
+
+    Ok(files)
+}
+
+fn write_osz(osz_path: &Path, files: Vec<(String, PathBuf)>) -> Result<()> {
+    let mut zip = ZipWriter::new(File::create(paths::extended(osz_path))?);
+    let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    for (name, path) in files {
+        zip.start_file(name, options)?;
+        io::copy(&mut File::open(path)?, &mut zip)?;
+    }
+
+    zip.finish()?;
+
+    Ok(())
+}
+
+// Local sets with the same artist and title, such as different mappers' versions of a song, would
+// otherwise overwrite each other's .osz. The later ones are numbered from (2).
+fn unique_name(used_names: &mut HashSet<String>, name: &str) -> String {
+    let mut unique = name.to_string();
+    let mut number = 2;
+    while !used_names.insert(unique.to_lowercase()) {
+        unique = format!("{} ({})", name, number);
+        number += 1;
+    }
+
+    unique
+}
+
+// Artist and title can contain characters that aren't allowed in file names
+fn sanitize_filename(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect::<String>()
+        .trim_end_matches(|c: char| c == '.' || c == ' ')
+        .to_string()
+}
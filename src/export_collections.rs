@@ -0,0 +1,108 @@
+use anyhow::{anyhow, Result};
+use std::{
+    collections::HashSet,
+    fs,
+    io::{stdout, Write},
+};
+
+use crate::{
+    collection_db::{self, Collection, CollectionDb},
+    exit::ExitCode,
+    get_lazer_path, get_stable_installs,
+    i18n::tr,
+    options::Options,
+    read_stable_beatmaps, theme, wait_for_input,
+};
+
+// Writes osu!lazer's collections into osu!stable's collection.db, for going back to osu!stable.
+// Collections osu!stable already has by the same name get the beatmaps they're missing, unless
+// `replace` is given, in which case osu!stable's are thrown away.
+pub fn export_collections(options: &Options, replace: bool) -> Result<ExitCode> {
+    let lazer_collections_path = get_lazer_path(options)?.join("collection.db");
+    if !lazer_collections_path.exists() {
+        return Err(anyhow!(tr!("error-no-lazer-collections")));
+    }
+    let lazer = collection_db::read(&lazer_collections_path)?;
+
+    let installs = get_stable_installs(options)?;
+    let stable_collections_path = installs[0].path.join("collection.db");
+    let mut stable = if !replace && stable_collections_path.exists() {
+        collection_db::read(&stable_collections_path)?
+    } else {
+        CollectionDb::default()
+    };
+
+    // Beatmaps osu!stable doesn't have are still written, and show up once they're downloaded
+    let library: HashSet<String> = read_stable_beatmaps(options, &installs)?
+        .into_iter()
+        .map(|bm| bm.hash)
+        .collect();
+    let mut missing = 0;
+    let mut added = 0;
+    for collection in lazer.collections {
+        for hash in collection
+            .hashes
+            .iter()
+            .filter(|hash| !library.contains(*hash))
+        {
+            missing += 1;
+            println!(
+                "{}",
+                theme::warning(&tr!(
+                    "collection-missing",
+                    collection = collection.name.as_str(),
+                    hash = hash.as_str()
+                ))
+            );
+        }
+
+        let index = match stable
+            .collections
+            .iter()
+            .position(|existing| existing.name == collection.name)
+        {
+            Some(index) => index,
+            None => {
+                stable.collections.push(Collection {
+                    name: collection.name.clone(),
+                    hashes: vec![],
+                });
+                stable.collections.len() - 1
+            }
+        };
+        let existing = &mut stable.collections[index];
+        let mut known: HashSet<String> = existing.hashes.iter().cloned().collect();
+        for hash in collection.hashes {
+            if known.insert(hash.clone()) {
+                existing.hashes.push(hash);
+                added += 1;
+            }
+        }
+    }
+
+    println!("{}", tr!("close-games"));
+    print!("{}", tr!("press-enter-to-continue"));
+    stdout().flush()?;
+    wait_for_input()?;
+
+    if stable_collections_path.exists() {
+        fs::copy(
+            &stable_collections_path,
+            stable_collections_path.with_file_name("collection.db.osu-link-backup"),
+        )?;
+    }
+    collection_db::write(&stable_collections_path, &stable)?;
+
+    println!(
+        "{}",
+        tr!(
+            "export-collections-summary",
+            path = format!("{:?}", stable_collections_path),
+            collections = stable.collections.len(),
+            beatmaps = added,
+            missing = missing
+        )
+    );
+
+    Ok(ExitCode::Success)
+}
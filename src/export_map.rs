@@ -0,0 +1,100 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use crate::{
+    exit::ExitCode,
+    get_lazer_path, get_stable_installs,
+    i18n::tr,
+    open_lazer_db,
+    options::{MapFormat, Options},
+    paths, read_stable_beatmaps,
+};
+
+// Where a .osu can be found, in either client
+#[derive(Serialize, Default)]
+struct Location {
+    stable: Option<String>,
+    lazer: Option<String>,
+}
+
+// Writes where every .osu is by its MD5, for tools that need the files without reading osu!.db or
+// osu!lazer's database themselves
+pub fn export_map(options: &Options, format: MapFormat, output: &Path) -> Result<ExitCode> {
+    let mut locations: BTreeMap<String, Location> = BTreeMap::new();
+
+    let installs = get_stable_installs(options)?;
+    for bm in read_stable_beatmaps(options, &installs)? {
+        let set_path = paths::resolve(&bm.songs_path, &bm.folder_name);
+        let path = paths::resolve(&set_path, &bm.beatmap_file_name);
+        locations.entry(bm.hash).or_default().stable = Some(path.to_string_lossy().into_owned());
+    }
+
+    let lazer_path = get_lazer_path(options)?;
+    let db_connection = open_lazer_db(&lazer_path.join("client.db"))?;
+    let lazer_files = db_connection
+        .prepare(
+            "SELECT b.MD5Hash, f.Hash
+             FROM BeatmapInfo b
+             JOIN BeatmapSetFileInfo sf
+               ON sf.BeatmapSetInfoID = b.BeatmapSetInfoID
+              AND sf.Filename = b.Path
+             JOIN FileInfo f ON f.ID = sf.FileInfoID
+             WHERE b.MD5Hash NOT NULL",
+        )?
+        .query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    for (md5, sha256) in lazer_files {
+        let path = paths::store_file(&lazer_path, &sha256);
+        locations.entry(md5).or_default().lazer = Some(path.to_string_lossy().into_owned());
+    }
+
+    let fd = File::create(output)
+        .with_context(|| format!("Failed to create the mapping at {:?}", output))?;
+    let mut writer = BufWriter::new(fd);
+    match format {
+        MapFormat::Json => serde_json::to_writer_pretty(&mut writer, &locations)?,
+        MapFormat::Csv => {
+            writeln!(writer, "md5,stable,lazer")?;
+            for (md5, location) in &locations {
+                writeln!(
+                    writer,
+                    "{},{},{}",
+                    md5,
+                    csv_field(location.stable.as_deref()),
+                    csv_field(location.lazer.as_deref())
+                )?;
+            }
+        }
+    }
+    writer.flush()?;
+
+    println!(
+        "{}",
+        tr!(
+            "export-map-done",
+            count = locations.len(),
+            path = format!("{:?}", output)
+        )
+    );
+
+    Ok(ExitCode::Success)
+}
+
+// Quoted when it has to be, per RFC 4180. Missing paths are left empty
+fn csv_field(value: Option<&str>) -> String {
+    match value {
+        Some(value) if value.contains(|c: char| matches!(c, ',' | '"' | '\n' | '\r')) => {
+            format!("\"{}\"", value.replace('"', "\"\""))
+        }
+        Some(value) => value.to_string(),
+        None => String::new(),
+    }
+}
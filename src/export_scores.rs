@@ -0,0 +1,259 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use md5::{Digest, Md5};
+use serde::Deserialize;
+use std::{
+    collections::{HashMap, HashSet},
+    convert::TryInto,
+    fs,
+    io::{stdout, Write},
+};
+
+use crate::{
+    database,
+    exit::ExitCode,
+    get_lazer_path, get_stable_installs,
+    i18n::tr,
+    open_lazer_db,
+    options::Options,
+    read_stable_beatmaps,
+    scores_db::{self, Score, ScoresDb},
+    wait_for_input, WIN_TO_UNIX_EPOCH,
+};
+
+// Mods as osu!lazer stores them
+#[derive(Deserialize)]
+struct LazerMod {
+    #[serde(alias = "Acronym")]
+    acronym: String,
+    // Customised mods, such as DT at another speed, have no osu!stable counterpart
+    #[serde(default, alias = "Settings")]
+    settings: HashMap<String, serde_json::Value>,
+}
+
+// A score as read from ScoreInfo
+struct LazerScore {
+    ruleset_id: i64,
+    player: Option<String>,
+    total_score: i64,
+    max_combo: i64,
+    mods: String,
+    statistics: String,
+    date: DateTime<Utc>,
+    online_id: Option<i64>,
+    beatmap_hash: String,
+}
+
+// Copies osu!lazer's local scores into osu!stable's scores.db, for the beatmaps osu!stable has.
+// Scores osu!stable can't show, with customised or lazer-only mods, are left out.
+pub fn export_scores(options: &Options) -> Result<ExitCode> {
+    let db_connection = open_lazer_db(&get_lazer_path(options)?.join("client.db"))?;
+    if !database::has_table(&db_connection, "ScoreInfo")? {
+        println!("{}", tr!("export-scores-none"));
+        return Ok(ExitCode::Success);
+    }
+
+    let installs = get_stable_installs(options)?;
+    let stable_scores_path = installs[0].path.join("scores.db");
+    let mut stable = if stable_scores_path.exists() {
+        scores_db::read(&stable_scores_path)?
+    } else {
+        ScoresDb::default()
+    };
+    let library: HashSet<String> = read_stable_beatmaps(options, &installs)?
+        .into_iter()
+        .map(|bm| bm.hash)
+        .collect();
+
+    let lazer_scores = db_connection
+        .prepare(
+            "SELECT s.RulesetID, s.User, s.TotalScore, s.MaxCombo, s.Mods, s.Statistics, s.Date,
+                    s.OnlineScoreID, b.MD5Hash
+             FROM ScoreInfo s
+             JOIN BeatmapInfo b ON b.ID = s.BeatmapInfoID
+             WHERE s.DeletePending = 0
+               AND b.MD5Hash NOT NULL",
+        )?
+        .query_map([], |row| {
+            Ok(LazerScore {
+                ruleset_id: row.get(0)?,
+                player: row.get(1)?,
+                total_score: row.get(2)?,
+                max_combo: row.get(3)?,
+                mods: row.get(4)?,
+                statistics: row.get(5)?,
+                date: row.get(6)?,
+                online_id: row.get(7)?,
+                beatmap_hash: row.get(8)?,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    let mut known: HashSet<String> = stable
+        .beatmaps
+        .iter()
+        .flat_map(|(_, scores)| scores.iter().map(|score| score.replay_hash.clone()))
+        .collect();
+    let (mut exported, mut not_in_stable, mut unrepresentable) = (0, 0, 0);
+    for lazer_score in lazer_scores {
+        if !library.contains(&lazer_score.beatmap_hash) {
+            not_in_stable += 1;
+            continue;
+        }
+        let score = match convert(&lazer_score, stable.version) {
+            Some(score) => score,
+            None => {
+                unrepresentable += 1;
+                continue;
+            }
+        };
+        // Exported on an earlier run
+        if !known.insert(score.replay_hash.clone()) {
+            continue;
+        }
+
+        match stable
+            .beatmaps
+            .iter_mut()
+            .find(|(hash, _)| *hash == score.beatmap_hash)
+        {
+            Some((_, scores)) => scores.push(score),
+            None => stable
+                .beatmaps
+                .push((score.beatmap_hash.clone(), vec![score])),
+        }
+        exported += 1;
+    }
+
+    if exported > 0 {
+        println!("{}", tr!("close-games"));
+        print!("{}", tr!("press-enter-to-continue"));
+        stdout().flush()?;
+        wait_for_input()?;
+
+        if stable_scores_path.exists() {
+            fs::copy(
+                &stable_scores_path,
+                stable_scores_path.with_file_name("scores.db.osu-link-backup"),
+            )?;
+        }
+        scores_db::write(&stable_scores_path, &stable)?;
+    }
+
+    println!(
+        "{}",
+        tr!(
+            "export-scores-summary",
+            path = format!("{:?}", stable_scores_path),
+            exported = exported,
+            missing = not_in_stable,
+            unrepresentable = unrepresentable
+        )
+    );
+
+    Ok(ExitCode::Success)
+}
+
+fn convert(lazer_score: &LazerScore, version: i32) -> Option<Score> {
+    let mode: u8 = lazer_score
+        .ruleset_id
+        .try_into()
+        .ok()
+        .filter(|&mode| mode < 4)?;
+    let mods = stable_mods(&lazer_score.mods)?;
+    let statistics: HashMap<String, u16> = serde_json::from_str(&lazer_score.statistics).ok()?;
+    let count = |result: &str| statistics.get(result).copied().unwrap_or(0);
+
+    // Each ruleset fills osu!stable's counts with different judgements
+    let (count_geki, count_300, count_katu, count_100, count_50) = match mode {
+        0 | 1 => (0, count("Great"), 0, count("Ok"), count("Meh")),
+        2 => (
+            0,
+            count("Great"),
+            count("SmallTickMiss"),
+            count("LargeTickHit"),
+            count("SmallTickHit"),
+        ),
+        _ => (
+            count("Perfect"),
+            count("Great"),
+            count("Good"),
+            count("Ok"),
+            count("Meh"),
+        ),
+    };
+    let count_miss = count("Miss");
+    let timestamp = WIN_TO_UNIX_EPOCH as i64 + lazer_score.date.timestamp_nanos() / 100;
+
+    Some(Score {
+        mode,
+        version,
+        beatmap_hash: lazer_score.beatmap_hash.clone(),
+        player: lazer_score.player.clone().unwrap_or_default(),
+        // There's no replay to name, but it has to be unique, and the same on every export
+        replay_hash: format!(
+            "{:x}",
+            Md5::digest(format!("{}{}", lazer_score.beatmap_hash, timestamp).as_bytes())
+        ),
+        count_300,
+        count_100,
+        count_50,
+        count_geki,
+        count_katu,
+        count_miss,
+        score: lazer_score.total_score.try_into().ok()?,
+        max_combo: lazer_score.max_combo.try_into().ok()?,
+        // osu!lazer doesn't store whether the combo was full, so only misses can tell
+        perfect: count_miss == 0,
+        mods,
+        life_graph: String::new(),
+        timestamp,
+        online_id: lazer_score.online_id.unwrap_or(0),
+        target_practice_accuracy: None,
+    })
+}
+
+// osu!stable's mod bits, or None if a mod has none
+fn stable_mods(mods: &str) -> Option<i32> {
+    let mods: Vec<LazerMod> = serde_json::from_str(mods).ok()?;
+
+    let mut bits = 0;
+    for m in mods {
+        if !m.settings.is_empty() {
+            return None;
+        }
+        bits |= match m.acronym.as_str() {
+            "NF" => 1,
+            "EZ" => 2,
+            "TD" => 4,
+            "HD" => 8,
+            "HR" => 16,
+            "SD" => 32,
+            "DT" => 64,
+            "RX" => 128,
+            "HT" => 256,
+            "NC" => 512 | 64,
+            "FL" => 1024,
+            "SO" => 4096,
+            "AP" => 8192,
+            "PF" => 16384 | 32,
+            "4K" => 1 << 15,
+            "5K" => 1 << 16,
+            "6K" => 1 << 17,
+            "7K" => 1 << 18,
+            "8K" => 1 << 19,
+            "FI" => 1 << 20,
+            "RD" => 1 << 21,
+            "9K" => 1 << 24,
+            "1K" => 1 << 26,
+            "3K" => 1 << 27,
+            "2K" => 1 << 28,
+            "MR" => 1 << 30,
+            // Classic is how osu!stable plays anyway
+            "CL" => 0,
+            _ => return None,
+        };
+    }
+
+    Some(bits)
+}
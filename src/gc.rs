@@ -0,0 +1,147 @@
+use anyhow::Result;
+use indicatif::HumanBytes;
+use rusqlite::Connection;
+use std::{
+    collections::HashSet,
+    fs::{self, Metadata},
+    io::{stdout, Write},
+    path::{Path, PathBuf},
+};
+use walkdir::WalkDir;
+
+use crate::{
+    database, exit::ExitCode, get_lazer_path, i18n::tr, open_lazer_db, options::Options, paths,
+    undo, wait_for_input,
+};
+
+// Tables other than BeatmapSetFileInfo that refer to FileInfo, in some versions of lazer
+const FILE_TABLES: &[&str] = &["ScoreFileInfo", "SkinFileInfo"];
+
+// Removes what's left in lazer's file store once nothing uses it: FileInfo rows whose reference
+// count dropped to 0 (with --force or dedupe), and files without a row at all, as left by `undo`
+pub fn gc(options: &Options) -> Result<ExitCode> {
+    let lazer_path = get_lazer_path(options)?;
+    let lazer_db_path = lazer_path.join("client.db");
+    let mut db_connection = open_lazer_db(&lazer_db_path)?;
+    let lazer_path = paths::extended(&lazer_path);
+
+    let unreferenced = unreferenced_files(&db_connection)?;
+    let orphans = orphan_files(&db_connection, &lazer_path)?;
+    if unreferenced.is_empty() && orphans.is_empty() {
+        println!("{}", tr!("gc-none"));
+        return Ok(ExitCode::Success);
+    }
+
+    println!("{}", tr!("close-games"));
+    print!("{}", tr!("press-enter-to-continue"));
+    stdout().flush()?;
+    wait_for_input()?;
+
+    undo::back_up(&lazer_db_path)?;
+
+    // Rows go first, so a failed commit doesn't leave them pointing at nothing
+    let transaction = db_connection.transaction()?;
+    for (id, _) in &unreferenced {
+        transaction.execute("DELETE FROM FileInfo WHERE ID = ?", [id])?;
+    }
+    transaction.commit()?;
+
+    let mut removed = 0;
+    let mut reclaimed = 0;
+    let paths = unreferenced
+        .iter()
+        .map(|(_, hash)| paths::store_file(&lazer_path, hash))
+        .chain(orphans);
+    for path in paths {
+        // Also catches dangling symlinks, which `exists` follows
+        if let Ok(metadata) = fs::symlink_metadata(&path) {
+            if fs::remove_file(&path).is_ok() {
+                removed += 1;
+                reclaimed += freed(&metadata);
+            }
+        }
+    }
+
+    println!(
+        "{}",
+        tr!(
+            "gc-summary",
+            rows = unreferenced.len(),
+            files = removed,
+            size = HumanBytes(reclaimed).to_string()
+        )
+    );
+
+    Ok(ExitCode::Success)
+}
+
+// (ID, hash) of FileInfo rows nothing refers to
+fn unreferenced_files(db_connection: &Connection) -> Result<Vec<(i64, String)>> {
+    // The reference count is only trusted as far as the tables osu-link knows about agree
+    let mut query = "SELECT ID, Hash
+         FROM FileInfo f
+         WHERE ReferenceCount <= 0
+           AND NOT EXISTS (SELECT 1 FROM BeatmapSetFileInfo WHERE FileInfoID = f.ID)"
+        .to_string();
+    for table in FILE_TABLES {
+        if database::has_table(db_connection, table)? {
+            query += &format!(
+                "\n           AND NOT EXISTS (SELECT 1 FROM {} WHERE FileInfoID = f.ID)",
+                table
+            );
+        }
+    }
+
+    let mut statement = db_connection.prepare(&query)?;
+    let rows = statement.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+
+    Ok(rows.collect::<rusqlite::Result<_>>()?)
+}
+
+// Files in the store that have no FileInfo row
+fn orphan_files(db_connection: &Connection, lazer_path: &Path) -> Result<Vec<PathBuf>> {
+    let mut statement = db_connection.prepare("SELECT Hash FROM FileInfo")?;
+    let known = statement
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<HashSet<_>>>()?;
+
+    let files_path = lazer_path.join("files");
+    if !files_path.is_dir() {
+        return Ok(vec![]);
+    }
+
+    let mut orphans = vec![];
+    // files/a/ab/abcd...
+    for entry in WalkDir::new(&files_path).min_depth(3).max_depth(3) {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy();
+        let is_hash = name.len() == 64 && name.chars().all(|c| c.is_ascii_hexdigit());
+        if is_hash && !known.contains(name.as_ref()) {
+            orphans.push(entry.path().to_path_buf());
+        }
+    }
+
+    Ok(orphans)
+}
+
+// Space given back by removing a store file: none for links, which share it with osu!stable
+#[cfg(target_family = "unix")]
+fn freed(metadata: &Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+
+    if metadata.is_file() && metadata.nlink() == 1 {
+        metadata.len()
+    } else {
+        0
+    }
+}
+
+// Windows doesn't tell how many hard links a file has, so those count as freed too
+#[cfg(target_family = "windows")]
+fn freed(metadata: &Metadata) -> u64 {
+    if metadata.is_file() {
+        metadata.len()
+    } else {
+        0
+    }
+}
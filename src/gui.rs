@@ -0,0 +1,231 @@
+use anyhow::Result;
+use eframe::{
+    egui::{self, Color32, ScrollArea},
+    epi,
+};
+use indicatif::ProgressBar;
+use rfd::FileDialog;
+use std::{
+    path::PathBuf,
+    sync::mpsc::{channel, Receiver},
+    thread::spawn,
+};
+
+use crate::{
+    detect_stable_path, get_beatmaps, get_lazer_path, i18n::tr, import, open_lazer_db,
+    options::Options, report::Report, undo, State,
+};
+
+const STAGES: [&str; 4] = [
+    "stage-processing-beatmaps",
+    "stage-inserting-beatmaps",
+    "stage-processing-files",
+    "stage-inserting-files",
+];
+
+pub fn run(options: Options) -> ! {
+    // Pre-fill whatever can be found without asking, the rest is picked in the window
+    // Only a single osu!stable install and osu!lazer target can be picked here
+    let stable = match options.stable.first() {
+        Some(path) => Some(path.clone()),
+        None => detect_stable_path(),
+    };
+    let lazer = get_lazer_path(&options).ok();
+
+    let app = App {
+        options,
+        stable,
+        lazer,
+        error: None,
+        import: None,
+    };
+    eframe::run_native(Box::new(app), epi::NativeOptions::default())
+}
+
+struct App {
+    options: Options,
+    stable: Option<PathBuf>,
+    lazer: Option<PathBuf>,
+    error: Option<String>,
+    import: Option<Import>,
+}
+
+// An import running on its own thread, followed through the same progress bars the CLI draws
+struct Import {
+    bars: [ProgressBar; 4],
+    report: Report,
+    receiver: Receiver<Result<()>>,
+    result: Option<Result<(), String>>,
+}
+
+impl Import {
+    fn is_running(&self) -> bool {
+        self.result.is_none()
+    }
+
+    fn poll(&mut self) {
+        if let Ok(result) = self.receiver.try_recv() {
+            self.result = Some(result.map_err(|e| format!("{:?}", e)));
+        }
+    }
+}
+
+impl App {
+    fn start(&mut self) {
+        self.options.stable = self.stable.iter().cloned().collect();
+        self.options.lazer = self.lazer.iter().cloned().collect();
+        let state = match State::new(self.options.clone()) {
+            Ok(state) => state,
+            Err(e) => {
+                self.error = Some(format!("{:?}", e));
+                return;
+            }
+        };
+
+        let bars = [
+            state.progress_bars.beatmap.clone(),
+            state.progress_bars.beatmap_insert.clone(),
+            state.progress_bars.hash.clone(),
+            state.progress_bars.hash_insert.clone(),
+        ];
+        let report = state.report.clone();
+        let (sender, receiver) = channel();
+        spawn(move || {
+            sender.send(run_import(&state)).unwrap();
+        });
+
+        self.error = None;
+        self.import = Some(Import {
+            bars,
+            report,
+            receiver,
+            result: None,
+        });
+    }
+}
+
+impl epi::App for App {
+    fn name(&self) -> &str {
+        "osu-link"
+    }
+
+    fn update(&mut self, ctx: &egui::CtxRef, _frame: &mut epi::Frame<'_>) {
+        if let Some(import) = &mut self.import {
+            import.poll();
+        }
+        let running = self.import.as_ref().map_or(false, Import::is_running);
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.heading("osu-link");
+
+            ui.scope(|ui| {
+                ui.set_enabled(!running);
+
+                path_picker(ui, &tr!("gui-stable-path"), &mut self.stable, true);
+                path_picker(ui, &tr!("gui-lazer-path"), &mut self.lazer, true);
+                path_picker(ui, &tr!("gui-pool"), &mut self.options.link.pool, false);
+
+                ui.checkbox(
+                    &mut self.options.link.recalculate_sr,
+                    tr!("gui-recalculate-sr"),
+                );
+                ui.horizontal(|ui| {
+                    ui.label(tr!("gui-server"));
+                    ui.text_edit_singleline(&mut self.options.server);
+                });
+                if !self.options.is_bancho() {
+                    ui.checkbox(
+                        &mut self.options.keep_online_ids,
+                        tr!("gui-keep-online-ids"),
+                    );
+                }
+
+                ui.label(tr!("gui-warning"));
+                let ready = self.stable.is_some() && self.lazer.is_some();
+                if ui
+                    .add_enabled(ready, egui::Button::new(tr!("gui-import")))
+                    .clicked()
+                {
+                    self.start();
+                }
+            });
+
+            if let Some(error) = &self.error {
+                ui.colored_label(Color32::RED, error);
+            }
+
+            if let Some(import) = &self.import {
+                ui.separator();
+
+                for (stage, bar) in STAGES.iter().zip(&import.bars) {
+                    let (position, length) = (bar.position(), bar.length());
+                    let progress = if length > 0 {
+                        (position as f32 / length as f32).min(1.0)
+                    } else {
+                        0.0
+                    };
+
+                    ui.add(egui::ProgressBar::new(progress).text(format!(
+                        "{}: {}/{}",
+                        tr!(stage),
+                        position,
+                        length
+                    )));
+                }
+
+                match &import.result {
+                    Some(Ok(())) => {
+                        ui.label(tr!("done"));
+                    }
+                    Some(Err(e)) => {
+                        ui.colored_label(Color32::RED, e);
+                    }
+                    None => {}
+                }
+
+                let messages = import.report.messages();
+                ui.label(tr!("dashboard-messages-title", count = messages.len()));
+                ScrollArea::vertical().show(ui, |ui| {
+                    for message in messages {
+                        ui.label(message);
+                    }
+                });
+            }
+        });
+
+        if running {
+            ctx.request_repaint();
+        }
+    }
+}
+
+fn run_import(state: &State) -> Result<()> {
+    let mut db_connection = open_lazer_db(&state.lazer_db_path)?;
+    let (_, _, beatmaps) = get_beatmaps(state, &db_connection)?;
+    undo::back_up(&state.lazer_db_path)?;
+
+    import(state, &mut db_connection, beatmaps)
+}
+
+fn path_picker(ui: &mut egui::Ui, label: &str, path: &mut Option<PathBuf>, folder: bool) {
+    ui.horizontal(|ui| {
+        ui.label(format!("{}:", label));
+        ui.label(
+            path.as_ref()
+                .map_or(tr!("gui-none"), |path| path.display().to_string()),
+        );
+
+        if ui.button(tr!("gui-browse")).clicked() {
+            let dialog = FileDialog::new().set_title(label);
+            let picked = if folder {
+                dialog.pick_folder()
+            } else {
+                dialog.pick_file()
+            };
+
+            if picked.is_some() {
+                *path = picked;
+            }
+        }
+    });
+}
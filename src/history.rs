@@ -0,0 +1,116 @@
+use anyhow::{Context, Result};
+use chrono::Utc;
+use itertools::Itertools;
+use md5::{Digest, Md5};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::library::StableBeatmap;
+
+// What earlier runs left osu!lazer up to date with, kept between runs for --since-last-run
+#[derive(Serialize, Deserialize, Default)]
+struct History {
+    // By osu!lazer data directory, as each can be imported into separately
+    targets: HashMap<String, LastRun>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct LastRun {
+    // Unix timestamp
+    pub timestamp: i64,
+    // Snapshots of the sets osu!lazer was up to date with, by folder
+    pub sets: HashMap<String, String>,
+}
+
+// The sets of this run by folder, and which of them osu!lazer is up to date with once it's done
+#[derive(Default)]
+pub struct Snapshot {
+    pub sets: HashMap<String, String>,
+    pub handled: HashSet<String>,
+}
+
+pub fn path() -> Option<PathBuf> {
+    dirs::data_local_dir().map(|path| path.join("osu-link").join("state.json"))
+}
+
+fn read() -> Result<History> {
+    let path = path().context("No data directory?")?;
+    let contents = match fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(History::default()),
+        Err(e) => {
+            return Err(e).with_context(|| format!("Failed to read the state file at {:?}", path))
+        }
+    };
+
+    serde_json::from_str(&contents).with_context(|| {
+        format!(
+            "Failed to parse the state file at {:?}, delete it to start anew",
+            path
+        )
+    })
+}
+
+pub fn last_run(lazer_path: &Path) -> Result<Option<LastRun>> {
+    Ok(read()?.targets.remove(&key(lazer_path)))
+}
+
+// Saves the snapshots of the sets handled this run. Sets still left to import keep the snapshot of
+// when they were last handled, if ever, so they still count as changed on the next run
+pub fn record(lazer_path: &Path, snapshot: &Snapshot) -> Result<()> {
+    let mut history = read()?;
+    let key = key(lazer_path);
+
+    let mut sets = history
+        .targets
+        .remove(&key)
+        .map(|last_run| last_run.sets)
+        .unwrap_or_default();
+    sets.retain(|folder, _| snapshot.sets.contains_key(folder));
+    for folder in &snapshot.handled {
+        if let Some(hash) = snapshot.sets.get(folder) {
+            sets.insert(folder.clone(), hash.clone());
+        }
+    }
+
+    history.targets.insert(
+        key,
+        LastRun {
+            timestamp: Utc::now().timestamp(),
+            sets,
+        },
+    );
+
+    let path = path().context("No data directory?")?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&path, serde_json::to_string(&history)?)
+        .with_context(|| format!("Failed to write the state file at {:?}", path))
+}
+
+// An MD5 of the .osu hashes of each set's beatmaps, which changes whenever a difficulty is added,
+// removed or edited
+pub fn snapshot(beatmaps: &[StableBeatmap]) -> HashMap<String, String> {
+    beatmaps
+        .iter()
+        .into_group_map_by(|bm| bm.folder_name.clone())
+        .into_iter()
+        .map(|(folder, beatmaps)| {
+            let hashes = beatmaps
+                .iter()
+                .map(|bm| bm.hash.as_str())
+                .sorted()
+                .join(",");
+            (folder, format!("{:x}", Md5::digest(hashes.as_bytes())))
+        })
+        .collect()
+}
+
+fn key(lazer_path: &Path) -> String {
+    lazer_path.to_string_lossy().into_owned()
+}
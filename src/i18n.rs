@@ -0,0 +1,86 @@
+use fluent_bundle::{concurrent::FluentBundle, FluentArgs, FluentResource};
+use once_cell::sync::OnceCell;
+use unic_langid::LanguageIdentifier;
+
+// Bundled translations, as (locale, Fluent source). en-US is the baseline every other locale falls
+// back to for messages it doesn't translate (yet).
+const LOCALES: &[(&str, &str)] = &[("en-US", include_str!("../locales/en-US.ftl"))];
+const FALLBACK: &str = "en-US";
+
+static BUNDLES: OnceCell<Vec<FluentBundle<FluentResource>>> = OnceCell::new();
+
+// Translates a message, with optional `name = value` arguments:
+//   tr!("stable-beatmap-count", count = stable_len)
+macro_rules! tr {
+    ($id:expr) => {
+        $crate::i18n::message($id, None)
+    };
+    ($id:expr, $($name:ident = $value:expr),+ $(,)?) => {{
+        let mut args = ::fluent_bundle::FluentArgs::new();
+        $(args.set(stringify!($name), $value);)+
+        $crate::i18n::message($id, Some(&args))
+    }};
+}
+pub(crate) use tr;
+
+// Picks the language to use, from `preferred` (set in the config) or otherwise the OS locale.
+// Only takes effect if nothing has been translated yet.
+pub fn init(preferred: Option<&str>) {
+    let _ = BUNDLES.set(load(preferred));
+}
+
+pub fn message(id: &str, args: Option<&FluentArgs>) -> String {
+    for bundle in BUNDLES.get_or_init(|| load(None)) {
+        if let Some(pattern) = bundle.get_message(id).and_then(|message| message.value()) {
+            let mut errors = vec![];
+            return bundle
+                .format_pattern(pattern, args, &mut errors)
+                .into_owned();
+        }
+    }
+
+    id.to_string()
+}
+
+fn load(preferred: Option<&str>) -> Vec<FluentBundle<FluentResource>> {
+    let requested = preferred
+        .map(str::to_string)
+        .or_else(sys_locale::get_locale)
+        .and_then(|locale| locale.parse::<LanguageIdentifier>().ok());
+
+    let mut locales = vec![];
+    if let Some(requested) = requested {
+        let langid = |locale: &str| locale.parse::<LanguageIdentifier>().unwrap();
+
+        // An exact match if there is one, otherwise the same language from another region
+        let found = LOCALES
+            .iter()
+            .find(|(locale, _)| langid(locale) == requested)
+            .or_else(|| {
+                LOCALES
+                    .iter()
+                    .find(|(locale, _)| langid(locale).language == requested.language)
+            });
+        locales.extend(found.map(|(locale, _)| *locale));
+    }
+    if !locales.contains(&FALLBACK) {
+        locales.push(FALLBACK);
+    }
+
+    locales.into_iter().map(bundle).collect()
+}
+
+fn bundle(locale: &str) -> FluentBundle<FluentResource> {
+    let (_, source) = LOCALES.iter().find(|(id, _)| *id == locale).unwrap();
+    let resource =
+        FluentResource::try_new(source.to_string()).expect("Invalid bundled translation");
+
+    let mut bundle = FluentBundle::new(vec![locale.parse().unwrap()]);
+    // Unicode isolation marks around arguments show up as garbage in most terminals
+    bundle.set_use_isolating(false);
+    bundle
+        .add_resource(resource)
+        .expect("Duplicate message in bundled translation");
+
+    bundle
+}
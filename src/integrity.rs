@@ -0,0 +1,135 @@
+use anyhow::Result;
+use rusqlite::{params, Connection};
+
+use crate::{
+    exit::{self, ExitCode},
+    i18n::tr,
+};
+
+// Tables an import writes to
+const TABLES: [&str; 6] = [
+    "BeatmapDifficulty",
+    "BeatmapInfo",
+    "BeatmapMetadata",
+    "BeatmapSetFileInfo",
+    "BeatmapSetInfo",
+    "FileInfo",
+];
+
+// (table, column) pairs the inserts rely on having a unique index: the target of `ON CONFLICT`
+// in `insert_beatmapset_info`, and the hash -> ID cache (`file_ids`) with the plain `INSERT` in
+// `insert_file`, which assume a hash has exactly one FileInfo row
+const UNIQUE_INDEXES: [(&str, &str); 2] = [
+    ("BeatmapSetInfo", "OnlineBeatmapSetID"),
+    ("FileInfo", "Hash"),
+];
+
+// Makes sure lazer's database has what the import expects before anything is written, so a
+// schema that doesn't match is diagnosed up front instead of failing halfway through
+pub fn check_schema(db_connection: &Connection) -> Result<()> {
+    for table in TABLES {
+        let count: u32 = db_connection.query_row(
+            "SELECT COUNT(*)
+             FROM sqlite_master
+             WHERE type = 'table'
+               AND name = ?",
+            params![table],
+            |row| row.get(0),
+        )?;
+        if count == 0 {
+            return Err(exit::fail(
+                ExitCode::VersionMismatch,
+                tr!("error-missing-table", table = table),
+            ));
+        }
+    }
+
+    for (table, column) in UNIQUE_INDEXES {
+        let count: u32 = db_connection.query_row(
+            "SELECT COUNT(*)
+             FROM pragma_index_list(?1) l
+             JOIN pragma_index_info(l.name) i
+             WHERE l.\"unique\" = 1
+               AND i.name = ?2
+               AND (SELECT COUNT(*) FROM pragma_index_info(l.name)) = 1",
+            params![table, column],
+            |row| row.get(0),
+        )?;
+        if count == 0 {
+            return Err(exit::fail(
+                ExitCode::VersionMismatch,
+                tr!("error-missing-index", table = table, column = column),
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+// References lazer doesn't guard with foreign keys, or that a broken import could leave dangling
+const CHECKS: [(&str, &str); 5] = [
+    (
+        "integrity-beatmap-set",
+        "SELECT COUNT(*)
+         FROM BeatmapInfo
+         WHERE BeatmapSetInfoID NOT IN (SELECT ID FROM BeatmapSetInfo)",
+    ),
+    (
+        "integrity-beatmap-difficulty",
+        "SELECT COUNT(*)
+         FROM BeatmapInfo
+         WHERE BaseDifficultyID NOT IN (SELECT ID FROM BeatmapDifficulty)",
+    ),
+    (
+        "integrity-beatmap-metadata",
+        "SELECT COUNT(*)
+         FROM BeatmapInfo
+         WHERE MetadataID IS NOT NULL
+           AND MetadataID NOT IN (SELECT ID FROM BeatmapMetadata)",
+    ),
+    (
+        "integrity-set-metadata",
+        "SELECT COUNT(*)
+         FROM BeatmapSetInfo
+         WHERE MetadataID NOT IN (SELECT ID FROM BeatmapMetadata)",
+    ),
+    (
+        "integrity-set-file",
+        "SELECT COUNT(*)
+         FROM BeatmapSetFileInfo
+         WHERE FileInfoID NOT IN (SELECT ID FROM FileInfo)
+            OR BeatmapSetInfoID NOT IN (SELECT ID FROM BeatmapSetInfo)",
+    ),
+];
+
+// Looks over lazer's database once an import is committed, so problems are known before lazer is
+// started on a broken library. Returns a description of each problem found.
+pub fn check(db_connection: &Connection) -> Result<Vec<String>> {
+    let mut problems = vec![];
+
+    let mut statement = db_connection.prepare("PRAGMA integrity_check")?;
+    for row in statement.query_map([], |row| row.get::<_, String>(0))? {
+        let row = row?;
+        if row != "ok" {
+            problems.push(tr!("integrity-corrupt", details = row));
+        }
+    }
+
+    let mut statement = db_connection.prepare("PRAGMA foreign_key_check")?;
+    let rows = statement.query_map([], |row| {
+        Ok((row.get::<_, String>(0)?, row.get::<_, String>(2)?))
+    })?;
+    for row in rows {
+        let (table, parent) = row?;
+        problems.push(tr!("integrity-foreign-key", table = table, parent = parent));
+    }
+
+    for (id, query) in CHECKS {
+        let count: u32 = db_connection.query_row(query, [], |row| row.get(0))?;
+        if count > 0 {
+            problems.push(tr!(id, count = count));
+        }
+    }
+
+    Ok(problems)
+}
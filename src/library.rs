@@ -0,0 +1,175 @@
+use anyhow::Result;
+use libosu::{db::DbBeatmap, prelude::Mods};
+use md5::{Digest, Md5};
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use std::{
+    convert::TryInto,
+    fs,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use crate::{i18n::tr, parsing, WIN_TO_UNIX_EPOCH};
+
+// BeatmapSetOnlineStatus.None
+pub const STATUS_NONE: i8 = -3;
+
+// An osu!stable installation to import from
+#[derive(Clone)]
+pub struct StableInstall {
+    pub path: PathBuf,
+    pub songs_path: PathBuf,
+}
+
+// A difficulty in the osu!stable library, with everything the import needs to know about it.
+// Usually read from osu!.db, but can also be scanned straight from a Songs folder.
+#[derive(Clone)]
+pub struct StableBeatmap {
+    pub beatmap_id: u32,
+    pub beatmap_set_id: u32,
+    // The Songs folder of the install it's from, which `folder_name` is relative to
+    pub songs_path: PathBuf,
+    pub folder_name: String,
+    pub beatmap_file_name: String,
+    pub artist: String,
+    pub title: String,
+    pub creator: String,
+    pub source: String,
+    pub tags: Vec<String>,
+    // MD5 of the .osu
+    pub hash: String,
+    // As lazer's BeatmapSetOnlineStatus
+    pub status: i8,
+    // In windows ticks
+    pub modification_date: u64,
+    // In milliseconds, 0 if unknown
+    pub total_time: f64,
+    // No-mod star rating for each ruleset, in RulesetID order
+    pub star_ratings: [f64; 4],
+
+    // The osu!.db entry itself, if there is one
+    pub db_entry: Option<DbBeatmap>,
+}
+
+impl StableBeatmap {
+    pub fn from_db(db_beatmap: DbBeatmap, songs_path: &Path) -> Self {
+        let nomod = |ratings: &[(Mods, f64)]| {
+            ratings
+                .iter()
+                .find(|t| t.0 == Mods::None)
+                .map_or(0.0, |o| o.1)
+        };
+
+        Self {
+            beatmap_id: db_beatmap.beatmap_id,
+            beatmap_set_id: db_beatmap.beatmap_set_id,
+            songs_path: songs_path.to_path_buf(),
+            folder_name: db_beatmap.folder_name.clone(),
+            beatmap_file_name: db_beatmap.beatmap_file_name.clone(),
+            artist: db_beatmap.artist_name.clone(),
+            title: db_beatmap.song_title.clone(),
+            creator: db_beatmap.creator_name.clone(),
+            source: db_beatmap.song_source.clone(),
+            tags: split_tags(&db_beatmap.song_tags),
+            hash: db_beatmap.hash.clone(),
+            status: db_beatmap.ranked_status as i8 - 3,
+            modification_date: db_beatmap.modification_date,
+            total_time: db_beatmap.total_time.0 as f64,
+            star_ratings: [
+                nomod(&db_beatmap.std_star_rating),
+                nomod(&db_beatmap.std_taiko_rating),
+                nomod(&db_beatmap.std_ctb_rating),
+                nomod(&db_beatmap.std_mania_rating),
+            ],
+
+            db_entry: Some(db_beatmap),
+        }
+    }
+}
+
+// Builds the library from the .osu files of every set folder, for when there's no osu!.db.
+// Files that can't be read are reported through `print`.
+pub fn scan_songs(
+    songs_path: &Path,
+    print: &(dyn Fn(String) + Sync),
+) -> Result<Vec<StableBeatmap>> {
+    let mut set_paths = vec![];
+    for entry in fs::read_dir(songs_path)? {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            set_paths.push(entry.path());
+        }
+    }
+
+    let beatmaps = set_paths
+        .into_par_iter()
+        .flat_map_iter(|set_path| {
+            let folder_name = set_path.file_name().unwrap().to_string_lossy().into_owned();
+            let files: Vec<PathBuf> = match fs::read_dir(&set_path) {
+                Ok(files) => files.flatten().map(|file| file.path()).collect(),
+                Err(e) => {
+                    print(tr!(
+                        "error-scanning",
+                        path = format!("{:?}", set_path),
+                        error = e.to_string()
+                    ));
+                    vec![]
+                }
+            };
+
+            files
+                .into_iter()
+                .filter(|path| {
+                    path.extension()
+                        .map_or(false, |ext| ext.eq_ignore_ascii_case("osu"))
+                })
+                .filter_map(
+                    move |path| match scan_beatmap(songs_path, &folder_name, &path) {
+                        Ok(beatmap) => Some(beatmap),
+                        Err(e) => {
+                            print(tr!(
+                                "error-scanning",
+                                path = format!("{:?}", path),
+                                error = e.to_string()
+                            ));
+                            None
+                        }
+                    },
+                )
+        })
+        .collect();
+
+    Ok(beatmaps)
+}
+
+fn scan_beatmap(songs_path: &Path, folder_name: &str, path: &Path) -> Result<StableBeatmap> {
+    let contents = fs::read(path)?;
+    let (beatmap, _) = parsing::parse_beatmap(&contents)?;
+    let modified = fs::metadata(path)?.modified()?.duration_since(UNIX_EPOCH)?;
+
+    Ok(StableBeatmap {
+        // Unsubmitted maps are marked the same way osu!.db does
+        beatmap_id: beatmap.beatmap_id.try_into().unwrap_or(0),
+        beatmap_set_id: beatmap.beatmap_set_id.try_into().unwrap_or(u32::MAX),
+        songs_path: songs_path.to_path_buf(),
+        folder_name: folder_name.to_string(),
+        beatmap_file_name: path.file_name().unwrap().to_string_lossy().into_owned(),
+        artist: beatmap.artist.clone(),
+        title: beatmap.title.clone(),
+        creator: beatmap.creator.clone(),
+        source: beatmap.source.clone(),
+        tags: beatmap.tags.clone(),
+        hash: format!("{:x}", Md5::digest(&contents)),
+        status: STATUS_NONE,
+        modification_date: WIN_TO_UNIX_EPOCH + (modified.as_nanos() / 100) as u64,
+        total_time: 0.0,
+        star_ratings: [0.0; 4],
+
+        db_entry: None,
+    })
+}
+
+// osu!.db keeps the tags as they are in the .osu, separated by spaces
+pub fn split_tags(tags: &str) -> Vec<String> {
+    tags.split_whitespace().map(str::to_string).collect()
+}
@@ -0,0 +1,170 @@
+use anyhow::{anyhow, Result};
+use std::{fs, io, path::Path};
+
+use crate::{i18n::tr, library::StableInstall, options::LinkMode, paths};
+
+// A way of bringing a file from stable's Songs folder into lazer's file store
+pub trait LinkStrategy: Send + Sync {
+    fn link(&self, source: &Path, target: &Path) -> io::Result<()>;
+}
+
+pub struct HardLink;
+pub struct SymLink;
+// A copy-on-write clone on filesystems that support it (btrfs, XFS, APFS, ReFS), a copy elsewhere
+pub struct Reflink;
+pub struct CopyFile;
+// Takes the file out of stable, for leaving it behind altogether
+pub struct MoveFile;
+// Tries the first strategy, then the second if that fails
+pub struct Fallback<A, B>(pub A, pub B);
+
+impl LinkStrategy for HardLink {
+    fn link(&self, source: &Path, target: &Path) -> io::Result<()> {
+        fs::hard_link(source, target)
+    }
+}
+
+impl LinkStrategy for SymLink {
+    fn link(&self, source: &Path, target: &Path) -> io::Result<()> {
+        #[cfg(target_family = "unix")]
+        return std::os::unix::fs::symlink(source, target);
+        #[cfg(target_family = "windows")]
+        return std::os::windows::fs::symlink_file(source, target);
+    }
+}
+
+impl LinkStrategy for Reflink {
+    fn link(&self, source: &Path, target: &Path) -> io::Result<()> {
+        reflink::reflink_or_copy(source, target).map(|_| ())
+    }
+}
+
+impl LinkStrategy for CopyFile {
+    fn link(&self, source: &Path, target: &Path) -> io::Result<()> {
+        fs::copy(source, target).map(|_| ())
+    }
+}
+
+impl LinkStrategy for MoveFile {
+    fn link(&self, source: &Path, target: &Path) -> io::Result<()> {
+        // Renaming only works within a filesystem
+        fs::rename(source, target).or_else(|_| {
+            fs::copy(source, target)?;
+            fs::remove_file(source)
+        })
+    }
+}
+
+impl<A: LinkStrategy, B: LinkStrategy> LinkStrategy for Fallback<A, B> {
+    fn link(&self, source: &Path, target: &Path) -> io::Result<()> {
+        self.0
+            .link(source, target)
+            .or_else(|_| self.1.link(source, target))
+    }
+}
+
+// Turns --link-mode into a strategy, checking up front that it can work for every install. Hard
+// links are the default on Windows, where symlinks need extra privileges, and symlinks elsewhere.
+pub fn select(
+    mode: LinkMode,
+    lazer_path: &Path,
+    installs: &[StableInstall],
+) -> Result<Box<dyn LinkStrategy>> {
+    let mode = match mode {
+        LinkMode::Auto if cfg!(target_family = "windows") => LinkMode::Hardlink,
+        LinkMode::Auto => LinkMode::Symlink,
+        mode => mode,
+    };
+
+    // Files are linked from the Songs folder, which may be on another filesystem than the stable
+    // directory itself. Only checked when linking, since it writes test files on Windows.
+    let cross_filesystem = || {
+        installs
+            .iter()
+            .any(|install| !same_filesystem(lazer_path, &install.songs_path))
+    };
+
+    Ok(match mode {
+        LinkMode::Hardlink if cross_filesystem() => {
+            if symlink_check(lazer_path).is_err() {
+                return Err(anyhow!(tr!("error-hard-link")));
+            }
+            println!("{}", tr!("cross-filesystem"));
+            println!("{}", tr!("hardlink-fallback"));
+            // Installs on lazer's filesystem are still hard linked
+            Box::new(Fallback(HardLink, SymLink))
+        }
+        LinkMode::Hardlink => Box::new(HardLink),
+        LinkMode::Symlink => {
+            if symlink_check(lazer_path).is_err() {
+                return Err(anyhow!(tr!("error-hard-link")));
+            }
+            // Links into another filesystem silently break once it's unmounted
+            if cross_filesystem() {
+                println!("{}", tr!("cross-filesystem"));
+            }
+            Box::new(SymLink)
+        }
+        LinkMode::Reflink => Box::new(Reflink),
+        LinkMode::Copy => Box::new(CopyFile),
+        LinkMode::Move => Box::new(MoveFile),
+        LinkMode::Auto => unreachable!(),
+    })
+}
+
+// Whether files can be brought in without copying them, one way or another
+pub fn check(lazer_path: &Path, songs_path: &Path) -> Result<()> {
+    if same_filesystem(lazer_path, songs_path) {
+        Ok(())
+    } else {
+        symlink_check(lazer_path)
+    }
+}
+
+// Whether hard links can be made between the two directories
+#[cfg(target_family = "unix")]
+fn same_filesystem(a: &Path, b: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    match (a.metadata(), b.metadata()) {
+        (Ok(a), Ok(b)) => a.dev() == b.dev(),
+        _ => false,
+    }
+}
+
+// Windows has no device ID to compare, so this tries hard linking a test file instead
+#[cfg(target_family = "windows")]
+fn same_filesystem(lazer_path: &Path, songs_path: &Path) -> bool {
+    let lazer_path = paths::extended(lazer_path).join("_link_test");
+    let songs_path = paths::extended(songs_path).join("_link_test");
+
+    if fs::write(&lazer_path, "hello from osu-link!").is_err() {
+        return false;
+    }
+
+    let res = fs::hard_link(&lazer_path, &songs_path);
+
+    let _ = fs::remove_file(&lazer_path);
+    let _ = fs::remove_file(&songs_path);
+
+    res.is_ok()
+}
+
+// Symlinks on Windows need Developer Mode or SeCreateSymbolicLinkPrivilege (usually only held by
+// admins)
+fn symlink_check(lazer_path: &Path) -> Result<()> {
+    let lazer_path = paths::extended(lazer_path);
+    let target_path = lazer_path.join("_link_test");
+    let link_path = lazer_path.join("_symlink_test");
+
+    fs::write(&target_path, "hello from osu-link!")?;
+
+    let res = SymLink.link(&target_path, &link_path);
+
+    fs::remove_file(&target_path)?;
+    let _ = fs::remove_file(&link_path);
+
+    res?;
+
+    Ok(())
+}
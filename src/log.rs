@@ -0,0 +1,91 @@
+use anyhow::{Context, Result};
+use chrono::Local;
+use itertools::Itertools;
+use once_cell::sync::OnceCell;
+use std::{
+    env,
+    fs::{self, File, OpenOptions},
+    io::Write,
+    path::PathBuf,
+    sync::Mutex,
+};
+
+// -v/--verbose levels, each logging everything the ones before it do
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    // The command that was run, and its warnings and errors
+    Info = 1,
+    // Every beatmap and file imported
+    Debug = 2,
+    // Every row inserted, updated or deleted, with its key values
+    Trace = 3,
+}
+
+struct Log {
+    level: u64,
+    file: Mutex<File>,
+}
+
+static LOG: OnceCell<Log> = OnceCell::new();
+
+// Logs a message if running with at least `level` of verbosity. The message is only formatted then:
+//   log!(Level::Trace, "INSERT FileInfo Hash={}", hash)
+macro_rules! log {
+    ($level:expr, $($arg:tt)+) => {
+        if $crate::log::enabled($level) {
+            $crate::log::write(&format!($($arg)+));
+        }
+    };
+}
+pub(crate) use log;
+
+pub fn path() -> Option<PathBuf> {
+    dirs::data_local_dir().map(|path| path.join("osu-link").join("osu-link.log"))
+}
+
+// Opens the log for the given number of -v flags, returning where it is. Every run appends to the
+// same file, starting with the arguments it was given.
+pub fn init(verbose: u64) -> Result<Option<PathBuf>> {
+    if verbose == 0 {
+        return Ok(None);
+    }
+
+    let path = path().context("No data directory?")?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .with_context(|| format!("Failed to open the log at {:?}", path))?;
+
+    let _ = LOG.set(Log {
+        level: verbose,
+        file: Mutex::new(file),
+    });
+    write(&format!(
+        "osu-link {} {}",
+        env!("CARGO_PKG_VERSION"),
+        env::args().skip(1).join(" ")
+    ));
+
+    Ok(Some(path))
+}
+
+pub fn enabled(level: Level) -> bool {
+    LOG.get().map_or(false, |log| log.level >= level as u64)
+}
+
+// Failing to log isn't worth failing an import over, so errors are ignored
+pub fn write(message: &str) {
+    if let Some(log) = LOG.get() {
+        let mut file = log.file.lock().unwrap();
+        let _ = writeln!(
+            file,
+            "{} {}",
+            Local::now().format("%Y-%m-%d %H:%M:%S%.3f"),
+            message
+        );
+    }
+}
@@ -0,0 +1,103 @@
+use anyhow::Result;
+use itertools::Itertools;
+use rusqlite::{Connection, Transaction};
+use std::{fs, path::Path};
+
+use crate::{
+    log::{log, Level},
+    State,
+};
+
+// osu!lazer looks beatmaps up in the online.db of its data directory before asking the API, so the
+// rows of the imported beatmaps, and their sets, are copied there from the online.db given with
+// --online-db. Returns how many beatmaps were added.
+pub fn populate(state: &State, source: &Path, hashes: &[String]) -> Result<usize> {
+    let cache_path = state.lazer_path.join("online.db");
+    if !cache_path.exists() {
+        // osu!lazer only downloads its own when there's none, which a partial copy would prevent
+        fs::copy(source, &cache_path)?;
+        log!(Level::Debug, "Copied {:?} to {:?}", source, cache_path);
+        return Ok(hashes.len());
+    }
+    if fs::canonicalize(source)? == fs::canonicalize(&cache_path)? {
+        return Ok(0);
+    }
+
+    let mut cache = Connection::open(&cache_path)?;
+    // Can't be done inside a transaction
+    cache.execute("ATTACH DATABASE ? AS source", [source.to_string_lossy()])?;
+
+    let tx = cache.transaction()?;
+    tx.execute_batch("CREATE TEMP TABLE imported (checksum TEXT PRIMARY KEY)")?;
+    {
+        let mut insert = tx.prepare("INSERT OR IGNORE INTO temp.imported VALUES (?)")?;
+        for hash in hashes {
+            insert.execute([hash])?;
+        }
+    }
+
+    let beatmaps = copy_rows(
+        &tx,
+        "osu_beatmaps",
+        "beatmap_id",
+        "checksum IN (SELECT checksum FROM temp.imported)",
+    )?;
+    let sets = copy_rows(
+        &tx,
+        "osu_beatmapsets",
+        "beatmapset_id",
+        "beatmapset_id IN (
+             SELECT beatmapset_id
+             FROM source.osu_beatmaps
+             WHERE checksum IN (SELECT checksum FROM temp.imported)
+         )",
+    )?;
+    tx.execute_batch("DROP TABLE temp.imported")?;
+    tx.commit()?;
+    log!(
+        Level::Debug,
+        "Added {} beatmaps and {} sets to {:?}",
+        beatmaps,
+        sets,
+        cache_path
+    );
+
+    Ok(beatmaps)
+}
+
+// Copies the rows of the source's table matching the condition that the cache doesn't have yet.
+// Only the columns both have are copied, as online.db gains some from time to time.
+fn copy_rows(tx: &Transaction, table: &str, key: &str, condition: &str) -> Result<usize> {
+    let source_columns = columns(tx, "source", table)?;
+    let common = columns(tx, "main", table)?
+        .into_iter()
+        .filter(|column| source_columns.contains(column))
+        .map(|column| format!("\"{}\"", column))
+        .join(", ");
+    if common.is_empty() {
+        return Ok(0);
+    }
+
+    let copied = tx.execute(
+        &format!(
+            "INSERT INTO main.{0} ({1})
+             SELECT {1}
+             FROM source.{0}
+             WHERE {2}
+               AND {3} NOT IN (SELECT {3} FROM main.{0})",
+            table, common, condition, key
+        ),
+        [],
+    )?;
+
+    Ok(copied)
+}
+
+fn columns(tx: &Transaction, schema: &str, table: &str) -> Result<Vec<String>> {
+    let mut query = tx.prepare(&format!("PRAGMA {}.table_info({})", schema, table))?;
+    let columns = query
+        .query_map([], |row| row.get::<_, String>("name"))?
+        .collect::<rusqlite::Result<_>>()?;
+
+    Ok(columns)
+}
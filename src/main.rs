@@ -1,25 +1,103 @@
 use anyhow::{anyhow, Context, Result};
+use chrono::{Local, NaiveDate, TimeZone};
+use clap::{IntoApp, Parser};
 use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
 use itertools::Itertools;
 use libosu::db::{Db, DbBeatmap};
+use rand::{rngs::StdRng, seq::SliceRandom, thread_rng, Rng, SeedableRng};
+use regex::Regex;
 use rfd::FileDialog;
-use rusqlite::Connection;
+use rusqlite::{Connection, OpenFlags};
 use std::{
-    collections::HashSet,
+    collections::{HashMap, HashSet},
     convert::TryInto,
     fs::File,
     io::{stdin, stdout, BufRead, BufReader, Write},
     path::{Path, PathBuf},
-    sync::mpsc::channel,
+    process,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::channel,
+        Arc, Mutex,
+    },
     thread::spawn,
+    time::Instant,
 };
 
+mod analysis;
+mod budget;
+mod check_stable;
+mod clean;
+mod collection_db;
+mod config;
+mod control;
+mod dashboard;
 mod database;
+mod dedupe;
+mod detect;
+mod doctor;
+mod exit;
+mod export;
+mod export_collections;
+mod export_map;
+mod export_scores;
+mod gc;
+#[cfg(feature = "gui")]
+mod gui;
+mod history;
+mod i18n;
+mod integrity;
+mod library;
+mod link;
+mod log;
+mod lookup_cache;
+mod manifest;
+mod notify;
+mod options;
+mod parsing;
+mod paths;
+mod pool;
 mod processors;
-
-use crate::processors::{
-    context::{BeatmapProcessed, HashProcessed, HashRequest},
-    BeatmapProcessor, HashProcessor,
+mod progress_file;
+mod replays;
+mod report;
+mod scores_db;
+mod select;
+mod sql_trace;
+mod stable_db;
+mod staging;
+mod steps;
+mod storage;
+mod storyboard;
+mod theme;
+mod timings;
+mod undo;
+mod verify;
+mod via_lazer;
+
+use crate::{
+    budget::MemoryBudget,
+    config::Config,
+    control::Control,
+    dashboard::Dashboard,
+    exit::ExitCode,
+    history::{LastRun, Snapshot},
+    i18n::tr,
+    library::{StableBeatmap, StableInstall},
+    link::LinkStrategy,
+    log::{log, Level},
+    manifest::{self, KnownHashes, Manifest},
+    notify::Notifier,
+    options::{Command, ConflictPolicy, LinkOptions, Options},
+    processors::{
+        context::{HashRequest, Processed, ScanRequest},
+        BeatmapProcessor, HashProcessor, ScanProcessor,
+    },
+    progress_file::ProgressFile,
+    report::{FailureKind, Report},
+    steps::Step,
+    storage::Threads,
+    timings::{Stage, Timings},
 };
 
 // Uses a random 32 characters long hexadecimal string instead of calculating the sha256 of each map
@@ -27,7 +105,7 @@ use crate::processors::{
 const FAKE_HASH: bool = false;
 
 // The last SQLite migration ID, used for version checking
-const LAST_MIGRATION_ID: &str = "20210912144011_AddSamplesMatchPlaybackRate";
+const LAST_MIGRATION_ID: &str = database::MIGRATION_ID;
 
 // Difference between windows epoch (0001/01/01 12:00:00 UTC) to unix epoch (1970/01/01 12:00:00 UTC)
 // Units are in windows ticks; 1 tick = 100ns; 10 000 ticks = 1ms
@@ -35,116 +113,216 @@ const WIN_TO_UNIX_EPOCH: u64 = 621_355_968_000_000_000;
 
 struct ProgressBars {
     manager: MultiProgress,
+    overall: ProgressBar,
     beatmap: ProgressBar,
     beatmap_insert: ProgressBar,
     hash: ProgressBar,
     hash_insert: ProgressBar,
+    // One per reading thread, with --worker-progress
+    hash_workers: Vec<ProgressBar>,
 }
 
 struct ProgressStyles {
     length_unchanging: ProgressStyle,
     length_changing: ProgressStyle,
     waiting: ProgressStyle,
+    worker: ProgressStyle,
+    large_file: ProgressStyle,
 }
 
 pub struct State {
     pub lazer_path: PathBuf,
-    pub stable_path: PathBuf,
     pub lazer_db_path: PathBuf,
-    pub stable_db_path: PathBuf,
-    pub stable_songs_path: PathBuf,
-
-    db_online_connection: Connection,
+    pub stable: Vec<StableInstall>,
+    pub options: Options,
+    pub set_dates_supported: bool,
+    pub link_strategy: Box<dyn LinkStrategy>,
+    pub report: Report,
+    pub timings: Timings,
+    pub memory_budget: MemoryBudget,
+    pub threads: Threads,
+    pub manifest: Manifest,
+    // From `apply-manifest`'s manifest
+    pub known_hashes: Option<Arc<KnownHashes>>,
+    // The previous run's, for --since-last-run
+    pub last_run: Option<LastRun>,
+    // Recorded for the next --since-last-run once the import is done
+    pub snapshot: Mutex<Snapshot>,
+    // Folder of the set being inserted, for the progress file
+    pub current_set: Arc<Mutex<String>>,
+    // Set through the control socket, see `control`
+    pub cancelled: Arc<AtomicBool>,
+
+    db_online_connection: Option<Connection>,
     progress_bars: ProgressBars,
     progress_styles: ProgressStyles,
 }
 
 impl State {
-    fn new() -> Result<Self> {
-        let lazer_path = get_lazer_path()?;
+    fn new(options: Options) -> Result<Self> {
+        let lazer_path = get_lazer_path(&options)?;
 
         let mut lazer_db_path = lazer_path.clone();
         lazer_db_path.push("client.db");
         if !lazer_db_path.exists() {
-            return Err(anyhow!(
-                "Not a valid osu!lazer directory? (missing client.db)"
+            return Err(exit::fail(
+                ExitCode::PathNotFound,
+                tr!("error-invalid-lazer"),
             ));
         };
 
-        let mut lazer_online_db_path = lazer_path.clone();
-        lazer_online_db_path.push("online.db");
-        if !lazer_online_db_path.exists() {
-            return Err(anyhow!(
-                "Missing osu!lazer online.db, try opening the game, closing it, and then rerunning this tool?"
-            ));
+        let online_db_path = match &options.online_db {
+            Some(path) if !path.exists() => {
+                return Err(exit::fail(
+                    ExitCode::PathNotFound,
+                    tr!("error-online-db-not-found", path = format!("{:?}", path)),
+                ));
+            }
+            Some(path) => Some(path.clone()),
+            // lazer's online.db only knows about bancho's beatmaps
+            None if options.is_bancho() => {
+                let mut lazer_online_db_path = lazer_path.clone();
+                lazer_online_db_path.push("online.db");
+                if !lazer_online_db_path.exists() {
+                    return Err(exit::fail(
+                        ExitCode::PathNotFound,
+                        tr!("error-missing-online-db"),
+                    ));
+                };
+                Some(lazer_online_db_path)
+            }
+            None => None,
         };
 
-        let stable_path = get_stable_path()?;
-        let stable_db_path = stable_path.join("osu!.db");
-        let stable_songs_path = get_songs_directory(&stable_path)?;
+        let stable = get_stable_installs(&options)?;
 
-        #[cfg(target_family = "windows")]
-        if let Err(_) = windows_link_check(&lazer_path, &stable_path) {
-            return Err(anyhow!("Hard link test failed! On Windows, both lazer and stable must be on the same disk for linking to work."));
-        }
+        let link_strategy = link::select(options.link.link_mode, &lazer_path, &stable)?;
+        let threads = Threads::pick(&options.link, options.songs.as_deref(), &stable);
+
+        let known_hashes = match &options.link.from_manifest {
+            Some(path) => Some(Arc::new(manifest::read_hashes(path)?)),
+            None => None,
+        };
 
-        let db_online_connection =
-            Connection::open(&lazer_online_db_path).context("Failed to open online.db")?;
+        let last_run = if options.link.since_last_run {
+            history::last_run(&lazer_path)?
+        } else {
+            None
+        };
+
+        let db_online_connection = match online_db_path {
+            Some(path) => Some(Connection::open(&path).context(tr!("error-open-online-db"))?),
+            None => None,
+        };
+
+        let set_dates_supported = match &db_online_connection {
+            Some(online_db) if options.uses_online_ids() => {
+                database::supports_set_dates(&Connection::open(&lazer_db_path)?, online_db)?
+            }
+            _ => false,
+        };
 
+        // Elapsed time, ETA and rate, so a multi-hour import gives an idea of when it'll finish
+        let bar_template = theme::bar_template(format!(
+            "{{prefix}} {{msg:17}} [{{wide_bar}}] {{percent:>3}}% {{pos:>8}}/{{len:8}} \
+             {{elapsed_precise}} {} {{eta_precise}} {{per_sec:>9}}",
+            tr!("bar-eta")
+        ));
         let progress_styles = ProgressStyles {
-            length_unchanging: ProgressStyle::default_bar()
-                .template("{prefix} {msg:17} [{wide_bar}] {percent:>3}% {pos:>8}/{len:8}")
-                .progress_chars("=> "),
-            length_changing: ProgressStyle::default_bar()
-                .template("{prefix} {msg:17} [{wide_bar}] {percent:>3}% {pos:>8}/{len:8}")
-                .progress_chars("-> "),
-            waiting: ProgressStyle::default_spinner()
-                .tick_chars("⠁⠂⠄⡀⢀⠠⠐⠈")
-                .template("{prefix} {wide_msg} {spinner} /{len:8}"),
+            length_unchanging: theme::bar_style(&bar_template),
+            length_changing: theme::growing_bar_style(&bar_template),
+            waiting: theme::spinner_style(&theme::spinner_template(
+                "{prefix} {wide_msg} {spinner} /{len:8}",
+            )),
+            worker: theme::spinner_style("  {spinner} {prefix} {elapsed:>4} {wide_msg}"),
+            large_file: theme::bar_style(
+                "  {prefix:20!} [{wide_bar}] {bytes:>10}/{total_bytes:10} {bytes_per_sec:>12}",
+            ),
         };
 
         let manager = MultiProgress::new();
         manager.set_draw_target(ProgressDrawTarget::hidden());
 
+        let overall = manager
+            .add(ProgressBar::new(steps::STEPS as u64))
+            .with_prefix(bar_prefix("bar-overall"))
+            .with_style(theme::bar_style(&theme::bar_template(
+                "{prefix} {msg:40} [{wide_bar}] {elapsed_precise}".to_string(),
+            )));
+        steps::advance(&overall, Step::Parsing);
+
         let beatmap = manager
             .add(ProgressBar::new(0))
-            .with_prefix("Processing beatmaps:")
+            .with_prefix(bar_prefix("bar-processing-beatmaps"))
             .with_style(progress_styles.length_unchanging.clone());
         beatmap.tick();
 
         let beatmap_insert = manager
             .add(ProgressBar::new(0))
-            .with_prefix("Inserting beatmaps: ")
+            .with_prefix(bar_prefix("bar-inserting-beatmaps"))
             .with_style(progress_styles.length_changing.clone());
         beatmap_insert.tick();
 
         let hash = manager
             .add(ProgressBar::new(0))
-            .with_prefix("Processing files:   ")
+            .with_prefix(bar_prefix("bar-processing-files"))
             .with_style(progress_styles.length_changing.clone());
         hash.tick();
 
         let hash_insert = manager
             .add(ProgressBar::new(0))
-            .with_prefix("Inserting files:    ")
+            .with_prefix(bar_prefix("bar-inserting-files"))
             .with_style(progress_styles.waiting.clone())
-            .with_message("Waiting...");
+            .with_message(tr!("waiting"));
         hash_insert.enable_steady_tick(250);
 
+        let hash_workers = if options.link.worker_progress {
+            (0..threads.read)
+                .map(|i| {
+                    let bar = manager
+                        .add(ProgressBar::new_spinner())
+                        .with_prefix(tr!("bar-worker", worker = i + 1))
+                        .with_style(progress_styles.worker.clone())
+                        .with_message(tr!("worker-idle"));
+                    bar.enable_steady_tick(250);
+                    bar
+                })
+                .collect()
+        } else {
+            vec![]
+        };
+
+        let timings = Timings::new(options.link.timings);
+        let memory_budget =
+            MemoryBudget::new(options.link.memory_budget.saturating_mul(1024 * 1024));
+
         Ok(Self {
             lazer_path,
             lazer_db_path,
-            stable_path,
-            stable_db_path,
-            stable_songs_path,
+            stable,
+            options,
+            set_dates_supported,
+            link_strategy,
+            report: Report::default(),
+            timings,
+            memory_budget,
+            threads,
+            manifest: Manifest::default(),
+            known_hashes,
+            last_run,
+            snapshot: Mutex::default(),
+            current_set: Arc::default(),
+            cancelled: Arc::default(),
 
             db_online_connection,
             progress_bars: ProgressBars {
                 manager,
+                overall,
                 beatmap,
                 beatmap_insert,
                 hash,
                 hash_insert,
+                hash_workers,
             },
             progress_styles,
         })
@@ -157,123 +335,713 @@ impl State {
     }
 }
 
+// Bar prefixes are padded to the longest one, so the bars line up in every language
+fn bar_prefix(id: &str) -> String {
+    const PREFIXES: &[&str] = &[
+        "bar-overall",
+        "bar-processing-beatmaps",
+        "bar-inserting-beatmaps",
+        "bar-processing-files",
+        "bar-inserting-files",
+        "bar-database",
+    ];
+    let width = PREFIXES
+        .iter()
+        .map(|id| tr!(id).chars().count())
+        .max()
+        .unwrap_or(0);
+
+    format!("{:width$}", tr!(id), width = width)
+}
+
 fn main() {
-    if let Err(e) = run() {
-        eprintln!("Error: {:?}", e);
+    let code = match run() {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!(
+                "{}",
+                theme::error(&tr!("error", error = format!("{:?}", e)))
+            );
+
+            #[cfg(target_os = "windows")]
+            {
+                eprintln!();
+                eprint!("{}", tr!("press-enter-to-exit"));
+                stdout().flush().unwrap();
+                wait_for_input().unwrap();
+            }
+
+            exit::code_of(&e)
+        }
+    };
 
-        #[cfg(target_os = "windows")]
+    std::process::exit(code as i32);
+}
+
+fn run() -> Result<ExitCode> {
+    let mut options = Options::parse();
+    let config = Config::load()?;
+    i18n::init(config.language.as_deref());
+    theme::init(&config);
+    if let Some(path) = log::init(options.verbose)? {
+        println!("{}", tr!("log-file", path = format!("{:?}", path)));
+    }
+    if let Some(path) = &options.sql_trace {
+        sql_trace::init(path)?;
+    }
+
+    if let Some(profile) = &options.profile {
+        let path = config.profiles.get(profile).ok_or_else(|| {
+            exit::fail(
+                ExitCode::PathNotFound,
+                tr!("error-unknown-profile", profile = profile.as_str()),
+            )
+        })?;
+        options.lazer = vec![path.clone()];
+    }
+
+    // Picked on an earlier run, used unless it has since moved
+    if options.stable.is_empty() && options.songs.is_none() {
+        if let Some(path) = config
+            .stable
+            .as_ref()
+            .filter(|path| check_stable_path(path))
         {
-            eprintln!();
-            eprint!("Press enter to exit");
-            stdout().flush().unwrap();
-            wait_for_input().unwrap();
+            options.stable = vec![path.clone()];
         }
     }
+
+    match options.command.take() {
+        None => link(options, &config),
+        Some(Command::Link(link_options)) => {
+            options.link = link_options;
+            link(options, &config)
+        }
+        Some(Command::Update(link_options)) => {
+            options.link = link_options;
+            options.link.force = true;
+            options.link.update = true;
+            link(options, &config)
+        }
+        Some(Command::ApplyManifest {
+            manifest,
+            link: link_options,
+        }) => {
+            options.link = link_options;
+            options.link.from_manifest = Some(manifest);
+            link(options, &config)
+        }
+        Some(Command::ImportPool {
+            pool_file,
+            link: link_options,
+        }) => {
+            options.link = link_options;
+            options.link.pool = Some(pool_file);
+            link(options, &config)
+        }
+        Some(Command::ImportBracket {
+            bracket,
+            link: link_options,
+        }) => {
+            options.link = link_options;
+            options.link.bracket = Some(bracket);
+            link(options, &config)
+        }
+        Some(Command::Diff) => diff(options),
+        Some(Command::Verify { deep, sample }) => verify::verify(&options, deep, sample),
+        Some(Command::CheckStable) => check_stable::check_stable(&options),
+        Some(Command::Undo) => undo::undo(&options).map(|_| ExitCode::Success),
+        Some(Command::Dedupe) => dedupe::dedupe(&options),
+        Some(Command::Gc) => gc::gc(&options),
+        Some(Command::Clean) => clean::clean(&options),
+        Some(Command::Export { source }) => export::export(&options, source),
+        Some(Command::ExportCollections { replace }) => {
+            export_collections::export_collections(&options, replace)
+        }
+        Some(Command::ExportScores) => export_scores::export_scores(&options),
+        Some(Command::ExportMap { format, output }) => {
+            export_map::export_map(&options, format, &output)
+        }
+        Some(Command::Doctor) => doctor::doctor(&options).map(|_| ExitCode::Success),
+        Some(Command::Status { cancel, json }) => control::status(cancel, json),
+        Some(Command::Completions { shell }) => {
+            clap_complete::generate(
+                shell,
+                &mut Options::into_app(),
+                env!("CARGO_PKG_NAME"),
+                &mut stdout(),
+            );
+            Ok(ExitCode::Success)
+        }
+        #[cfg(feature = "gui")]
+        Some(Command::Gui) => gui::run(options),
+    }
 }
 
-fn run() -> Result<()> {
-    let state = State::new()?;
+// Links into each osu!lazer target in turn
+fn link(mut options: Options, config: &Config) -> Result<ExitCode> {
+    // The command line wins over the config file
+    options.link.read_threads = options.link.read_threads.or(config.read_threads);
+    options.link.hash_threads = options.link.hash_threads.or(config.hash_threads);
 
-    println!("Preparing...");
+    if options.lazer.len() <= 1 {
+        return link_target(options, config);
+    }
 
-    let mut db_connection = Connection::open(&state.lazer_db_path)?;
+    // Asked for once, rather than once per target
+    if options.stable.is_empty() && options.songs.is_none() {
+        options.stable = vec![get_stable_path()?];
+    }
 
-    if !check_version(&db_connection)? {
-        return Err(anyhow!("Database version mismatch! Please make sure you have the latest versions of both osu! and osu-link"));
+    let mut code = ExitCode::Success;
+    for lazer_path in options.lazer.clone() {
+        println!(
+            "{}",
+            tr!("lazer-target", path = format!("{:?}", lazer_path))
+        );
+
+        let mut target = options.clone();
+        target.lazer = vec![lazer_path];
+        if link_target(target, config)? == ExitCode::PartialSuccess {
+            code = ExitCode::PartialSuccess;
+        }
     }
 
-    let (stable_len, lazer_len, beatmaps) = get_beatmaps(&state, &db_connection)?;
+    Ok(code)
+}
+
+fn link_target(options: Options, config: &Config) -> Result<ExitCode> {
+    let state = State::new(options)?;
+    let notifier = Notifier::new(&state.options.link, config);
+
+    println!("{}", tr!("preparing"));
+
+    // Databases osu-link can't write to are what --via-lazer is for, so it only reads from it
+    let mut db_connection = if state.options.link.via_lazer {
+        Connection::open_with_flags(&state.lazer_db_path, OpenFlags::SQLITE_OPEN_READ_ONLY)?
+    } else {
+        open_lazer_db(&state.lazer_db_path)?
+    };
+
+    let (stable_len, lazer_len, mut beatmaps) = get_beatmaps(&state, &db_connection)?;
+
+    for install in &state.stable {
+        println!(
+            "{}",
+            tr!("stable-path", path = format!("{:?}", install.path))
+        );
+        println!(
+            "{}",
+            tr!(
+                "stable-songs-path",
+                path = format!("{:?}", install.songs_path)
+            )
+        );
+    }
+    println!(
+        "{}",
+        tr!("lazer-path", path = format!("{:?}", state.lazer_path))
+    );
+    println!("{}", tr!("stable-beatmap-count", count = stable_len));
+    println!("{}", tr!("lazer-beatmap-count", count = lazer_len));
+    if state.options.link.since_last_run {
+        match &state.last_run {
+            Some(last_run) => println!(
+                "{}",
+                tr!(
+                    "since-last-run",
+                    date = Local
+                        .timestamp(last_run.timestamp, 0)
+                        .format("%Y-%m-%d %H:%M")
+                        .to_string()
+                )
+            ),
+            None => println!("{}", tr!("since-last-run-none")),
+        }
+    }
+    if !state.options.is_bancho() {
+        println!("{}", tr!("server", server = state.options.server.as_str()));
+        if !state.options.uses_online_ids() {
+            println!("{}", tr!("local-import"));
+        }
+    }
+
+    // osu!lazer (as of LAST_MIGRATION_ID) has nowhere to store per-beatmap settings, so the best
+    // that can be done is letting the user know which preferences won't carry over
+    let overridden_len = beatmaps
+        .iter()
+        .filter(|bm| bm.db_entry.as_ref().map_or(false, has_visual_overrides))
+        .count();
+    if overridden_len > 0 {
+        println!("{}", tr!("overridden-settings", count = overridden_len));
+        println!("{}", tr!("overridden-settings-not-migrated"));
+    }
+
+    if state.options.link.interactive_select {
+        beatmaps = select::select_sets(beatmaps)?;
+        state
+            .progress_bars
+            .beatmap
+            .set_length(beatmaps.len().try_into()?);
+    }
+
+    if state.options.link.via_lazer {
+        return via_lazer::import(&state, beatmaps);
+    }
 
-    println!("Stable path: {:?}", state.stable_path);
-    println!("Stable songs path: {:?}", state.stable_songs_path);
-    println!("Lazer path: {:?}", state.lazer_path);
-    println!("Stable beatmap count: {}", stable_len);
-    println!("Lazer beatmap count: {}", lazer_len);
-    println!("Make sure both osu!stable and osu!lazer are closed!");
-    println!("Also back up your osu!lazer folder before continuing!");
-    print!("Press enter to continue, Ctrl+C to cancel");
+    println!("{}", tr!("close-games"));
+    println!("{}", tr!("back-up-lazer"));
+    print!("{}", tr!("press-enter-to-continue"));
     stdout().flush()?;
     wait_for_input()?;
 
-    state.show_progress();
+    undo::back_up(&state.lazer_db_path)?;
 
-    let (bm_sx, bm_rx) = channel::<BeatmapProcessed>();
+    let dashboard = if state.options.link.tui {
+        Some(Dashboard::start(&state))
+    } else {
+        state.show_progress();
+        None
+    };
+    let progress_file = ProgressFile::start(&state);
+    let control = Control::start(&state);
+
+    let imported: HashSet<String> = beatmaps.iter().map(|bm| bm.folder_name.clone()).collect();
+    let started = Instant::now();
+    let res = if state.options.link.stage_in_memory {
+        staging::import(&state, beatmaps)
+    } else {
+        import(&state, &mut db_connection, beatmaps)
+    };
+
+    let elapsed = started.elapsed().as_secs();
+    let message = match &res {
+        Ok(()) => tr!(
+            "notification-done",
+            beatmaps = state.progress_bars.beatmap_insert.position(),
+            files = state.progress_bars.hash_insert.position(),
+            elapsed = format!("{}:{:02}", elapsed / 60, elapsed % 60),
+            failures = state.report.failures()
+        ),
+        Err(e) => tr!("notification-failed", error = e.to_string()),
+    };
+    log!(Level::Info, "{}", message);
+    notifier.send(&state.report, &state.progress_bars.hash_insert, &message);
+    res?;
+
+    if let Some(dashboard) = dashboard {
+        dashboard.finish()?;
+    }
+    if let Some(progress_file) = progress_file {
+        progress_file.finish();
+    }
+    if let Some(control) = control {
+        control.finish();
+    }
+    if let Some(path) = &state.options.link.manifest {
+        state.manifest.write(path)?;
+        println!("{}", tr!("manifest-written", path = format!("{:?}", path)));
+    }
+    // `update` leaves out sets osu!lazer doesn't have at all, so it can't tell what's handled
+    if !state.options.link.update {
+        let mut snapshot = state.snapshot.lock().unwrap();
+        let failed = state.report.failed_sets();
+        snapshot.handled.extend(
+            imported
+                .into_iter()
+                .filter(|folder| !failed.contains(folder)),
+        );
+        history::record(&state.lazer_path, &snapshot)?;
+    }
+    state.timings.print();
+    for line in state.report.breakdown() {
+        println!("{}", theme::warning(&line));
+    }
+    after_import(&state.options.link)?;
+
+    if state.report.failures() > 0 {
+        Ok(ExitCode::PartialSuccess)
+    } else {
+        Ok(ExitCode::Success)
+    }
+}
+
+// osu!lazer only reads the database when it starts, so it either has to be started, or restarted
+// if it was left running
+fn after_import(link: &LinkOptions) -> Result<()> {
+    if detect::lazer_running() {
+        println!("{}", theme::warning(&tr!("warning-lazer-running")));
+        return Ok(());
+    }
+    if !link.launch_lazer {
+        return Ok(());
+    }
+
+    match detect::lazer_executable(link.lazer_exe.as_deref()) {
+        Some(exe) => {
+            process::Command::new(&exe).spawn()?;
+            println!("{}", tr!("lazer-launched"));
+        }
+        None => println!("{}", theme::warning(&tr!("warning-lazer-not-found"))),
+    }
+
+    Ok(())
+}
+
+// Lists the beatmapsets `link` would import
+fn diff(options: Options) -> Result<ExitCode> {
+    let state = State::new(options)?;
+    let db_connection = open_lazer_db(&state.lazer_db_path)?;
+    let (stable_len, lazer_len, beatmaps) = get_beatmaps(&state, &db_connection)?;
+
+    println!("{}", tr!("stable-beatmap-count", count = stable_len));
+    println!("{}", tr!("lazer-beatmap-count", count = lazer_len));
+
+    let sets = beatmaps
+        .iter()
+        .map(|bm| (bm.beatmap_set_id, bm.folder_name.as_str()))
+        .unique()
+        .sorted()
+        .collect_vec();
+    for (set_id, folder_name) in &sets {
+        println!("{:>7} {}", set_id, folder_name);
+    }
+    println!(
+        "{}",
+        tr!("diff-summary", beatmaps = beatmaps.len(), sets = sets.len())
+    );
+
+    Ok(ExitCode::Success)
+}
+
+// The import itself, shared by the CLI and the GUI. Progress goes to the state's progress bars
+fn import(
+    state: &State,
+    db_connection: &mut Connection,
+    beatmaps: Vec<StableBeatmap>,
+) -> Result<()> {
+    let hashes = beatmaps.iter().map(|bm| bm.hash.clone()).collect_vec();
+
+    // parse -> scan -> hash -> write, where only the writer touches the transaction
+    let (sx, rx) = channel::<Processed>();
+    let (scan_req_sx, scan_req_rx) = channel::<ScanRequest>();
     let (hash_req_sx, hash_req_rx) = channel::<HashRequest>();
-    let (hash_sx, hash_rx) = channel::<HashProcessed>();
 
     let b_ctx = BeatmapProcessor::new(&state);
+    let b_sx = sx.clone();
     let beatmap_thread = spawn(move || {
-        b_ctx.start(beatmaps, bm_sx);
+        b_ctx.start(beatmaps, b_sx, scan_req_sx);
+    });
+    let s_ctx = ScanProcessor::new(&state);
+    let scan_thread = spawn(move || {
+        s_ctx.start(hash_req_sx, scan_req_rx);
     });
     let h_ctx = HashProcessor::new(&state);
     let hash_thread = spawn(move || {
-        h_ctx.start(hash_sx, hash_req_rx);
+        h_ctx.start(sx, hash_req_rx);
     });
 
+    // The bars were created before the confirmation prompt, which shouldn't count towards them
+    for bar in [
+        &state.progress_bars.overall,
+        &state.progress_bars.beatmap,
+        &state.progress_bars.beatmap_insert,
+        &state.progress_bars.hash,
+        &state.progress_bars.hash_insert,
+    ] {
+        bar.reset_elapsed();
+        bar.reset_eta();
+    }
+
     let transaction = db_connection.transaction()?;
 
-    database::insert_beatmaps(&state, &transaction, bm_rx, hash_req_sx)?;
+    database::write(&state, &transaction, rx)?;
     state
         .progress_bars
         .beatmap_insert
-        .finish_with_message("Done.");
-
-    state.progress_bars.hash_insert.disable_steady_tick();
+        .finish_with_message(tr!("done"));
     state
         .progress_bars
         .hash_insert
-        .set_style(state.progress_styles.length_unchanging.clone());
-    database::insert_hashes(&state, &transaction, hash_rx)?;
-    state.progress_bars.hash_insert.finish_with_message("Done.");
+        .finish_with_message(tr!("done"));
 
     beatmap_thread.join().unwrap();
+    scan_thread.join().unwrap();
     hash_thread.join().unwrap();
 
-    let db_progress = ProgressBar::new_spinner()
-        .with_prefix("Database:           ")
-        .with_message("Committing")
-        .with_style(state.progress_styles.waiting);
+    // Only whole sets can be committed, which a cancelled import can't promise, so it's rolled
+    // back. Files linked already are left for `gc`.
+    if state.cancelled.load(Ordering::Relaxed) {
+        transaction.rollback()?;
+        return Err(exit::fail(ExitCode::Aborted, tr!("error-cancelled")));
+    }
+
+    steps::advance(&state.progress_bars.overall, Step::Committing);
+    let db_progress = state
+        .progress_bars
+        .manager
+        .add(ProgressBar::new_spinner())
+        .with_prefix(bar_prefix("bar-database"))
+        .with_message(tr!("relinking-replays"))
+        .with_style(state.progress_styles.waiting.clone());
     db_progress.tick();
-    transaction.commit()?;
-    db_progress.finish_with_message("Done.");
+    let relinked = replays::relink(&transaction, &state.lazer_path)?;
+    if relinked > 0 {
+        state
+            .report
+            .warn(&db_progress, tr!("replays-relinked", count = relinked));
+    }
+
+    db_progress.set_message(tr!("committing"));
+    state.timings.time(Stage::Commit, || transaction.commit())?;
+
+    db_progress.set_message(tr!("checking-database"));
+    for problem in integrity::check(db_connection)? {
+        state.report.error(&db_progress, problem);
+        state.report.add_failure(FailureKind::Integrity, None);
+    }
+
+    // Private servers' beatmap IDs would only mislead osu!lazer's lookups
+    match &state.options.online_db {
+        Some(source) if state.options.link.populate_lookup_cache => {
+            db_progress.set_message(tr!("populating-lookup-cache"));
+            if !state.options.uses_online_ids() {
+                state
+                    .report
+                    .warn(&db_progress, tr!("warning-lookup-cache-private-server"));
+            } else if let Err(e) = lookup_cache::populate(state, source, &hashes) {
+                state.report.warn(
+                    &db_progress,
+                    tr!("warning-lookup-cache", error = e.to_string()),
+                );
+            }
+        }
+        _ => {}
+    }
+    db_progress.finish_with_message(tr!("done"));
+    state.progress_bars.overall.finish_with_message(tr!("done"));
 
     Ok(())
 }
 
+fn open_lazer_db(lazer_db_path: &Path) -> Result<Connection> {
+    let mut db_connection = Connection::open(lazer_db_path)?;
+    sql_trace::attach(&mut db_connection);
+    if !check_version(&db_connection)? {
+        return Err(exit::fail(
+            ExitCode::VersionMismatch,
+            tr!("error-version-mismatch"),
+        ));
+    }
+    integrity::check_schema(&db_connection)?;
+
+    Ok(db_connection)
+}
+
 fn get_beatmaps(
     state: &State,
     db_connection: &Connection,
-) -> Result<(usize, usize, Vec<DbBeatmap>)> {
-    let fd = File::open(&state.stable_db_path)?;
-    let beatmaps = Db::parse(BufReader::new(fd))?.beatmaps;
-    let mut stable_beatmaps: HashSet<u32> = beatmaps.iter().map(|bm| bm.beatmap_id).collect();
-    let stable_len = stable_beatmaps.len();
+) -> Result<(usize, usize, Vec<StableBeatmap>)> {
+    let online_ids = state.options.uses_online_ids();
+
+    // osu!.db is often 100MB+, so osu!lazer's database is read while it's parsed
+    let bar = ProgressBar::new_spinner()
+        .with_style(theme::spinner_style(&theme::spinner_template(
+            "{spinner} {wide_msg}",
+        )))
+        .with_message(tr!("reading-databases", lazer = 0));
+    bar.enable_steady_tick(100);
+    let stable_thread = {
+        let options = state.options.clone();
+        let installs = state.stable.clone();
+        let timings = state.timings.clone();
+        let bar = bar.clone();
+        spawn(move || {
+            timings.time(Stage::StableDb, || {
+                // Printed above the spinner, which would otherwise draw over it
+                read_stable_beatmaps_with(&options, &installs, &|line| bar.println(line))
+            })
+        })
+    };
+    let lazer_beatmaps = state.timings.time(Stage::LazerQuery, || {
+        read_lazer_beatmaps(db_connection, online_ids, state.options.link.update, &bar)
+    })?;
+    let beatmaps = stable_thread.join().unwrap()?;
+    bar.finish_with_message(tr!(
+        "read-databases",
+        stable = beatmaps.len(),
+        lazer = lazer_beatmaps.len()
+    ));
 
-    let mut query = db_connection.prepare(
-        "
-        SELECT OnlineBeatmapID
-        FROM BeatmapInfo
-        WHERE OnlineBeatmapID NOT NULL
-    ",
-    )?;
+    // Local maps have no online ID to compare with, but their hash works just as well
+    let key = |bm: &StableBeatmap| {
+        if online_ids {
+            bm.beatmap_id.to_string()
+        } else {
+            bm.hash.clone()
+        }
+    };
+
+    let mut stable_beatmaps: HashSet<String> = beatmaps.iter().map(key).collect();
+    let stable_len = stable_beatmaps.len();
 
-    let lazer_beatmaps = query.query_map([], |row| row.get::<_, u32>(0))?;
-    let mut lazer_len = 0;
+    let pool = match (&state.options.link.pool, &state.options.link.bracket) {
+        (Some(pool), _) => Some(pool::read_pool(pool)?),
+        (_, Some(bracket)) => Some(pool::read_bracket(bracket)?),
+        _ => None,
+    };
+    let pool = match pool {
+        Some(pool) => {
+            let pool: HashSet<u32> = pool.into_iter().collect();
+            let stable_ids: HashSet<u32> = beatmaps.iter().map(|bm| bm.beatmap_id).collect();
+
+            let missing = pool.difference(&stable_ids).sorted().collect_vec();
+            if !missing.is_empty() {
+                println!("{}", tr!("pool-missing", ids = missing.iter().join(", ")));
+            }
 
-    for b in lazer_beatmaps {
-        lazer_len += 1;
-        stable_beatmaps.remove(&b?);
+            Some(pool)
+        }
+        _ => None,
+    };
+
+    let lazer_len = lazer_beatmaps.len();
+    match lazer_beatmaps {
+        LazerBeatmaps::Details(details) => {
+            let changed = changed_beatmaps(&details, &beatmaps, online_ids);
+            stable_beatmaps = changed.iter().map(|bm| key(bm)).collect();
+        }
+        LazerBeatmaps::Hashes(hashes) => {
+            // Beatmaps edited since they were imported are left to --on-conflict
+            let stable_hashes: HashMap<String, &str> =
+                if online_ids && state.options.link.on_conflict != ConflictPolicy::Skip {
+                    beatmaps
+                        .iter()
+                        .map(|bm| (key(bm), bm.hash.as_str()))
+                        .collect()
+                } else {
+                    HashMap::new()
+                };
+
+            for (b, hash) in hashes {
+                let edited = stable_hashes
+                    .get(&b)
+                    .map_or(false, |stable_hash| hash.as_deref() != Some(*stable_hash));
+                if !state.options.link.force && !edited {
+                    stable_beatmaps.remove(&b);
+                }
+            }
+        }
     }
 
+    // Sets osu!lazer is already up to date with are handled whether or not anything is imported
+    let sets = history::snapshot(&beatmaps);
+    let pending: HashSet<&str> = beatmaps
+        .iter()
+        .filter(|bm| stable_beatmaps.contains(&key(bm)))
+        .map(|bm| bm.folder_name.as_str())
+        .collect();
+    let handled = sets
+        .keys()
+        .filter(|folder| !pending.contains(folder.as_str()))
+        .cloned()
+        .collect();
+
+    let link = &state.options.link;
+    let matches = |filter: &Option<Regex>, value: &str| {
+        filter
+            .as_ref()
+            .map_or(true, |filter| filter.is_match(value))
+    };
+    let modified_after = link.modified_after.map(date_to_windows_ticks);
+    let online_sets = if link.genre.is_empty() && link.language.is_empty() {
+        None
+    } else {
+        let online_db = state
+            .db_online_connection
+            .as_ref()
+            .ok_or_else(|| anyhow!(tr!("error-genre-without-online-db")))?;
+        Some(database::sets_by_genre_and_language(
+            online_db,
+            &link.genre,
+            &link.language,
+        )?)
+    };
     let mut beatmaps = beatmaps
         .into_iter()
         .filter(|bm| {
-            stable_beatmaps.contains(&bm.beatmap_id) &&
-            // TODO: unsubmitted maps
-            bm.beatmap_id != 0 &&
-            bm.beatmap_set_id != u32::MAX
+            stable_beatmaps.contains(&key(bm)) &&
+            modified_after.map_or(true, |ticks| bm.modification_date >= ticks) &&
+            matches(&link.filter_artist, &bm.artist) &&
+            matches(&link.filter_title, &bm.title) &&
+            matches(&link.filter_creator, &bm.creator) &&
+            online_sets.as_ref().map_or(true, |sets| sets.contains(&bm.beatmap_set_id)) &&
+            link.filter_tags.as_ref().map_or(true, |filter| {
+                filter.is_match(&bm.source) || bm.tags.iter().any(|tag| filter.is_match(tag))
+            }) &&
+            state.last_run.as_ref().map_or(true, |last_run| {
+                last_run.sets.get(&bm.folder_name) != sets.get(&bm.folder_name)
+            }) &&
+            // TODO: unsubmitted maps, unless everything is imported as local anyway
+            (!online_ids || (bm.beatmap_id != 0 && bm.beatmap_set_id != u32::MAX)) &&
+            pool.as_ref().map_or(true, |pool| pool.contains(&bm.beatmap_id)) &&
+            state.known_hashes.as_ref().map_or(true, |known| known.contains_key(&bm.folder_name))
         })
         .collect_vec();
+    *state.snapshot.lock().unwrap() = Snapshot { sets, handled };
+
+    // osu!.db often still lists sets whose folder was deleted by hand, which are left out up front
+    // instead of failing one by one
+    let mut missing_folders = HashSet::new();
+    for (songs_path, folder_name) in beatmaps
+        .iter()
+        .map(|bm| (&bm.songs_path, &bm.folder_name))
+        .unique()
+    {
+        if !paths::resolve(&paths::extended(songs_path), folder_name).is_dir() {
+            missing_folders.insert((songs_path.clone(), folder_name.clone()));
+        }
+    }
+    if !missing_folders.is_empty() {
+        beatmaps.retain(|bm| {
+            !missing_folders.contains(&(bm.songs_path.clone(), bm.folder_name.clone()))
+        });
+
+        println!("{}", tr!("missing-folders", count = missing_folders.len()));
+        for (songs_path, folder_name) in missing_folders.iter().sorted() {
+            println!("  {}", songs_path.join(folder_name).display());
+        }
+    }
+
+    if let Some(limit) = link.limit {
+        let kept: HashSet<(PathBuf, String)> = beatmaps
+            .iter()
+            .map(|bm| (bm.songs_path.clone(), bm.folder_name.clone()))
+            .unique()
+            .sorted_by(|a, b| a.1.cmp(&b.1))
+            .skip(link.offset)
+            .take(limit)
+            .collect();
+        beatmaps.retain(|bm| kept.contains(&(bm.songs_path.clone(), bm.folder_name.clone())));
+    }
+
+    if let Some(sample) = link.sample {
+        let seed = link.seed.unwrap_or_else(|| thread_rng().gen());
+        println!("{}", tr!("sample-seed", seed = seed.to_string()));
+
+        // Sorted first, so the same seed picks the same sets whatever order osu!.db lists them in
+        let sets = beatmaps
+            .iter()
+            .map(|bm| (bm.songs_path.clone(), bm.folder_name.clone()))
+            .unique()
+            .sorted()
+            .collect_vec();
+        let kept: HashSet<&(PathBuf, String)> = sets
+            .choose_multiple(&mut StdRng::seed_from_u64(seed), sample)
+            .collect();
+        beatmaps.retain(|bm| kept.contains(&(bm.songs_path.clone(), bm.folder_name.clone())));
+    }
+
     beatmaps.sort_unstable_by(|a, b| a.beatmap_id.cmp(&b.beatmap_id));
     state
         .progress_bars
@@ -283,10 +1051,248 @@ fn get_beatmaps(
     Ok((stable_len, lazer_len, beatmaps))
 }
 
+// Local midnight of the date, in the windows ticks osu!.db stores its dates in
+fn date_to_windows_ticks(date: NaiveDate) -> u64 {
+    let timestamp = Local
+        .from_local_datetime(&date.and_hms(0, 0, 0))
+        .earliest()
+        .map_or(0, |datetime| datetime.timestamp());
+
+    WIN_TO_UNIX_EPOCH + timestamp.max(0) as u64 * 10_000_000
+}
+
+// What osu!lazer has, to compare osu!.db with
+enum LazerBeatmaps {
+    // (key, MD5 hash) of each beatmap
+    Hashes(Vec<(String, Option<String>)>),
+    // (MD5 hash, artist, title) of each beatmap by key, for --update
+    Details(HashMap<String, (Option<String>, Option<String>, Option<String>)>),
+}
+
+impl LazerBeatmaps {
+    fn len(&self) -> usize {
+        match self {
+            Self::Hashes(hashes) => hashes.len(),
+            Self::Details(details) => details.len(),
+        }
+    }
+}
+
+// Keyed by online ID, or for local maps by hash, or by file name with --update as that's what
+// stays the same when a .osu is edited
+fn read_lazer_beatmaps(
+    db_connection: &Connection,
+    online_ids: bool,
+    update: bool,
+    bar: &ProgressBar,
+) -> Result<LazerBeatmaps> {
+    let count = |read: usize| {
+        if read % 1000 == 0 {
+            bar.set_message(tr!("reading-databases", lazer = read));
+        }
+    };
+
+    if update {
+        let mut query = db_connection.prepare(&format!(
+            "SELECT {}, b.MD5Hash, m.Artist, m.Title
+             FROM BeatmapInfo b
+             JOIN BeatmapSetInfo s ON s.ID = b.BeatmapSetInfoID
+             JOIN BeatmapMetadata m ON m.ID = COALESCE(b.MetadataID, s.MetadataID)
+             WHERE {0} NOT NULL",
+            if online_ids {
+                "CAST(b.OnlineBeatmapID AS TEXT)"
+            } else {
+                "b.Path"
+            }
+        ))?;
+        let mut details = HashMap::new();
+        for row in query.query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                (
+                    row.get::<_, Option<String>>(1)?,
+                    row.get::<_, Option<String>>(2)?,
+                    row.get::<_, Option<String>>(3)?,
+                ),
+            ))
+        })? {
+            let (key, detail) = row?;
+            details.insert(key, detail);
+            count(details.len());
+        }
+        return Ok(LazerBeatmaps::Details(details));
+    }
+
+    let mut query = db_connection.prepare(if online_ids {
+        "
+        SELECT CAST(OnlineBeatmapID AS TEXT), MD5Hash
+        FROM BeatmapInfo
+        WHERE OnlineBeatmapID NOT NULL
+    "
+    } else {
+        "
+        SELECT MD5Hash, MD5Hash
+        FROM BeatmapInfo
+        WHERE MD5Hash NOT NULL
+    "
+    })?;
+    let mut hashes = vec![];
+    for row in query.query_map([], |row| {
+        Ok((row.get::<_, String>(0)?, row.get::<_, Option<String>>(1)?))
+    })? {
+        hashes.push(row?);
+        count(hashes.len());
+    }
+
+    Ok(LazerBeatmaps::Hashes(hashes))
+}
+
+// The beatmaps osu!lazer has, but whose .osu or metadata has changed in osu!stable since, for
+// `update`. Local maps are matched by file name, as their hash is what changed.
+fn changed_beatmaps<'a>(
+    lazer_beatmaps: &HashMap<String, (Option<String>, Option<String>, Option<String>)>,
+    beatmaps: &'a [StableBeatmap],
+    online_ids: bool,
+) -> Vec<&'a StableBeatmap> {
+    beatmaps
+        .iter()
+        .filter(|bm| {
+            let key = if online_ids {
+                bm.beatmap_id.to_string()
+            } else {
+                bm.beatmap_file_name.clone()
+            };
+            match lazer_beatmaps.get(&key) {
+                Some((hash, artist, title)) => {
+                    hash.as_deref() != Some(bm.hash.as_str())
+                        || artist.as_deref() != Some(bm.artist.as_str())
+                        || title.as_deref() != Some(bm.title.as_str())
+                }
+                None => false,
+            }
+        })
+        .collect()
+}
+
+fn read_stable_beatmaps(
+    options: &Options,
+    installs: &[StableInstall],
+) -> Result<Vec<StableBeatmap>> {
+    read_stable_beatmaps_with(options, installs, &|line| println!("{}", line))
+}
+
+// `print` shows progress and warnings, as the caller may have a progress bar running
+fn read_stable_beatmaps_with(
+    options: &Options,
+    installs: &[StableInstall],
+    print: &(dyn Fn(String) + Sync),
+) -> Result<Vec<StableBeatmap>> {
+    let mut beatmaps = vec![];
+    for install in installs {
+        match &options.songs {
+            Some(songs) => {
+                print(tr!("scanning-songs", path = format!("{:?}", songs)));
+                beatmaps.extend(library::scan_songs(songs, print)?);
+            }
+            None => {
+                let db_path = install.path.join("osu!.db");
+                let fd = File::open(&db_path)?;
+                match Db::parse(BufReader::new(fd)) {
+                    Ok(db) => beatmaps.extend(
+                        db.beatmaps
+                            .into_iter()
+                            .map(|bm| StableBeatmap::from_db(bm, &install.songs_path)),
+                    ),
+                    // Very old or very new versions, which are read as far as possible instead
+                    Err(e) => {
+                        let db = stable_db::read_lenient(&db_path, &install.songs_path)?;
+                        print(theme::warning(&tr!(
+                            "warning-stable-db-version",
+                            version = db.version,
+                            error = e.to_string()
+                        )));
+                        if db.beatmaps.len() < db.expected {
+                            print(theme::warning(&tr!(
+                                "warning-stable-db-partial",
+                                read = db.beatmaps.len(),
+                                expected = db.expected
+                            )));
+                        }
+                        beatmaps.extend(db.beatmaps);
+                    }
+                }
+            }
+        }
+    }
+
+    // A difficulty in several installs is only imported once, from an install whose folder
+    // actually has it
+    if installs.len() > 1 {
+        beatmaps.sort_by_cached_key(|bm| {
+            let set_path = paths::resolve(&paths::extended(&bm.songs_path), &bm.folder_name);
+            !paths::resolve(&set_path, &bm.beatmap_file_name).exists()
+        });
+        let mut seen = HashSet::new();
+        beatmaps.retain(|bm| seen.insert(bm.hash.clone()));
+    }
+
+    Ok(beatmaps)
+}
+
+fn has_visual_overrides(beatmap: &DbBeatmap) -> bool {
+    beatmap.ignore_beatmap_sound
+        || beatmap.ignore_beatmap_skin
+        || beatmap.disable_storyboard
+        || beatmap.disable_video
+        || beatmap.visual_override
+}
+
+// The osu!stable installs to import from, each given with --stable, or the Songs folder given with
+// --songs. Falls back to detecting (or prompting for) a single install.
+fn get_stable_installs(options: &Options) -> Result<Vec<StableInstall>> {
+    if let Some(songs) = &options.songs {
+        if !songs.is_dir() {
+            return Err(exit::fail(
+                ExitCode::PathNotFound,
+                tr!("error-songs-not-found", path = format!("{:?}", songs)),
+            ));
+        }
+
+        return Ok(vec![StableInstall {
+            path: songs.parent().unwrap_or(songs).to_path_buf(),
+            songs_path: songs.clone(),
+        }]);
+    }
+
+    let stable_paths = if options.stable.is_empty() {
+        vec![get_stable_path()?]
+    } else {
+        options.stable.clone()
+    };
+
+    stable_paths
+        .into_iter()
+        .map(|path| {
+            if !check_stable_path(&path) {
+                return Err(exit::fail(
+                    ExitCode::PathNotFound,
+                    tr!("error-invalid-stable"),
+                ));
+            }
+
+            Ok(StableInstall {
+                songs_path: get_songs_directory(&path)?,
+                path,
+            })
+        })
+        .collect()
+}
+
 fn get_songs_directory(stable_path: &Path) -> Result<PathBuf> {
-    let username = whoami::username();
-    let mut path = stable_path.to_path_buf();
-    path.push(format!("osu!.{}.cfg", username));
+    let path = match find_user_config(stable_path) {
+        Some(path) => path,
+        None => return Ok(stable_path.join("Songs")),
+    };
 
     let fd = File::open(path)?;
     let reader = BufReader::new(fd);
@@ -296,10 +1302,10 @@ fn get_songs_directory(stable_path: &Path) -> Result<PathBuf> {
 
         if line.starts_with("BeatmapDirectory") {
             let parts = line.split('=').collect_vec();
-
-            let mut path = stable_path.to_path_buf();
-            path.push(parts.get(1).unwrap().trim());
-            return Ok(path);
+            return Ok(resolve_beatmap_directory(
+                stable_path,
+                parts.get(1).unwrap().trim(),
+            ));
         }
     }
 
@@ -308,6 +1314,57 @@ fn get_songs_directory(stable_path: &Path) -> Result<PathBuf> {
     Ok(path)
 }
 
+// BeatmapDirectory is usually relative to the stable directory, but can also be an absolute path
+// on another drive (`D:\Songs`) or a network share (`\\server\Songs`)
+fn resolve_beatmap_directory(stable_path: &Path, value: &str) -> PathBuf {
+    let bytes = value.as_bytes();
+    let has_drive = bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':';
+    let is_unc = value.starts_with("\\\\");
+
+    // Under Wine, drive letters are symlinks in the prefix's dosdevices folder
+    #[cfg(not(target_os = "windows"))]
+    if has_drive {
+        let prefix = stable_path
+            .ancestors()
+            .find(|path| path.join("dosdevices").is_dir());
+        if let Some(prefix) = prefix {
+            let rest = value[2..].trim_start_matches('\\').replace('\\', "/");
+            return prefix
+                .join("dosdevices")
+                .join(value[..2].to_lowercase())
+                .join(rest);
+        }
+    }
+
+    if has_drive || is_unc {
+        PathBuf::from(value)
+    } else {
+        stable_path.join(value)
+    }
+}
+
+// The current user's `osu!.<username>.cfg`. Installs migrated from another machine or user only
+// have someone else's, in which case the most recently modified one is used.
+fn find_user_config(stable_path: &Path) -> Option<PathBuf> {
+    let path = stable_path.join(format!("osu!.{}.cfg", whoami::username()));
+    if path.exists() {
+        return Some(path);
+    }
+
+    stable_path
+        .read_dir()
+        .ok()?
+        .flatten()
+        .filter(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            // osu!.cfg holds the settings shared by every user
+            name.starts_with("osu!.") && name.ends_with(".cfg") && name != "osu!.cfg"
+        })
+        .filter_map(|entry| Some((entry.metadata().ok()?.modified().ok()?, entry.path())))
+        .max_by_key(|(modified, _)| *modified)
+        .map(|(_, path)| path)
+}
+
 fn check_version(conn: &Connection) -> Result<bool> {
     let last_migration: String = conn.query_row(
         "SELECT MigrationId FROM __EFMigrationsHistory
@@ -325,50 +1382,80 @@ fn check_stable_path(path: &Path) -> bool {
 }
 
 fn prompt_stable_path() -> Result<PathBuf> {
-    print!("You will be prompted to select the path to your osu!stable directory, press enter to continue");
+    print!("{}", tr!("prompt-stable-path"));
     stdout().flush()?;
     wait_for_input()?;
 
     let path = FileDialog::new()
-        .set_title("Select the path to your osu!stable directory")
+        .set_title(&tr!("select-stable-path"))
         .set_directory(dirs::data_dir().unwrap_or_else(|| "/".into()))
         .pick_folder()
-        .context("Failed to select the directory..?")?;
+        .ok_or_else(|| exit::fail(ExitCode::Aborted, tr!("error-select-directory")))?;
 
     if check_stable_path(&path) {
         Ok(path)
     } else {
-        Err(anyhow!(
-            "Not a valid osu!stable directory? (missing osu!.db)"
+        Err(exit::fail(
+            ExitCode::PathNotFound,
+            tr!("error-invalid-stable"),
         ))
     }
 }
 
 fn get_stable_path() -> Result<PathBuf> {
-    // https://osu.ppy.sh/wiki/en/osu%21_Program_Files#installation-paths
-    #[cfg(target_os = "macos")]
-    let path = Some(PathBuf::from(
-        "/Applications/osu!.app/Contents/Resources/drive_c/osu!",
-    ));
+    if let Some(path) = detect_stable_path() {
+        return Ok(path);
+    }
 
-    #[cfg(target_os = "linux")]
-    let path = dirs::data_local_dir().map(|p| p.join("osu!"));
+    let path = prompt_stable_path()?;
+    if let Err(e) = Config::save_stable(&path) {
+        println!(
+            "{}",
+            theme::warning(&tr!("warning-save-config", error = e.to_string()))
+        );
+    }
+    Ok(path)
+}
 
-    #[cfg(target_os = "windows")]
-    let path = get_stable_path_from_registry().ok();
+// The osu!stable directory at one of its usual installation paths, if there is one
+fn detect_stable_path() -> Option<PathBuf> {
+    detect::stable_candidates()
+        .into_iter()
+        .find(|path| check_stable_path(path))
+}
+
+fn get_lazer_path(options: &Options) -> Result<PathBuf> {
+    if let Some(path) = options.lazer.first() {
+        return Ok(path.clone());
+    }
+
+    let mut candidates = vec![dirs::data_dir()
+        .ok_or_else(|| exit::fail(ExitCode::PathNotFound, tr!("error-no-data-directory")))?
+        .join("osu")];
+
+    // Sandboxed installs keep their data under the sandbox's own home
+    #[cfg(target_os = "linux")]
+    if let Some(home) = dirs::home_dir() {
+        candidates.push(home.join(".var/app/sh.ppy.osu/data/osu"));
+        candidates.push(home.join(".var/app/sh.ppy.osulazer/data/osu"));
+        candidates.push(home.join("snap/osu-lazer/current/.local/share/osu"));
+    }
 
-    if let Some(path) = path {
-        if check_stable_path(&path) {
+    for path in candidates {
+        if let Some(path) = lazer_data_dir(&path)? {
             return Ok(path);
         }
     }
 
-    prompt_stable_path()
+    Err(exit::fail(
+        ExitCode::PathNotFound,
+        tr!("error-lazer-not-found"),
+    ))
 }
 
-fn get_lazer_path() -> Result<PathBuf> {
-    let path = dirs::data_dir().context("No data directory?")?.join("osu");
-
+// Where lazer keeps its data if its default directory is `path`, following storage.ini if the
+// user moved it elsewhere
+fn lazer_data_dir(path: &Path) -> Result<Option<PathBuf>> {
     let custom_storage = path.join("storage.ini");
     if custom_storage.exists() {
         let fd = File::open(custom_storage)?;
@@ -379,18 +1466,12 @@ fn get_lazer_path() -> Result<PathBuf> {
             if line.starts_with("FullPath") {
                 let parts = line.split('=').collect_vec();
 
-                return Ok(PathBuf::from(parts.get(1).unwrap().trim()));
+                return Ok(Some(PathBuf::from(parts.get(1).unwrap().trim())));
             }
         }
     }
 
-    if path.join("client.db").exists() {
-        Ok(path)
-    } else {
-        Err(anyhow!(
-            "Can't find lazer path, do you have the game installed?"
-        ))
-    }
+    Ok(path.join("client.db").exists().then(|| path.to_path_buf()))
 }
 
 #[cfg(target_family = "windows")]
@@ -423,22 +1504,3 @@ fn wait_for_input() -> Result<()> {
 
     Ok(())
 }
-
-#[cfg(target_family = "windows")]
-fn windows_link_check(lazer_path: &std::path::Path, stable_path: &std::path::Path) -> Result<()> {
-    let mut lazer_path = lazer_path.to_path_buf();
-    lazer_path.push("_link_test");
-    let mut stable_path = stable_path.to_path_buf();
-    stable_path.push("_link_test");
-
-    std::fs::write(&lazer_path, "hello from osu-link!")?;
-
-    let res = std::fs::hard_link(&lazer_path, &stable_path);
-
-    std::fs::remove_file(&lazer_path)?;
-    let _ = std::fs::remove_file(&stable_path);
-
-    res?;
-
-    Ok(())
-}
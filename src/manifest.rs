@@ -0,0 +1,74 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+// SHA-256 hashes from a manifest, by folder and then file (with `/` separators), for
+// `apply-manifest`
+pub type KnownHashes = HashMap<String, HashMap<String, String>>;
+
+// A file brought into lazer's store, for --manifest
+#[derive(Serialize, Deserialize)]
+pub struct Entry {
+    // None for sets imported as local maps
+    pub set_id: Option<u32>,
+    pub folder: String,
+    // Relative to the set's folder, as registered in BeatmapSetFileInfo
+    pub file: String,
+    pub sha256: String,
+    // Where the file is linked to, relative to osu!lazer's data directory
+    pub link_target: String,
+}
+
+// Every file an import registered, written out once it's done as a record of what it did
+#[derive(Default)]
+pub struct Manifest {
+    entries: Mutex<Vec<Entry>>,
+}
+
+impl Manifest {
+    pub fn add(&self, entry: Entry) {
+        self.entries.lock().unwrap().push(entry);
+    }
+
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let entries = self.entries.lock().unwrap();
+        fs::write(path, serde_json::to_string_pretty(&*entries)?)
+            .with_context(|| format!("Failed to write the manifest at {:?}", path))
+    }
+}
+
+pub fn read_hashes(path: &Path) -> Result<KnownHashes> {
+    let contents = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read the manifest at {:?}", path))?;
+    let entries: Vec<Entry> = serde_json::from_str(&contents)
+        .with_context(|| format!("Failed to parse the manifest at {:?}", path))?;
+
+    let mut hashes = KnownHashes::new();
+    for entry in entries {
+        hashes
+            .entry(entry.folder)
+            .or_default()
+            .insert(normalize(&entry.file), entry.sha256);
+    }
+
+    Ok(hashes)
+}
+
+// The manifest may come from another OS
+pub fn normalize(file: &str) -> String {
+    file.replace('\\', "/")
+}
+
+pub fn to_path(file: &str) -> PathBuf {
+    file.split('/').collect()
+}
+
+// `files/a/ab/abcd...`, the same on every OS
+pub fn link_target(hash: &str) -> String {
+    format!("files/{}/{}/{}", &hash[..1], &hash[..2], hash)
+}
@@ -0,0 +1,55 @@
+use anyhow::Result;
+use indicatif::ProgressBar;
+use notify_rust::Notification;
+
+use crate::{config::Config, i18n::tr, options::LinkOptions, report::Report};
+
+// Lets the user know an import finished, for when they walked away from a long one
+pub struct Notifier {
+    desktop: bool,
+    webhook: Option<String>,
+}
+
+impl Notifier {
+    // The command line takes precedence over the config file
+    pub fn new(options: &LinkOptions, config: &Config) -> Self {
+        Self {
+            desktop: options.notify || config.notify,
+            webhook: options.webhook.clone().or_else(|| config.webhook.clone()),
+        }
+    }
+
+    // Failing to notify doesn't fail the import, it's only reported
+    pub fn send(&self, report: &Report, bar: &ProgressBar, message: &str) {
+        let mut results = vec![];
+        if self.desktop {
+            results.push(desktop(message));
+        }
+        if let Some(url) = &self.webhook {
+            results.push(webhook(url, message));
+        }
+
+        for e in results.into_iter().filter_map(Result::err) {
+            report.warn(bar, tr!("warning-notification", error = e.to_string()));
+        }
+    }
+}
+
+fn desktop(message: &str) -> Result<()> {
+    Notification::new()
+        .summary("osu-link")
+        .body(message)
+        .show()?;
+
+    Ok(())
+}
+
+// Discord's webhook payload, which most chat services accept as well
+fn webhook(url: &str, message: &str) -> Result<()> {
+    ureq::post(url).send_json(ureq::json!({
+        "username": "osu-link",
+        "content": message,
+    }))?;
+
+    Ok(())
+}
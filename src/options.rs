@@ -0,0 +1,496 @@
+use chrono::NaiveDate;
+use clap::{ArgEnum, Args, Parser, Subcommand};
+use clap_complete::Shell;
+use regex::Regex;
+use std::path::PathBuf;
+
+#[derive(Parser, Clone)]
+#[clap(version, about)]
+pub struct Options {
+    #[clap(subcommand)]
+    pub command: Option<Command>,
+
+    /// Server the osu!stable library was played on. Beatmap IDs of private servers don't
+    /// correspond to bancho's, so their maps are imported as local maps without online IDs
+    #[clap(long, global = true, default_value = "bancho")]
+    pub server: String,
+
+    /// online.db to look up beatmap metadata in. Defaults to osu!lazer's, which is only
+    /// consulted for bancho
+    #[clap(long, global = true)]
+    pub online_db: Option<PathBuf>,
+
+    /// Keep online IDs when importing from a private server that mirrors bancho's beatmap IDs
+    #[clap(long, global = true)]
+    pub keep_online_ids: bool,
+
+    /// osu!stable directory, detected (or prompted for) if not given. Can be given more than
+    /// once to merge several installs, each set being imported from whichever has it
+    #[clap(long, global = true)]
+    pub stable: Vec<PathBuf>,
+
+    /// osu!lazer data directory, detected if not given. Can be given more than once to link into
+    /// each in turn, other commands only use the first
+    #[clap(long, global = true)]
+    pub lazer: Vec<PathBuf>,
+
+    /// Use the osu!lazer data directory saved as `profile.<name>` in the config file
+    #[clap(long, global = true, conflicts_with = "lazer")]
+    pub profile: Option<String>,
+
+    /// Log to osu-link.log in the OS data directory. Give it twice to also log every beatmap and
+    /// file imported, or three times to log every database row written with its key values
+    #[clap(short, long, global = true, parse(from_occurrences))]
+    pub verbose: u64,
+
+    /// Record every SQL statement run on osu!lazer's database to this file, with its parameters
+    /// filled in and hashes shortened, for replaying against a copy when reporting a bug
+    #[clap(long, global = true)]
+    pub sql_trace: Option<PathBuf>,
+
+    /// Import straight from a Songs folder, for when there's no osu!.db to read from. Every .osu
+    /// has to be parsed and hashed up front, so this is a lot slower
+    #[clap(long, global = true)]
+    pub songs: Option<PathBuf>,
+
+    // Linking is the default, so its options are accepted without the `link` subcommand too
+    #[clap(flatten)]
+    pub link: LinkOptions,
+}
+
+#[derive(Args, Clone, Default)]
+pub struct LinkOptions {
+    /// Recalculate star ratings with rosu-pp instead of copying them from osu!.db, which are often
+    /// outdated or missing for converts
+    #[clap(long)]
+    pub recalculate_sr: bool,
+
+    /// Import only the difficulties listed in a mappool file, one beatmap ID or link per line
+    #[clap(long)]
+    pub pool: Option<PathBuf>,
+
+    /// Import only beatmaps modified in osu!stable on or after this date (YYYY-MM-DD, local
+    /// time), such as those downloaded since an earlier migration
+    #[clap(long)]
+    pub modified_after: Option<NaiveDate>,
+
+    /// Import only beatmaps whose artist matches this regular expression, case-sensitively unless
+    /// it starts with (?i)
+    #[clap(long)]
+    pub filter_artist: Option<Regex>,
+
+    /// Import only beatmaps whose title matches this regular expression
+    #[clap(long)]
+    pub filter_title: Option<Regex>,
+
+    /// Import only beatmaps by a mapper matching this regular expression
+    #[clap(long)]
+    pub filter_creator: Option<Regex>,
+
+    /// Import only beatmaps with a tag, or a source, matching this regular expression, such as
+    /// the maps of a tournament
+    #[clap(long)]
+    pub filter_tags: Option<Regex>,
+
+    /// Import only ranked sets of this genre, as listed in online.db. Can be given more than once
+    #[clap(long, arg_enum)]
+    pub genre: Vec<Genre>,
+
+    /// Import only ranked sets in this language, as listed in online.db, where instrumental counts
+    /// as one. Can be given more than once
+    #[clap(long, arg_enum)]
+    pub language: Vec<Language>,
+
+    /// Import only sets added or changed in osu!stable since the last import into this osu!lazer,
+    /// which osu-link keeps track of between runs
+    #[clap(long)]
+    pub since_last_run: bool,
+
+    /// Import only this many of the sets that would be imported, to try osu-link on a few first.
+    /// Sets are taken in the order of their folder names
+    #[clap(long)]
+    pub limit: Option<usize>,
+
+    /// Skip this many sets before the ones --limit takes, to go through the library in batches
+    #[clap(long, requires = "limit", default_value = "0")]
+    pub offset: usize,
+
+    /// Import this many randomly picked sets of the ones that would be imported, to check osu-link
+    /// against a slice of a large library
+    #[clap(long, conflicts_with = "limit")]
+    pub sample: Option<usize>,
+
+    /// Seed for picking the --sample, to pick the same sets again. A random one is used, and
+    /// printed, if not given
+    #[clap(long, requires = "sample")]
+    pub seed: Option<u64>,
+
+    /// Pick which of the missing beatmapsets to import from a checklist, instead of importing all
+    /// of them
+    #[clap(long)]
+    pub interactive_select: bool,
+
+    /// Re-import beatmaps that are already in osu!lazer, replacing their rows and files. Combine
+    /// with --pool or --interactive-select to only re-import some sets
+    #[clap(long)]
+    pub force: bool,
+
+    /// Show a full-screen dashboard while importing, with throughput and a scrollable pane of
+    /// warnings and errors, instead of plain progress bars
+    #[clap(long)]
+    pub tui: bool,
+
+    /// Print how much wall-clock and CPU time each stage of the import took
+    #[clap(long)]
+    pub timings: bool,
+
+    /// Import into a copy of osu!lazer's database held in memory, and only write the rows that
+    /// changed to osu!lazer's once the import is done and has passed the integrity checks. Keeps
+    /// osu!lazer's database untouched until then, at the cost of memory
+    #[clap(long, conflicts_with = "via-lazer")]
+    pub stage_in_memory: bool,
+
+    /// Write the rows of beatmaps to temporary tables, and copy them into osu!lazer's all at once
+    /// at the end, which is much faster for large imports. --force gains little from it, as each
+    /// re-imported beatmap has to copy them first
+    #[clap(long)]
+    pub bulk_insert: bool,
+
+    /// Write the rows in the same order on every run, sorted by path, so they get the same IDs when
+    /// importing the same maps again. Nothing is written until every beatmap and file has been
+    /// processed, which makes the import slower
+    #[clap(long)]
+    pub deterministic: bool,
+
+    /// How many threads parse .osu files. Defaults to one per CPU, or 2 if the Songs folder is on
+    /// a spinning disk, where more only make it seek back and forth
+    #[clap(long)]
+    pub beatmap_threads: Option<usize>,
+
+    /// How many threads list and read the beatmapsets' files, with the same default as
+    /// --beatmap-threads. Also set by `read_threads` in the config file
+    #[clap(long)]
+    pub read_threads: Option<usize>,
+
+    /// How many threads hash the files once read. Defaults to one per CPU, as hashing doesn't wait
+    /// on the disk. Also set by `hash_threads` in the config file
+    #[clap(long)]
+    pub hash_threads: Option<usize>,
+
+    /// How many megabytes of files may be queued for or being hashed at once, 0 for no limit.
+    /// Lower it if large videos make the import use too much memory
+    #[clap(long, default_value = "1024")]
+    pub memory_budget: u64,
+
+    /// Show a status line under the progress bars for every reading thread, with the file it's on
+    /// and how long it has been at it
+    #[clap(long)]
+    pub worker_progress: bool,
+
+    /// Write a JSON manifest of every file imported to this path once done, with its set, hash and
+    /// where it's linked to in osu!lazer. With several --lazer, the last one's is kept
+    #[clap(long)]
+    pub manifest: Option<PathBuf>,
+
+    /// Show a desktop notification once the import finishes
+    #[clap(long)]
+    pub notify: bool,
+
+    /// POST a summary to this (Discord-compatible) webhook once the import finishes
+    #[clap(long)]
+    pub webhook: Option<String>,
+
+    /// Import through osu!lazer instead of writing to its database, for versions of osu!lazer
+    /// osu-link doesn't support yet: sets are packaged into .osz files, which osu!lazer is started
+    /// with. Much slower, and files take up space again instead of being linked
+    #[clap(long)]
+    pub via_lazer: bool,
+
+    /// Copy what --online-db has on the imported beatmaps into osu!lazer's own online.db, which
+    /// osu!lazer checks before looking beatmaps up online, so it doesn't ask the API about every
+    /// one of them
+    #[clap(long, requires = "online-db")]
+    pub populate_lookup_cache: bool,
+
+    /// Start osu!lazer once the import is done, so it loads the new beatmaps
+    #[clap(long)]
+    pub launch_lazer: bool,
+
+    /// osu!lazer's executable for --via-lazer and --launch-lazer, if it isn't found. Without one,
+    /// --via-lazer leaves the .osz files for dragging into osu!lazer
+    #[clap(long)]
+    pub lazer_exe: Option<PathBuf>,
+
+    // Set by `update`, which only imports beatmaps osu!lazer already has an outdated copy of
+    #[clap(skip)]
+    pub update: bool,
+
+    // Set by `apply-manifest`
+    #[clap(skip)]
+    pub from_manifest: Option<PathBuf>,
+
+    // Set by `import-bracket`, taking the place of --pool
+    #[clap(skip)]
+    pub bracket: Option<PathBuf>,
+
+    /// How files are brought into osu!lazer. Defaults to hard links on Windows and symlinks
+    /// elsewhere. Hard links keep working if the Songs folder is moved, but need it on the same
+    /// filesystem as osu!lazer, falling back to symlinks otherwise. Reflinks and copies take up
+    /// space (reflinks only once changed), but keep working if osu!stable's drive is unmounted.
+    /// Moving takes the files out of osu!stable, for leaving it behind
+    #[clap(long, arg_enum, default_value = "auto")]
+    pub link_mode: LinkMode,
+
+    /// What to do with what osu!lazer already has: a difficulty with the same online ID (usually
+    /// edited in osu!stable since), a set with the same online ID, or a file of the same name in
+    /// a set. Skip keeps osu!lazer's, overwrite replaces it, and duplicate imports the set again
+    /// alongside as a local map. --force always overwrites
+    #[clap(long, arg_enum, default_value = "skip")]
+    pub on_conflict: ConflictPolicy,
+}
+
+#[derive(ArgEnum, Clone, Copy, PartialEq)]
+pub enum LinkMode {
+    Auto,
+    Symlink,
+    Hardlink,
+    Reflink,
+    Copy,
+    Move,
+}
+
+impl Default for LinkMode {
+    fn default() -> Self {
+        Self::Auto
+    }
+}
+
+// osu-web's genre IDs, which online.db has too
+#[derive(ArgEnum, Clone, Copy, PartialEq)]
+pub enum Genre {
+    Unspecified = 1,
+    VideoGame = 2,
+    Anime = 3,
+    Rock = 4,
+    Pop = 5,
+    Other = 6,
+    Novelty = 7,
+    HipHop = 9,
+    Electronic = 10,
+    Metal = 11,
+    Classical = 12,
+    Folk = 13,
+    Jazz = 14,
+}
+
+// osu-web's language IDs
+#[derive(ArgEnum, Clone, Copy, PartialEq)]
+pub enum Language {
+    Unspecified = 1,
+    English = 2,
+    Japanese = 3,
+    Chinese = 4,
+    Instrumental = 5,
+    Korean = 6,
+    French = 7,
+    German = 8,
+    Swedish = 9,
+    Spanish = 10,
+    Italian = 11,
+    Russian = 12,
+    Polish = 13,
+    Other = 14,
+}
+
+#[derive(ArgEnum, Clone, Copy, PartialEq)]
+pub enum MapFormat {
+    Json,
+    Csv,
+}
+
+#[derive(ArgEnum, Clone, Copy, PartialEq)]
+pub enum ConflictPolicy {
+    Skip,
+    Overwrite,
+    Duplicate,
+}
+
+impl Default for ConflictPolicy {
+    fn default() -> Self {
+        Self::Skip
+    }
+}
+
+#[derive(Subcommand, Clone)]
+pub enum Command {
+    /// Import osu!stable's beatmaps into osu!lazer by linking their files (the default)
+    Link(LinkOptions),
+
+    /// Refresh beatmaps imported earlier whose .osu or metadata has since changed in osu!stable,
+    /// replacing their rows and files. Beatmaps are matched by online ID (or file name for local
+    /// maps), so ones osu!lazer downloaded itself are refreshed too
+    Update(LinkOptions),
+
+    /// Import the same files as an import elsewhere did, from the manifest it wrote with
+    /// --manifest. For a machine sharing the same Songs folder: files are taken to be the same as
+    /// the manifest says, so only .osu files are read, and nothing is scanned or hashed
+    ApplyManifest {
+        manifest: PathBuf,
+
+        #[clap(flatten)]
+        link: LinkOptions,
+    },
+
+    /// Import only the difficulties listed in a mappool file, one beatmap ID or link per line. Same
+    /// as link --pool
+    ImportPool {
+        pool_file: PathBuf,
+
+        #[clap(flatten)]
+        link: LinkOptions,
+    },
+
+    /// Import the mappools of every round of an osu!lazer tournament's bracket.json, found in the
+    /// tournaments folder of the osu!lazer data directory the tournament client uses
+    ImportBracket {
+        bracket: PathBuf,
+
+        #[clap(flatten)]
+        link: LinkOptions,
+    },
+
+    /// List the beatmapsets that would be imported, without importing anything
+    Diff,
+
+    /// Check that every file osu!lazer's database refers to is still there, which catches links
+    /// broken by deleting or moving beatmaps in osu!stable
+    Verify {
+        /// Also hash the files again, to catch ones edited in osu!stable since they were linked
+        #[clap(long)]
+        deep: bool,
+
+        /// With --deep, only hash this many files picked at random, as hashing all of them takes
+        /// as long as an import
+        #[clap(long, requires = "deep")]
+        sample: Option<usize>,
+    },
+
+    /// Compare osu!.db with the Songs folder: folders it doesn't list, and .osu files that are
+    /// missing or have changed since osu!stable last scanned them
+    CheckStable,
+
+    /// Restore osu!lazer's database from the backup taken before the last import
+    Undo,
+
+    /// Merge beatmapsets that are in osu!lazer more than once, such as local copies left by an
+    /// import without online IDs, moving their difficulties, files and scores into one set
+    Dedupe,
+
+    /// Remove files osu!lazer no longer uses from its file store, such as ones left by `undo` or
+    /// by replacing beatmaps with --force
+    Gc,
+
+    /// Remove what osu-link leaves behind when it crashes or is killed, such as its link test
+    /// files and empty directories in osu!lazer's file store
+    Clean,
+
+    /// Package beatmapsets into .osz files
+    Export {
+        #[clap(subcommand)]
+        source: ExportSource,
+    },
+
+    /// Write osu!lazer's collections into osu!stable's collection.db, adding to the collections
+    /// osu!stable has by the same name, and list the beatmaps osu!stable doesn't have
+    ExportCollections {
+        /// Replace osu!stable's collections instead of adding to them
+        #[clap(long)]
+        replace: bool,
+    },
+
+    /// Copy osu!lazer's local scores into osu!stable's scores.db, for the beatmaps osu!stable has.
+    /// Scores keep osu!lazer's scoring, and ones with mods osu!stable doesn't have are left out
+    ExportScores,
+
+    /// Write where every .osu is in osu!stable and in osu!lazer's file store, by the MD5 of the
+    /// .osu, for tools like danser that need the files without reading either database
+    ExportMap {
+        #[clap(long, arg_enum, default_value = "json")]
+        format: MapFormat,
+
+        /// File to write the mapping to
+        #[clap(short, long)]
+        output: PathBuf,
+    },
+
+    /// Check that everything needed for linking is in place, without changing anything
+    Doctor,
+
+    /// Show how far the import running in another osu-link is, or cancel it
+    Status {
+        /// Cancel the import instead, rolling back what it wrote to osu!lazer's database
+        #[clap(long)]
+        cancel: bool,
+
+        /// Print the progress as JSON, in the same shape as progress.json
+        #[clap(long)]
+        json: bool,
+    },
+
+    /// Print a completion script for the given shell
+    Completions {
+        #[clap(arg_enum)]
+        shell: Shell,
+    },
+
+    /// Open a window to pick paths and options, and follow the import's progress
+    #[cfg(feature = "gui")]
+    Gui,
+}
+
+#[derive(Subcommand, Clone)]
+pub enum ExportSource {
+    /// Package osu!stable beatmapsets, for sharing or moving them to a machine where linking isn't
+    /// possible
+    Stable {
+        /// Beatmapset IDs to export
+        sets: Vec<u32>,
+
+        /// Also export every set that has a difficulty in this mappool file
+        #[clap(long)]
+        pool: Option<PathBuf>,
+
+        /// Directory to write the .osz files to
+        #[clap(short, long, default_value = ".")]
+        output: PathBuf,
+    },
+
+    /// Package osu!lazer beatmapsets, restoring their original file names
+    Lazer {
+        /// Online beatmapset IDs to export. Every set is exported if none are given
+        sets: Vec<u32>,
+
+        /// Directory to write the .osz files to
+        #[clap(short, long, default_value = ".")]
+        output: PathBuf,
+    },
+}
+
+impl Options {
+    pub fn is_bancho(&self) -> bool {
+        self.server.eq_ignore_ascii_case("bancho")
+    }
+
+    // Whether stable's beatmap IDs can be used as lazer's online IDs
+    pub fn uses_online_ids(&self) -> bool {
+        self.is_bancho() || self.keep_online_ids
+    }
+
+    pub fn conflict_policy(&self) -> ConflictPolicy {
+        if self.link.force {
+            ConflictPolicy::Overwrite
+        } else {
+            self.link.on_conflict
+        }
+    }
+}
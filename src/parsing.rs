@@ -0,0 +1,330 @@
+use anyhow::Result;
+use itertools::Itertools;
+use libosu::{beatmap::Beatmap, db::DbBeatmap};
+use std::{borrow::Cow, collections::HashSet};
+
+// Sections that are still kept when everything else has to be thrown away
+const METADATA_SECTIONS: &[&str] = &["General", "Editor", "Metadata", "Difficulty"];
+
+// Sections libosu expects to exist, filled in (empty or with defaults) when missing
+const REQUIRED_SECTIONS: &[&str] = &[
+    "General",
+    "Metadata",
+    "Difficulty",
+    "Events",
+    "TimingPoints",
+    "HitObjects",
+];
+
+// Keys in key-value sections whose values are free text; every other key is expected to be numeric
+const TEXT_KEYS: &[&str] = &[
+    "AudioFilename",
+    "SampleSet",
+    "SkinPreference",
+    "OverlayPosition",
+    "Title",
+    "TitleUnicode",
+    "Artist",
+    "ArtistUnicode",
+    "Creator",
+    "Version",
+    "Source",
+    "Tags",
+];
+
+// osu!stable's defaults for a difficulty without a [Difficulty] section
+const DEFAULT_DIFFICULTY: &str = "HPDrainRate:5
+CircleSize:5
+OverallDifficulty:5
+ApproachRate:5
+SliderMultiplier:1.4
+SliderTickRate:1
+";
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Fidelity {
+    // The file parsed as-is
+    Strict,
+    // Malformed lines had to be skipped, and missing sections filled with defaults
+    Lenient,
+    // The file couldn't be parsed at all, so it was rebuilt from osu!.db data instead
+    DatabaseOnly,
+}
+
+// [General] values libosu doesn't parse
+#[derive(Default)]
+pub struct General {
+    pub special_style: bool,
+    pub countdown_offset: i32,
+    pub samples_match_playback_rate: bool,
+}
+
+impl General {
+    pub fn parse(contents: &[u8]) -> Self {
+        let contents = String::from_utf8_lossy(contents);
+        let mut general = Self::default();
+
+        for (key, value) in section_values(&contents, "General") {
+            match key {
+                "SpecialStyle" => general.special_style = value == "1",
+                "CountdownOffset" => general.countdown_offset = value.parse().unwrap_or(0),
+                "SamplesMatchPlaybackRate" => general.samples_match_playback_rate = value == "1",
+                _ => {}
+            }
+        }
+
+        general
+    }
+}
+
+// Parses a .osu file, retrying with a sanitized copy when strict parsing fails. Plenty of old and
+// graveyarded maps have malformed lines which libosu rightfully rejects, but they still import
+// fine with best-effort metadata.
+pub fn parse_beatmap(contents: &[u8]) -> Result<(Beatmap, Fidelity)> {
+    let contents = without_hit_objects(contents);
+    let err = match Beatmap::parse(&contents[..]) {
+        Ok(beatmap) => return Ok((beatmap, Fidelity::Strict)),
+        Err(err) => err,
+    };
+
+    let contents = String::from_utf8_lossy(&contents);
+    for metadata_only in [false, true] {
+        let sanitized = sanitize(&contents, metadata_only);
+        if let Ok(beatmap) = Beatmap::parse(sanitized.as_bytes()) {
+            return Ok((beatmap, Fidelity::Lenient));
+        }
+    }
+
+    Err(err.into())
+}
+
+// Hit objects are most of a .osu, marathons having tens of thousands, but only the first and last
+// one's times are ever used (for the length and BPM). The ones in between are cut before libosu
+// gets to parse them. A file with anything after [HitObjects] is left as it is.
+fn without_hit_objects(contents: &[u8]) -> Cow<[u8]> {
+    const HEADER: &[u8] = b"[HitObjects]";
+
+    let start = match contents
+        .windows(HEADER.len())
+        .position(|window| window == HEADER)
+    {
+        Some(position) => position + HEADER.len(),
+        None => return Cow::Borrowed(contents),
+    };
+    let is_object =
+        |line: &&[u8]| !line.iter().all(u8::is_ascii_whitespace) && !line.starts_with(b"//");
+    let mut objects = contents[start..].split(|&b| b == b'\n').filter(is_object);
+    if objects.clone().any(|line| line.starts_with(b"[")) {
+        return Cow::Borrowed(contents);
+    }
+    let (first, last) = match (
+        objects.next(),
+        contents[start..].rsplit(|&b| b == b'\n').find(is_object),
+    ) {
+        (Some(first), Some(last)) if first.as_ptr() != last.as_ptr() => (first, last),
+        _ => return Cow::Borrowed(contents),
+    };
+
+    let mut trimmed = Vec::with_capacity(start + first.len() + last.len() + 3);
+    trimmed.extend_from_slice(&contents[..start]);
+    for line in [first, last] {
+        trimmed.push(b'\n');
+        trimmed.extend_from_slice(line);
+    }
+    trimmed.push(b'\n');
+
+    Cow::Owned(trimmed)
+}
+
+// Rebuilds a bare-bones .osu from what osu!.db knows about a difficulty, so maps whose file can't
+// be parsed at all still show up in lazer instead of being dropped
+pub fn synthesize_beatmap(db_beatmap: &DbBeatmap) -> Result<Beatmap> {
+    // osu!.db strings can't break out of their line in the synthesized file
+    let clean = |value: &str| value.replace(|c: char| c == '\r' || c == '\n', " ");
+
+    let contents = format!(
+        "osu file format v14
+
+[General]
+AudioFilename: {audio}
+Mode: {mode}
+StackLeniency: {stack_leniency}
+
+[Metadata]
+Title:{title}
+TitleUnicode:{title_unicode}
+Artist:{artist}
+ArtistUnicode:{artist_unicode}
+Creator:{creator}
+Version:{version}
+Source:{source}
+Tags:{tags}
+BeatmapID:{beatmap_id}
+BeatmapSetID:{beatmap_set_id}
+
+[Difficulty]
+HPDrainRate:{hp}
+CircleSize:{cs}
+OverallDifficulty:{od}
+ApproachRate:{ar}
+SliderMultiplier:{slider_multiplier}
+SliderTickRate:1
+
+[Events]
+
+[TimingPoints]
+
+[HitObjects]
+",
+        audio = clean(&db_beatmap.audio_file_name),
+        mode = db_beatmap.gameplay_mode as i8,
+        stack_leniency = db_beatmap.stack_leniency,
+        title = clean(&db_beatmap.song_title),
+        title_unicode = clean(&db_beatmap.song_title_unicode),
+        artist = clean(&db_beatmap.artist_name),
+        artist_unicode = clean(&db_beatmap.artist_name_unicode),
+        creator = clean(&db_beatmap.creator_name),
+        version = clean(&db_beatmap.difficulty_name),
+        source = clean(&db_beatmap.song_source),
+        tags = clean(&db_beatmap.song_tags),
+        beatmap_id = db_beatmap.beatmap_id,
+        beatmap_set_id = db_beatmap.beatmap_set_id,
+        hp = db_beatmap.hp_drain_rate,
+        cs = db_beatmap.circle_size,
+        od = db_beatmap.overall_difficulty,
+        ar = db_beatmap.approach_rate,
+        slider_multiplier = db_beatmap.slider_velocity,
+    );
+
+    Ok(Beatmap::parse(contents.as_bytes())?)
+}
+
+// Key-value pairs of a section like [General] or [Metadata]
+fn section_values<'a>(
+    contents: &'a str,
+    section: &str,
+) -> impl Iterator<Item = (&'a str, &'a str)> {
+    let header = format!("[{}]", section);
+
+    contents
+        .lines()
+        .map(str::trim)
+        .skip_while(move |line| *line != header.as_str())
+        .skip(1)
+        .take_while(|line| !line.starts_with('['))
+        .filter_map(|line| line.split_once(':'))
+        .map(|(key, value)| (key.trim(), value.trim()))
+}
+
+fn sanitize(contents: &str, metadata_only: bool) -> String {
+    let mut lines = contents
+        .trim_start_matches('\u{feff}')
+        .lines()
+        .map(str::trim_end)
+        .skip_while(|line| line.trim().is_empty())
+        .peekable();
+
+    let mut sanitized = String::with_capacity(contents.len());
+    match lines.peek() {
+        Some(line) if line.starts_with("osu file format") => {
+            sanitized.push_str(line);
+            lines.next();
+        }
+        _ => sanitized.push_str("osu file format v14"),
+    }
+    sanitized.push('\n');
+
+    let mut section = String::new();
+    let mut seen_sections = HashSet::new();
+
+    for line in lines {
+        if line.trim().is_empty() || line.starts_with("//") {
+            continue;
+        }
+
+        if line.starts_with('[') && line.ends_with(']') {
+            section = line[1..line.len() - 1].to_string();
+            seen_sections.insert(section.clone());
+
+            sanitized.push('\n');
+            sanitized.push_str(line);
+            sanitized.push('\n');
+            continue;
+        }
+
+        if metadata_only && !METADATA_SECTIONS.contains(&section.as_str()) {
+            continue;
+        }
+
+        if is_valid_line(&section, line) {
+            sanitized.push_str(line);
+            sanitized.push('\n');
+        }
+    }
+
+    for section in REQUIRED_SECTIONS {
+        if !seen_sections.contains(*section) {
+            sanitized.push_str(&format!("\n[{}]\n", section));
+            if *section == "Difficulty" {
+                sanitized.push_str(DEFAULT_DIFFICULTY);
+            }
+        }
+    }
+
+    sanitized
+}
+
+fn is_valid_line(section: &str, line: &str) -> bool {
+    match section {
+        "General" | "Editor" | "Metadata" | "Difficulty" => match line.split_once(':') {
+            Some((key, value)) => TEXT_KEYS.contains(&key.trim()) || is_number_list(value),
+            None => false,
+        },
+        "TimingPoints" => {
+            let fields = line.split(',').collect_vec();
+            fields.len() >= 2 && fields.iter().all(|field| is_number(field))
+        }
+        "HitObjects" => {
+            let fields = line.split(',').collect_vec();
+            fields.len() >= 5 && fields[..5].iter().all(|field| is_number(field))
+        }
+        "Colours" => match line.split_once(':') {
+            Some((_, value)) => {
+                let channels = value.split(',').collect_vec();
+                channels.len() >= 3 && channels.iter().all(|c| c.trim().parse::<u8>().is_ok())
+            }
+            None => false,
+        },
+        _ => true,
+    }
+}
+
+fn is_number(value: &str) -> bool {
+    value.trim().parse::<f64>().is_ok()
+}
+
+fn is_number_list(value: &str) -> bool {
+    !value.trim().is_empty() && value.split(',').all(is_number)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis;
+
+    const BEATMAP: &[u8] = include_bytes!("../tests/fixtures/beatmap.osu");
+
+    #[test]
+    fn hit_objects_between_are_cut() {
+        let contents = String::from_utf8_lossy(BEATMAP).replace(
+            "256,192,2000,",
+            "256,192,1500,1,0,0:0:0:0:\n256,192,1750,1,0,0:0:0:0:\n256,192,2000,",
+        );
+        let (beatmap, fidelity) = parse_beatmap(contents.as_bytes()).unwrap();
+
+        assert!(fidelity == Fidelity::Strict);
+        assert_eq!(beatmap.hit_objects.len(), 2);
+        assert_eq!(analysis::length(&beatmap), Some(1000.0));
+        assert_eq!(beatmap.title, "Fixture");
+    }
+}
@@ -0,0 +1,97 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use unicode_normalization::UnicodeNormalization;
+
+// Windows refuses paths longer than MAX_PATH (260 characters) unless they use the `\\?\`
+// extended-length syntax, which long unicode beatmap folder names easily go past.
+// Extended paths skip all normalization, so the path is rebuilt from its components here.
+#[cfg(target_family = "windows")]
+pub fn extended(path: &Path) -> PathBuf {
+    use std::{
+        ffi::OsString,
+        path::{Component, Prefix},
+    };
+
+    let mut components = path.components();
+    let prefix = match components.next() {
+        Some(Component::Prefix(prefix)) => prefix,
+        _ => return path.to_path_buf(),
+    };
+
+    let mut extended = OsString::from(r"\\?\");
+    match prefix.kind() {
+        Prefix::Disk(_) => extended.push(prefix.as_os_str()),
+        Prefix::UNC(server, share) => {
+            extended.push(r"UNC\");
+            extended.push(server);
+            extended.push(r"\");
+            extended.push(share);
+        }
+        // Already verbatim, or a device path
+        _ => return path.to_path_buf(),
+    }
+
+    let mut parts = vec![];
+    for component in components {
+        match component {
+            Component::Normal(part) => parts.push(part),
+            Component::ParentDir => {
+                parts.pop();
+            }
+            _ => {}
+        }
+    }
+
+    for part in parts {
+        extended.push(r"\");
+        extended.push(part);
+    }
+
+    PathBuf::from(extended)
+}
+
+#[cfg(not(target_family = "windows"))]
+pub fn extended(path: &Path) -> PathBuf {
+    path.to_path_buf()
+}
+
+// osu!.db stores names NFC-composed, but HFS+/APFS may hand them back NFD-decomposed, so opening
+// the composed name of e.g. a Japanese title fails. Tries the NFC form, then the NFD form, then
+// falls back to scanning the parent directory for an entry that matches after normalization.
+pub fn resolve(parent: &Path, name: &str) -> PathBuf {
+    let composed: String = name.nfc().collect();
+    let path = parent.join(&composed);
+    if path.exists() {
+        return path;
+    }
+
+    let decomposed = parent.join(name.nfd().collect::<String>());
+    if decomposed.exists() {
+        return decomposed;
+    }
+
+    if let Ok(entries) = fs::read_dir(parent) {
+        for entry in entries.flatten() {
+            let file_name = entry.file_name();
+            if let Some(file_name) = file_name.to_str() {
+                if file_name.nfc().eq(composed.chars()) {
+                    return entry.path();
+                }
+            }
+        }
+    }
+
+    path
+}
+
+// Where lazer keeps a file with the given SHA-256 hash, e.g. `files/a/ab/abcd...`
+pub fn store_file(lazer_path: &Path, hash: &str) -> PathBuf {
+    let mut path = lazer_path.join("files");
+    path.push(&hash[..1]);
+    path.push(&hash[..2]);
+    path.push(hash);
+
+    path
+}
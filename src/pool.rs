@@ -0,0 +1,66 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::{fs, path::Path};
+
+use crate::i18n::tr;
+
+// Reads a mappool file listing one beatmap per line. Lines starting with `#` are comments.
+pub fn read_pool(path: &Path) -> Result<Vec<u32>> {
+    let contents = fs::read_to_string(path)
+        .with_context(|| tr!("error-read-pool", path = format!("{:?}", path)))?;
+
+    Ok(contents.lines().filter_map(parse_line).collect())
+}
+
+// The last number on a line is the beatmap ID, which covers `129891`, `NM1 129891`,
+// `https://osu.ppy.sh/b/129891?m=0` and `https://osu.ppy.sh/beatmapsets/39804#osu/129891`
+fn parse_line(line: &str) -> Option<u32> {
+    let line = line.trim();
+    if line.starts_with('#') {
+        return None;
+    }
+
+    line.split('?')
+        .next()?
+        .split(|c: char| !c.is_ascii_digit())
+        .filter(|part| !part.is_empty())
+        .last()?
+        .parse()
+        .ok()
+}
+
+// The parts of a tournament's bracket.json that list its mappools, named as osu!lazer writes them
+#[derive(Deserialize)]
+struct Bracket {
+    #[serde(rename = "Rounds", default)]
+    rounds: Vec<Round>,
+}
+
+#[derive(Deserialize)]
+struct Round {
+    #[serde(rename = "Beatmaps", default)]
+    beatmaps: Vec<RoundBeatmap>,
+}
+
+#[derive(Deserialize)]
+struct RoundBeatmap {
+    #[serde(rename = "ID")]
+    id: i64,
+}
+
+// Reads the beatmaps pooled in every round of a bracket.json
+pub fn read_bracket(path: &Path) -> Result<Vec<u32>> {
+    let contents = fs::read_to_string(path)
+        .with_context(|| tr!("error-read-bracket", path = format!("{:?}", path)))?;
+    let bracket: Bracket = serde_json::from_str(&contents)
+        .with_context(|| tr!("error-read-bracket", path = format!("{:?}", path)))?;
+
+    // Slots not filled in yet are left at 0
+    Ok(bracket
+        .rounds
+        .iter()
+        .flat_map(|round| &round.beatmaps)
+        .filter_map(|beatmap| u32::try_from(beatmap.id).ok())
+        .filter(|&id| id > 0)
+        .collect())
+}
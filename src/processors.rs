@@ -1,29 +1,75 @@
-use anyhow::Result;
-use indicatif::{ProgressBar, ProgressStyle};
+use anyhow::{Context, Result};
+use indicatif::{HumanBytes, MultiProgress, ProgressBar, ProgressStyle};
 use itertools::Itertools;
-use libosu::{beatmap::Beatmap, db::DbBeatmap};
+use libosu::beatmap::Beatmap;
+use md5::Md5;
 use rand::{thread_rng, Rng};
 use rayon::iter::{IntoParallelRefIterator, ParallelBridge, ParallelIterator};
+use rosu_pp::BeatmapExt;
 use sha2::{Digest, Sha256};
 use std::{
+    cmp,
+    collections::{BinaryHeap, HashSet},
     fmt::Write,
-    fs::File,
-    io::Read,
+    fs::{self, File},
+    io::{self, Read},
     path::{Path, PathBuf},
-    sync::mpsc::{Receiver, Sender},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, Sender, SyncSender},
+        Arc,
+    },
+    time::Instant,
 };
+use walkdir::WalkDir;
 
-use crate::{State, FAKE_HASH};
+use crate::{
+    analysis,
+    budget::MemoryBudget,
+    i18n::tr,
+    library::StableBeatmap,
+    manifest::{self, KnownHashes},
+    parsing::{self, Fidelity, General},
+    paths,
+    report::{FailureKind, Report},
+    steps::{self, Step},
+    storyboard::EventFiles,
+    timings::{Stage, Timings},
+    State, FAKE_HASH,
+};
 
 pub mod context {
     use std::path::PathBuf;
 
-    use libosu::{beatmap::Beatmap, db::DbBeatmap};
+    use libosu::beatmap::Beatmap;
+
+    use crate::{analysis::Bpm, library::StableBeatmap, parsing::General, storyboard::EventFiles};
 
     pub struct BeatmapProcessed {
-        pub db_beatmap: DbBeatmap,
+        pub db_beatmap: StableBeatmap,
         pub beatmap: Beatmap,
+        pub event_files: EventFiles,
+        pub general: General,
         pub is_main: bool,
+
+        // MD5 of the .osu as it is on disk
+        pub hash: String,
+        pub bpm: Bpm,
+        // In milliseconds
+        pub length: f64,
+        // Recalculated with rosu-pp, if requested
+        pub star_rating: Option<f64>,
+    }
+
+    pub struct ScanRequest {
+        pub beatmap_id: u32,
+        pub beatmapset_id: u32,
+        pub songs_path: PathBuf,
+        pub folder_name: String,
+        pub file_name: String,
+
+        // Files the .osu refers to, see `resolve_case_collisions`
+        pub referenced: Vec<String>,
     }
 
     pub struct HashRequest {
@@ -32,9 +78,11 @@ pub mod context {
         pub folder_name: String,
         pub file_name: String,
 
-        pub beatmapset_info_id: i64,
         pub stripped_path: PathBuf,
         pub full_path: PathBuf,
+        pub size: u64,
+        // Taken from the memory budget, to be given back once hashed
+        pub reserved: u64,
     }
 
     pub struct HashProcessed {
@@ -42,66 +90,106 @@ pub mod context {
 
         pub hash: String,
     }
+
+    // Everything the writer, the only stage touching the database, receives
+    pub enum Processed {
+        Beatmap(Box<BeatmapProcessed>),
+        File(HashProcessed),
+    }
 }
 
-use context::{BeatmapProcessed, HashProcessed, HashRequest};
+use context::{BeatmapProcessed, HashProcessed, HashRequest, Processed, ScanRequest};
 
 pub struct BeatmapProcessor {
+    overall_bar: ProgressBar,
     bar: ProgressBar,
     insert_bar: ProgressBar,
+    report: Report,
+    timings: Timings,
     length_unchanging_style: ProgressStyle,
-    stable_songs_path: PathBuf,
+    recalculate_sr: bool,
+    online_ids: bool,
+    threads: usize,
+    // Set by `status --cancel`, after which the remaining beatmaps are skipped
+    cancelled: Arc<AtomicBool>,
 }
 
 impl BeatmapProcessor {
     pub fn new(state: &State) -> Self {
         Self {
+            overall_bar: state.progress_bars.overall.clone(),
             bar: state.progress_bars.beatmap.clone(),
             insert_bar: state.progress_bars.beatmap_insert.clone(),
+            report: state.report.clone(),
+            timings: state.timings.clone(),
             length_unchanging_style: state.progress_styles.length_unchanging.clone(),
-            stable_songs_path: state.stable_songs_path.clone(),
+            recalculate_sr: state.options.link.recalculate_sr,
+            online_ids: state.options.uses_online_ids(),
+            threads: state.threads.beatmap,
+            cancelled: state.cancelled.clone(),
         }
     }
 
-    pub fn start(self, beatmaps: Vec<DbBeatmap>, sender: Sender<BeatmapProcessed>) {
-        let mut processed_sets: Vec<u32> = vec![];
+    pub fn start(
+        self,
+        beatmaps: Vec<StableBeatmap>,
+        sender: Sender<Processed>,
+        scan_sender: Sender<ScanRequest>,
+    ) {
+        // Without online IDs, the folder is all there is to tell sets apart
+        let mut processed_sets = HashSet::new();
         let beatmaps = beatmaps
             .into_iter()
-            .map(|bm| -> (DbBeatmap, bool) {
-                if processed_sets.contains(&bm.beatmap_set_id) {
-                    (bm, false)
+            .map(|bm| -> (StableBeatmap, bool) {
+                let is_main = if self.online_ids {
+                    processed_sets.insert(bm.beatmap_set_id.to_string())
                 } else {
-                    processed_sets.push(bm.beatmap_set_id);
-                    (bm, true)
-                }
+                    processed_sets.insert(bm.folder_name.clone())
+                };
+
+                (bm, is_main)
             })
             .collect_vec();
 
         let pool = rayon::ThreadPoolBuilder::new()
-            .num_threads(num_cpus::get())
+            .num_threads(self.threads)
             .thread_name(|i| format!("(osu-link) beatmap thread {}", i))
             .build()
             .unwrap();
         pool.install(move || {
-            beatmaps
-                .par_iter()
-                .for_each_with(sender, |sender, (db_beatmap, is_main)| {
+            beatmaps.par_iter().for_each_with(
+                (sender, scan_sender),
+                |senders, (db_beatmap, is_main)| {
+                    if self.cancelled.load(Ordering::Relaxed) {
+                        return;
+                    }
                     self.bar.set_message(format!(
                         "{: <7} - {: <7}",
                         db_beatmap.beatmap_set_id, db_beatmap.beatmap_id
                     ));
                     self.bar.inc(1);
-                    if let Err(e) = self.process(sender, db_beatmap, *is_main) {
-                        self.bar.println(format!(
-                            "Error occurred while processing {}/{}",
-                            db_beatmap.folder_name, db_beatmap.beatmap_file_name
-                        ));
-                        self.bar.println(format!("{}", e));
+                    let res = self.timings.time(Stage::Parsing, || {
+                        self.process(senders, db_beatmap, *is_main)
+                    });
+                    if let Err(e) = res {
+                        self.report.error(
+                            &self.bar,
+                            tr!(
+                                "error-processing",
+                                folder = db_beatmap.folder_name.as_str(),
+                                file = db_beatmap.beatmap_file_name.as_str()
+                            ),
+                        );
+                        self.report.error(&self.bar, format!("{}", e));
+                        self.report
+                            .add_failure(FailureKind::of(&e), Some(&db_beatmap.folder_name));
                     }
                     self.insert_bar.inc_length(1);
-                });
+                },
+            );
 
-            self.bar.finish_with_message("Done.");
+            self.bar.finish_with_message(tr!("done"));
+            steps::advance(&self.overall_bar, Step::Hashing);
             self.insert_bar
                 .set_style(self.length_unchanging_style.clone());
         });
@@ -109,43 +197,145 @@ impl BeatmapProcessor {
 
     fn process(
         &self,
-        sender: &Sender<BeatmapProcessed>,
-        db_beatmap: &DbBeatmap,
+        (sender, scan_sender): &(Sender<Processed>, Sender<ScanRequest>),
+        db_beatmap: &StableBeatmap,
         is_main: bool,
     ) -> Result<()> {
-        let mut path = self.stable_songs_path.clone();
-        path.push(&db_beatmap.folder_name);
-        path.push(&db_beatmap.beatmap_file_name);
+        let set_path = paths::extended(&db_beatmap.songs_path);
+        let set_path = paths::resolve(&set_path, &db_beatmap.folder_name);
+        let path = paths::resolve(&set_path, &db_beatmap.beatmap_file_name);
+
+        let contents = fs::read(path)?;
+        let (beatmap, fidelity) = match parsing::parse_beatmap(&contents) {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                let db_entry = db_beatmap.db_entry.as_ref().context(e)?;
+                (
+                    parsing::synthesize_beatmap(db_entry)?,
+                    Fidelity::DatabaseOnly,
+                )
+            }
+        };
+
+        match fidelity {
+            Fidelity::Strict => {}
+            Fidelity::Lenient => self.report.warn(
+                &self.bar,
+                tr!(
+                    "warning-malformed",
+                    folder = db_beatmap.folder_name.as_str(),
+                    file = db_beatmap.beatmap_file_name.as_str()
+                ),
+            ),
+            Fidelity::DatabaseOnly => self.report.warn(
+                &self.bar,
+                tr!(
+                    "warning-database-only",
+                    folder = db_beatmap.folder_name.as_str(),
+                    file = db_beatmap.beatmap_file_name.as_str()
+                ),
+            ),
+        }
+
+        // osu!.db only knows the hash from when stable last scanned the file, which is stale if the
+        // .osu was edited since; lazer expects MD5Hash to match the contents it's linked to
+        let hash = format!("{:x}", Md5::digest(&contents));
+        if hash != db_beatmap.hash {
+            self.report.warn(
+                &self.bar,
+                tr!(
+                    "warning-changed",
+                    folder = db_beatmap.folder_name.as_str(),
+                    file = db_beatmap.beatmap_file_name.as_str()
+                ),
+            );
+        }
+
+        let star_rating = if self.recalculate_sr {
+            match rosu_pp::Beatmap::parse(contents.as_slice()) {
+                Ok(map) => Some(map.stars(0, None).stars()),
+                Err(e) => {
+                    self.report.warn(
+                        &self.bar,
+                        tr!(
+                            "warning-star-rating",
+                            folder = db_beatmap.folder_name.as_str(),
+                            file = db_beatmap.beatmap_file_name.as_str(),
+                            error = e.to_string()
+                        ),
+                    );
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        let general = General::parse(&contents);
+        let event_files = EventFiles::collect(&beatmap, &set_path);
+        let bpm = analysis::bpm(&beatmap);
+
+        // Some osu!.db entries have no total time, which makes lazer show 0:00
+        let length = if db_beatmap.total_time > 0.0 {
+            db_beatmap.total_time
+        } else {
+            analysis::length(&beatmap).unwrap_or(0.0)
+        };
+
+        // Only one folder per set is scanned, difficulties split across folders share its files
+        if is_main {
+            scan_sender.send(ScanRequest {
+                beatmap_id: db_beatmap.beatmap_id,
+                beatmapset_id: db_beatmap.beatmap_set_id,
+                songs_path: db_beatmap.songs_path.clone(),
+                folder_name: db_beatmap.folder_name.clone(),
+                file_name: db_beatmap.beatmap_file_name.clone(),
+                referenced: referenced_files(&beatmap, &event_files),
+            })?;
+        }
 
-        let fd = File::open(path)?;
-        let beatmap = Beatmap::parse(fd)?;
-        sender.send(BeatmapProcessed {
+        sender.send(Processed::Beatmap(Box::new(BeatmapProcessed {
             db_beatmap: db_beatmap.clone(),
             is_main,
             beatmap,
-        })?;
+            event_files,
+            general,
+            hash,
+            bpm,
+            length,
+            star_rating,
+        })))?;
 
         Ok(())
     }
 }
 
-pub struct HashProcessor {
+// Walks the folder of each set and requests every file in it to be hashed
+pub struct ScanProcessor {
     bar: ProgressBar,
-    insert_bar: ProgressBar,
+    report: Report,
+    timings: Timings,
+    memory_budget: MemoryBudget,
+    known_hashes: Option<Arc<KnownHashes>>,
+    threads: usize,
 }
 
-impl HashProcessor {
+impl ScanProcessor {
     pub fn new(state: &State) -> Self {
         Self {
             bar: state.progress_bars.hash.clone(),
-            insert_bar: state.progress_bars.hash_insert.clone(),
+            report: state.report.clone(),
+            timings: state.timings.clone(),
+            memory_budget: state.memory_budget.clone(),
+            known_hashes: state.known_hashes.clone(),
+            threads: state.threads.read,
         }
     }
 
-    pub fn start(self, sender: Sender<HashProcessed>, receiver: Receiver<HashRequest>) {
+    pub fn start(self, sender: Sender<HashRequest>, receiver: Receiver<ScanRequest>) {
         let pool = rayon::ThreadPoolBuilder::new()
-            .num_threads(num_cpus::get())
-            .thread_name(|i| format!("(osu-link) hash thread {}", i))
+            .num_threads(self.threads)
+            .thread_name(|i| format!("(osu-link) scan thread {}", i))
             .build()
             .unwrap();
         pool.install(move || {
@@ -153,30 +343,370 @@ impl HashProcessor {
                 .into_iter()
                 .par_bridge()
                 .for_each_with(sender, |sender, request| {
-                    self.bar.set_message(format!(
-                        "{: <7} - {: <7}",
-                        request.beatmapset_id, request.beatmap_id
-                    ));
-                    self.bar.inc(1);
-                    match HashProcessor::hash_file(&request.full_path) {
-                        Ok(hash) => {
-                            sender.send(HashProcessed { request, hash }).unwrap();
-                            self.insert_bar.inc_length(1);
-                        }
-                        Err(e) => {
-                            self.bar.println(format!(
-                                "Error occurred while processing {}/{}",
-                                request.folder_name, request.file_name
-                            ));
-                            self.bar.println(format!("{}", e));
-                        }
+                    let set_path = paths::extended(&request.songs_path);
+                    let set_path = paths::resolve(&set_path, &request.folder_name);
+
+                    let res = self
+                        .timings
+                        .time(Stage::Walking, || self.scan(sender, &set_path, &request));
+                    if let Err(e) = res {
+                        self.report.error(
+                            &self.bar,
+                            tr!(
+                                "error-scanning",
+                                path = format!("{:?}", set_path),
+                                error = e.to_string()
+                            ),
+                        );
+                        self.report
+                            .add_failure(FailureKind::of(&e), Some(&request.folder_name));
                     }
                 });
-            self.bar.finish_with_message("Done.");
         });
     }
 
-    fn hash_file(path: &Path) -> Result<String> {
+    fn scan(
+        &self,
+        sender: &Sender<HashRequest>,
+        set_path: &Path,
+        request: &ScanRequest,
+    ) -> Result<()> {
+        // A manifest lists the files that were imported, collisions already resolved
+        let known = self
+            .known_hashes
+            .as_ref()
+            .and_then(|known| known.get(&request.folder_name));
+        let files = match known {
+            Some(known) => known.keys().map(|file| manifest::to_path(file)).collect(),
+            None => self.walk(set_path, request)?,
+        };
+
+        for stripped_path in files {
+            let full_path = set_path.join(&stripped_path);
+            // Stalls here while the hash stage is behind on large files
+            let size = fs::metadata(&full_path).map_or(0, |metadata| metadata.len());
+            let reserved = self.memory_budget.acquire(size);
+
+            sender.send(HashRequest {
+                beatmap_id: request.beatmap_id,
+                beatmapset_id: request.beatmapset_id,
+                folder_name: request.folder_name.clone(),
+                file_name: request.file_name.clone(),
+                full_path,
+                stripped_path,
+                size,
+                reserved,
+            })?;
+
+            self.bar.inc_length(1);
+        }
+
+        Ok(())
+    }
+
+    fn walk(&self, set_path: &Path, request: &ScanRequest) -> Result<Vec<PathBuf>> {
+        let mut files = vec![];
+        for entry in WalkDir::new(set_path) {
+            let entry = entry?;
+            let path = entry.path();
+
+            if !path.is_file() {
+                continue;
+            }
+
+            files.push(path.strip_prefix(set_path)?.to_path_buf());
+        }
+
+        for (kept, dropped) in resolve_case_collisions(&mut files, &request.referenced) {
+            self.report.warn(
+                &self.bar,
+                tr!(
+                    "warning-case-collision",
+                    folder = request.folder_name.as_str(),
+                    kept = format!("{:?}", kept),
+                    dropped = format!("{:?}", dropped)
+                ),
+            );
+        }
+
+        Ok(files)
+    }
+}
+
+// Files the .osu itself points to, used to pick a winner when a set has case-insensitive duplicates
+fn referenced_files(beatmap: &Beatmap, event_files: &EventFiles) -> Vec<String> {
+    let mut files = vec![beatmap.audio_filename.clone()];
+    files.extend(event_files.background.clone());
+    files.extend(event_files.video.clone());
+
+    files
+}
+
+// Lazer looks files up case-insensitively, so two paths differing only in case (`BG.jpg` and
+// `bg.jpg`) can't both be registered. Keeps one path per collision, preferring the one referenced
+// by the .osu, and returns the (kept, dropped) pairs so the caller can warn about them.
+fn resolve_case_collisions(
+    files: &mut Vec<PathBuf>,
+    referenced: &[String],
+) -> Vec<(PathBuf, PathBuf)> {
+    let normalize = |path: &Path| path.to_string_lossy().replace('\\', "/");
+    let referenced = referenced
+        .iter()
+        .map(|file| file.replace('\\', "/"))
+        .collect::<HashSet<_>>();
+
+    let mut collisions = vec![];
+    let groups = files
+        .drain(..)
+        .into_group_map_by(|path| normalize(path).to_lowercase());
+
+    for (_, mut group) in groups {
+        if group.len() > 1 {
+            let kept = group
+                .iter()
+                .position(|path| referenced.contains(&normalize(path)))
+                .unwrap_or(0);
+            let kept = group.swap_remove(kept);
+
+            for dropped in group {
+                collisions.push((kept.clone(), dropped));
+            }
+            files.push(kept);
+        } else {
+            files.append(&mut group);
+        }
+    }
+
+    files.sort_unstable();
+    collisions
+}
+
+// Files at least this big (mostly videos) get a bar of their own while they're read, since the
+// hash bar can sit still for a while on them
+const LARGE_FILE_SIZE: u64 = 64 * 1024 * 1024;
+const READ_CHUNK_SIZE: u64 = 1024 * 1024;
+// How many read files may wait for each hashing thread. Their memory is covered by the budget, so
+// this only keeps reading from running too far ahead.
+const QUEUED_PER_HASH_THREAD: usize = 4;
+
+// Hands out the smallest of the files scanned so far, so the insert stage gets a steady stream of
+// hashes rather than stalling while every thread is on a video. Larger files go once nothing
+// smaller is waiting, and the memory budget bounds how far ahead of them the scan can get.
+struct SmallestFirst {
+    receiver: Receiver<HashRequest>,
+    pending: BinaryHeap<Pending>,
+}
+
+// Ordered by size, smallest being the greatest, as BinaryHeap pops the greatest first
+struct Pending(HashRequest);
+
+impl Ord for Pending {
+    fn cmp(&self, other: &Self) -> cmp::Ordering {
+        other.0.size.cmp(&self.0.size)
+    }
+}
+
+impl PartialOrd for Pending {
+    fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Pending {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.size == other.0.size
+    }
+}
+
+impl Eq for Pending {}
+
+impl SmallestFirst {
+    fn new(receiver: Receiver<HashRequest>) -> Self {
+        Self {
+            receiver,
+            pending: BinaryHeap::new(),
+        }
+    }
+}
+
+impl Iterator for SmallestFirst {
+    type Item = HashRequest;
+
+    fn next(&mut self) -> Option<HashRequest> {
+        // Only waits when nothing is pending, and ends once the scan is done and all was handed out
+        if self.pending.is_empty() {
+            self.pending.push(Pending(self.receiver.recv().ok()?));
+        }
+        self.pending.extend(self.receiver.try_iter().map(Pending));
+
+        self.pending.pop().map(|pending| pending.0)
+    }
+}
+
+// A file on its way from the reading threads to the hashing ones
+struct ReadFile {
+    request: HashRequest,
+    data: Result<FileData>,
+}
+
+enum FileData {
+    Contents(Vec<u8>),
+    // Known without reading the file, from a manifest or FAKE_HASH
+    Hash(String),
+}
+
+pub struct HashProcessor {
+    manager: MultiProgress,
+    large_file_style: ProgressStyle,
+    overall_bar: ProgressBar,
+    bar: ProgressBar,
+    insert_bar: ProgressBar,
+    worker_bars: Vec<ProgressBar>,
+    known_hashes: Option<Arc<KnownHashes>>,
+    report: Report,
+    timings: Timings,
+    memory_budget: MemoryBudget,
+    read_threads: usize,
+    hash_threads: usize,
+}
+
+impl HashProcessor {
+    pub fn new(state: &State) -> Self {
+        Self {
+            manager: state.progress_bars.manager.clone(),
+            large_file_style: state.progress_styles.large_file.clone(),
+            overall_bar: state.progress_bars.overall.clone(),
+            bar: state.progress_bars.hash.clone(),
+            insert_bar: state.progress_bars.hash_insert.clone(),
+            worker_bars: state.progress_bars.hash_workers.clone(),
+            known_hashes: state.known_hashes.clone(),
+            report: state.report.clone(),
+            timings: state.timings.clone(),
+            memory_budget: state.memory_budget.clone(),
+            read_threads: state.threads.read,
+            hash_threads: state.threads.hash,
+        }
+    }
+
+    // Files are read and hashed on separate pools, with the read ones queued in between, so a slow
+    // disk doesn't leave the CPU idle and the other way around
+    pub fn start(self, sender: Sender<Processed>, receiver: Receiver<HashRequest>) {
+        let read_pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(self.read_threads)
+            .thread_name(|i| format!("(osu-link) read thread {}", i))
+            .build()
+            .unwrap();
+        let hash_pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(self.hash_threads)
+            .thread_name(|i| format!("(osu-link) hash thread {}", i))
+            .build()
+            .unwrap();
+        let started = Instant::now();
+        let (read_sx, read_rx) = mpsc::sync_channel(self.hash_threads * QUEUED_PER_HASH_THREAD);
+        // Dropped by the hashing side once it has gone through every read file
+        let (done_sx, done_rx) = mpsc::channel::<()>();
+
+        let this = Arc::new(self);
+        let hasher = Arc::clone(&this);
+        hash_pool.spawn(move || {
+            read_rx
+                .into_iter()
+                .par_bridge()
+                .for_each_with(sender, |sender, file| hasher.hash(sender, file, started));
+            drop(done_sx);
+        });
+
+        read_pool.install(|| {
+            SmallestFirst::new(receiver)
+                .par_bridge()
+                .for_each_with(read_sx, |read_sx, request| this.read(read_sx, request));
+        });
+        for worker_bar in &this.worker_bars {
+            worker_bar.finish_and_clear();
+        }
+
+        // Everything has been read, and the queue closed with the last reading thread
+        let _ = done_rx.recv();
+        this.bar.finish_with_message(tr!("done"));
+        steps::advance(&this.overall_bar, Step::Writing);
+    }
+
+    fn read(&self, sender: &SyncSender<ReadFile>, request: HashRequest) {
+        let worker_bar = rayon::current_thread_index().and_then(|i| self.worker_bars.get(i));
+        if let Some(worker_bar) = worker_bar {
+            worker_bar.set_message(format!("{}/{}", request.folder_name, request.file_name));
+            worker_bar.reset_elapsed();
+        }
+
+        let data = match self.known_hash(&request) {
+            Some(hash) => Ok(FileData::Hash(hash)),
+            None => self
+                .timings
+                .time(Stage::Reading, || self.read_file(&request.full_path)),
+        };
+
+        if let Some(worker_bar) = worker_bar {
+            worker_bar.set_message(tr!("worker-idle"));
+            worker_bar.reset_elapsed();
+        }
+        // Stalls here while the hashing threads are behind
+        sender.send(ReadFile { request, data }).unwrap();
+    }
+
+    fn hash(&self, sender: &Sender<Processed>, file: ReadFile, started: Instant) {
+        let ReadFile { request, data } = file;
+        let res = data.and_then(|data| match data {
+            FileData::Contents(contents) => self
+                .timings
+                .time(Stage::Hashing, || self.hash_contents(&contents)),
+            FileData::Hash(hash) => Ok(hash),
+        });
+        self.memory_budget.release(request.reserved);
+
+        // Files vary too much in size for files/s to say much, so the bytes hashed per second are
+        // shown instead
+        let elapsed = started.elapsed().as_secs_f64();
+        if elapsed > 0.0 {
+            let rate = self.report.bytes_hashed() as f64 / elapsed;
+            self.bar
+                .set_message(format!("{}/s", HumanBytes(rate as u64)));
+        }
+        self.bar.inc(1);
+
+        match res {
+            Ok(hash) => {
+                sender
+                    .send(Processed::File(HashProcessed { request, hash }))
+                    .unwrap();
+                self.insert_bar.inc_length(1);
+            }
+            Err(e) => {
+                self.report.error(
+                    &self.bar,
+                    tr!(
+                        "error-processing",
+                        folder = request.folder_name.as_str(),
+                        file = request.file_name.as_str()
+                    ),
+                );
+                self.report.error(&self.bar, format!("{}", e));
+                self.report
+                    .add_failure(FailureKind::of(&e), Some(&request.folder_name));
+            }
+        }
+    }
+
+    // The hash `apply-manifest`'s manifest has for the file, if any
+    fn known_hash(&self, request: &HashRequest) -> Option<String> {
+        let file = manifest::normalize(request.stripped_path.to_str()?);
+        self.known_hashes
+            .as_ref()?
+            .get(&request.folder_name)?
+            .get(&file)
+            .cloned()
+    }
+
+    fn read_file(&self, path: &Path) -> Result<FileData> {
         if FAKE_HASH {
             let mut hash: [u8; 32] = [0; 32];
             thread_rng().fill(&mut hash);
@@ -186,16 +716,57 @@ impl HashProcessor {
                 write!(ret, "{:02x}", byte)?;
             }
 
-            return Ok(ret);
+            return Ok(FileData::Hash(ret));
         }
 
         let mut fd = File::open(path)?;
-        let mut buf = vec![];
-        fd.read_to_end(&mut buf)?;
+        let size = fd.metadata()?.len();
+        let file_bar = (size >= LARGE_FILE_SIZE).then(|| {
+            self.manager
+                .add(ProgressBar::new(size))
+                .with_prefix(
+                    path.file_name()
+                        .unwrap_or_default()
+                        .to_string_lossy()
+                        .into_owned(),
+                )
+                .with_style(self.large_file_style.clone())
+        });
+
+        let res = Self::read_stream(&mut fd, size, file_bar.as_ref());
+        if let Some(file_bar) = file_bar {
+            file_bar.finish_and_clear();
+        }
+
+        Ok(FileData::Contents(res?))
+    }
+
+    // Reads in chunks, so large files don't leave their bar frozen
+    fn read_stream(
+        fd: &mut File,
+        size: u64,
+        file_bar: Option<&ProgressBar>,
+    ) -> io::Result<Vec<u8>> {
+        let mut contents = Vec::with_capacity(size as usize);
+        loop {
+            let read = fd
+                .by_ref()
+                .take(READ_CHUNK_SIZE)
+                .read_to_end(&mut contents)?;
+            if read == 0 {
+                break;
+            }
+            if let Some(file_bar) = file_bar {
+                file_bar.inc(read as u64);
+            }
+        }
+
+        Ok(contents)
+    }
 
-        let mut hash = Sha256::new();
-        hash.update(buf);
-        let hash = hash.finalize();
+    fn hash_contents(&self, contents: &[u8]) -> Result<String> {
+        let hash = Sha256::digest(contents);
+        self.report.add_bytes_hashed(contents.len() as u64);
 
         let mut ret = String::with_capacity(2 * hash.len());
         for byte in hash {
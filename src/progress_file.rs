@@ -0,0 +1,195 @@
+use anyhow::Result;
+use chrono::Utc;
+use indicatif::ProgressBar;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread::{sleep, spawn, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    log::{log, Level},
+    report::Report,
+    State,
+};
+
+const STAGES: [&str; 4] = [
+    "processing-beatmaps",
+    "inserting-beatmaps",
+    "processing-files",
+    "inserting-files",
+];
+const INTERVAL: Duration = Duration::from_secs(2);
+
+// What's written to the progress file, and answered to `status`
+#[derive(Serialize, Deserialize)]
+pub struct Progress {
+    // Unix timestamp of when it was written
+    pub timestamp: i64,
+    // In seconds
+    pub elapsed: u64,
+    // The first stage that isn't done yet, or "done"
+    pub stage: String,
+    // Of the whole import, with each stage counting as much as the others
+    pub percent: f64,
+    pub stages: Vec<StageProgress>,
+    // Folder of the set whose beatmaps are being inserted
+    pub current_set: String,
+    pub failures: usize,
+    pub done: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct StageProgress {
+    pub name: String,
+    pub position: u64,
+    pub length: u64,
+    pub percent: f64,
+}
+
+// Reads how far the import is from another thread. Like the dashboard, it goes by the progress
+// bars' positions.
+#[derive(Clone)]
+pub struct Probe {
+    bars: [ProgressBar; 4],
+    report: Report,
+    current_set: Arc<Mutex<String>>,
+    started: Instant,
+}
+
+impl Probe {
+    pub fn new(state: &State) -> Self {
+        Self {
+            bars: [
+                state.progress_bars.beatmap.clone(),
+                state.progress_bars.beatmap_insert.clone(),
+                state.progress_bars.hash.clone(),
+                state.progress_bars.hash_insert.clone(),
+            ],
+            report: state.report.clone(),
+            current_set: state.current_set.clone(),
+            started: Instant::now(),
+        }
+    }
+
+    pub fn progress(&self, done: bool) -> Progress {
+        let stages = STAGES
+            .iter()
+            .zip(&self.bars)
+            .map(|(&name, bar)| {
+                let (position, length) = (bar.position(), bar.length());
+                StageProgress {
+                    name: name.to_string(),
+                    position,
+                    length,
+                    percent: if bar.is_finished() {
+                        100.0
+                    } else if length > 0 {
+                        (100.0 * position as f64 / length as f64).min(100.0)
+                    } else {
+                        0.0
+                    },
+                }
+            })
+            .collect::<Vec<_>>();
+        let stage = match STAGES
+            .iter()
+            .zip(&self.bars)
+            .find(|(_, bar)| !bar.is_finished())
+        {
+            Some((&name, _)) if !done => name,
+            _ => "done",
+        };
+
+        Progress {
+            timestamp: Utc::now().timestamp(),
+            elapsed: self.started.elapsed().as_secs(),
+            stage: stage.to_string(),
+            percent: stages.iter().map(|stage| stage.percent).sum::<f64>() / stages.len() as f64,
+            stages,
+            current_set: self.current_set.lock().unwrap().clone(),
+            failures: self.report.failures(),
+            done,
+        }
+    }
+}
+
+pub fn path() -> Option<PathBuf> {
+    dirs::data_local_dir().map(|path| path.join("osu-link").join("progress.json"))
+}
+
+// Writes how far the import is to progress.json in osu-link's data directory every few seconds,
+// for stream overlays and the like to poll without attaching to the process
+pub struct ProgressFile {
+    done: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl ProgressFile {
+    pub fn start(state: &State) -> Option<Self> {
+        let path = path()?;
+        let probe = Probe::new(state);
+
+        let done = Arc::new(AtomicBool::new(false));
+        let thread_done = done.clone();
+        let thread = spawn(move || {
+            let mut last_write: Option<Instant> = None;
+            loop {
+                let finished = thread_done.load(Ordering::Relaxed);
+                if finished || last_write.map_or(true, |at| at.elapsed() >= INTERVAL) {
+                    // A dashboard that can't read it is no reason to stop the import
+                    if let Err(e) = write(&path, &probe.progress(finished)) {
+                        log!(Level::Debug, "Couldn't write the progress file: {}", e);
+                    }
+                    last_write = Some(Instant::now());
+                }
+                if finished {
+                    break;
+                }
+                sleep(Duration::from_millis(100));
+            }
+        });
+
+        Some(Self {
+            done,
+            thread: Some(thread),
+        })
+    }
+
+    // Writes the final progress, marked as done
+    pub fn finish(mut self) {
+        self.join();
+    }
+
+    fn join(&mut self) {
+        self.done.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+// Stops writing if the import bails out early
+impl Drop for ProgressFile {
+    fn drop(&mut self) {
+        self.join();
+    }
+}
+
+// Written next to it first, so whatever polls it never reads half a file
+fn write(path: &Path, progress: &Progress) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let temp_path = path.with_extension("json.tmp");
+    fs::write(&temp_path, serde_json::to_vec_pretty(progress)?)?;
+    fs::rename(&temp_path, path)?;
+
+    Ok(())
+}
@@ -0,0 +1,96 @@
+use anyhow::Result;
+use rusqlite::{params, Transaction};
+use std::{fs::File, io::BufReader, path::Path};
+
+use crate::{
+    database,
+    log::{log, Level},
+    paths,
+    stable_db::DbReader,
+};
+
+// Points scores at the beatmap their replay was played on. A replay names its beatmap by the MD5
+// of the .osu, while the score refers to it by ID, which goes wrong when the score was imported
+// before the beatmap, or the beatmap was imported again as another row. Returns how many scores
+// were fixed.
+pub fn relink(tx: &Transaction, lazer_path: &Path) -> Result<usize> {
+    if !database::has_table(tx, "ScoreInfo")? || !database::has_table(tx, "ScoreFileInfo")? {
+        return Ok(0);
+    }
+
+    let replays = tx
+        .prepare(
+            "SELECT s.ID, s.BeatmapInfoID, f.Hash
+             FROM ScoreInfo s
+             JOIN ScoreFileInfo sf ON sf.ScoreInfoID = s.ID
+             JOIN FileInfo f ON f.ID = sf.FileInfoID
+             WHERE s.DeletePending = 0",
+        )?
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, Option<i64>>(1)?,
+                row.get::<_, String>(2)?,
+            ))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    let lazer_path = paths::extended(lazer_path);
+    let mut beatmaps = tx.prepare(
+        "SELECT ID
+         FROM BeatmapInfo
+         WHERE MD5Hash = ?
+         ORDER BY ID",
+    )?;
+    let mut relinked = 0;
+    for (score_id, beatmap_info_id, replay_hash) in replays {
+        let beatmap_hash = match read_beatmap_hash(&paths::store_file(&lazer_path, &replay_hash)) {
+            Ok(hash) => hash,
+            Err(e) => {
+                log!(
+                    Level::Debug,
+                    "Couldn't read the replay of score {}: {}",
+                    score_id,
+                    e
+                );
+                continue;
+            }
+        };
+
+        let ids = beatmaps
+            .query_map([&beatmap_hash], |row| row.get::<_, i64>(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        let id = match ids.first() {
+            Some(_) if beatmap_info_id.map_or(false, |id| ids.contains(&id)) => continue,
+            Some(id) => *id,
+            // The beatmap isn't in osu!lazer (anymore), so the score is left as it is
+            None => continue,
+        };
+
+        tx.execute(
+            "UPDATE ScoreInfo
+             SET BeatmapInfoID = ?
+             WHERE ID = ?",
+            params![id, score_id],
+        )?;
+        log!(
+            Level::Trace,
+            "UPDATE ScoreInfo ID={} BeatmapInfoID={:?} -> {}",
+            score_id,
+            beatmap_info_id,
+            id
+        );
+        relinked += 1;
+    }
+
+    Ok(relinked)
+}
+
+// An .osr starts with the ruleset and game version, followed by the beatmap's MD5
+fn read_beatmap_hash(path: &Path) -> Result<String> {
+    let mut reader = DbReader(BufReader::new(File::open(path)?));
+    reader.u8()?;
+    reader.i32()?;
+
+    Ok(reader.string()?)
+}
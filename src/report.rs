@@ -0,0 +1,166 @@
+use indicatif::ProgressBar;
+use itertools::Itertools;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use crate::{
+    i18n::tr,
+    log::{log, Level},
+    theme,
+};
+
+// What a failure was caused by, to tell harmless skips (a set shipping a file it doesn't have)
+// from real problems
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum FailureKind {
+    MissingFile,
+    Parse,
+    Io,
+    Link,
+    Database,
+    Integrity,
+}
+
+impl FailureKind {
+    // Sorts an error by its innermost known cause. Anything that isn't IO or SQLite comes from
+    // reading the beatmap itself.
+    pub fn of(err: &anyhow::Error) -> Self {
+        for cause in err.chain() {
+            if let Some(e) = cause.downcast_ref::<io::Error>() {
+                return if e.kind() == io::ErrorKind::NotFound {
+                    Self::MissingFile
+                } else {
+                    Self::Io
+                };
+            }
+            if cause.downcast_ref::<rusqlite::Error>().is_some() {
+                return Self::Database;
+            }
+        }
+
+        Self::Parse
+    }
+
+    fn label(self) -> String {
+        tr!(match self {
+            Self::MissingFile => "failure-missing-file",
+            Self::Parse => "failure-parse",
+            Self::Io => "failure-io",
+            Self::Link => "failure-link",
+            Self::Database => "failure-database",
+            Self::Integrity => "failure-integrity",
+        })
+    }
+}
+
+// Collects the warnings and errors of an import, along with the number of beatmaps or files that
+// failed and of bytes hashed so far.
+// Messages are printed above the progress bars, unless the dashboard is drawing the screen, in
+// which case they only show up in its error pane.
+#[derive(Clone, Default)]
+pub struct Report {
+    messages: Arc<Mutex<Vec<String>>>,
+    bytes_hashed: Arc<AtomicU64>,
+    // Number of failures and the sets affected, by kind
+    failures: Arc<Mutex<BTreeMap<FailureKind, (usize, BTreeSet<String>)>>>,
+    quiet: Arc<AtomicBool>,
+}
+
+impl Report {
+    pub fn warn(&self, bar: &ProgressBar, message: String) {
+        if !self.quiet.load(Ordering::Relaxed) {
+            bar.println(theme::warning(&message));
+        }
+        log!(Level::Info, "{}", message);
+        self.messages.lock().unwrap().push(message);
+    }
+
+    pub fn error(&self, bar: &ProgressBar, message: String) {
+        if !self.quiet.load(Ordering::Relaxed) {
+            bar.println(theme::error(&message));
+        }
+        log!(Level::Info, "{}", message);
+        self.messages.lock().unwrap().push(message);
+    }
+
+    pub fn set_quiet(&self, quiet: bool) {
+        self.quiet.store(quiet, Ordering::Relaxed);
+    }
+
+    pub fn messages(&self) -> Vec<String> {
+        self.messages.lock().unwrap().clone()
+    }
+
+    // Counts a beatmap or file that couldn't be imported, and the set folder it's from if any
+    pub fn add_failure(&self, kind: FailureKind, set: Option<&str>) {
+        let mut failures = self.failures.lock().unwrap();
+        let (count, sets) = failures.entry(kind).or_default();
+        *count += 1;
+        if let Some(set) = set {
+            sets.insert(set.to_string());
+        }
+    }
+
+    // Every set folder something failed in
+    pub fn failed_sets(&self) -> BTreeSet<String> {
+        let failures = self.failures.lock().unwrap();
+        failures
+            .values()
+            .flat_map(|(_, sets)| sets.iter().cloned())
+            .collect()
+    }
+
+    pub fn failures(&self) -> usize {
+        let failures = self.failures.lock().unwrap();
+        failures.values().map(|(count, _)| count).sum()
+    }
+
+    pub fn failures_of(&self, kind: FailureKind) -> usize {
+        let failures = self.failures.lock().unwrap();
+        failures.get(&kind).map_or(0, |(count, _)| *count)
+    }
+
+    // The number of failures of each kind, and a few of the sets they affected
+    pub fn breakdown(&self) -> Vec<String> {
+        const SHOWN_SETS: usize = 5;
+
+        let failures = self.failures.lock().unwrap();
+        if failures.is_empty() {
+            return vec![];
+        }
+
+        let mut lines = vec![tr!("failure-breakdown")];
+        for (kind, (count, sets)) in failures.iter() {
+            let mut shown = sets.iter().take(SHOWN_SETS).join(", ");
+            if sets.len() > SHOWN_SETS {
+                shown = tr!(
+                    "failure-more-sets",
+                    sets = shown,
+                    count = sets.len() - SHOWN_SETS
+                );
+            }
+            lines.push(tr!(
+                "failure-category",
+                category = kind.label(),
+                count = *count,
+                sets = shown
+            ));
+        }
+
+        lines
+    }
+
+    pub fn add_bytes_hashed(&self, bytes: u64) {
+        self.bytes_hashed.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    pub fn bytes_hashed(&self) -> u64 {
+        self.bytes_hashed.load(Ordering::Relaxed)
+    }
+}
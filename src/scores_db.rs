@@ -0,0 +1,139 @@
+use anyhow::{Context, Result};
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Write},
+    path::Path,
+};
+
+use crate::stable_db::{DbReader, DbWriter};
+
+// What scores.db is written with when osu!stable doesn't have one yet
+pub const DEFAULT_VERSION: i32 = 20150203;
+// Only scores played with Target Practice have their accuracy stored separately
+const TARGET_PRACTICE: i32 = 1 << 23;
+
+// osu!stable's scores.db, holding the local scores of each beatmap
+// https://github.com/ppy/osu/wiki/Legacy-database-file-structure
+pub struct ScoresDb {
+    pub version: i32,
+    // By the MD5 of the .osu
+    pub beatmaps: Vec<(String, Vec<Score>)>,
+}
+
+pub struct Score {
+    pub mode: u8,
+    pub version: i32,
+    pub beatmap_hash: String,
+    pub player: String,
+    // Names the replay in Data/r, and tells scores apart
+    pub replay_hash: String,
+    pub count_300: u16,
+    pub count_100: u16,
+    pub count_50: u16,
+    pub count_geki: u16,
+    pub count_katu: u16,
+    pub count_miss: u16,
+    pub score: i32,
+    pub max_combo: u16,
+    pub perfect: bool,
+    pub mods: i32,
+    pub life_graph: String,
+    // In windows ticks
+    pub timestamp: i64,
+    pub online_id: i64,
+    pub target_practice_accuracy: Option<f64>,
+}
+
+impl Default for ScoresDb {
+    fn default() -> Self {
+        Self {
+            version: DEFAULT_VERSION,
+            beatmaps: vec![],
+        }
+    }
+}
+
+pub fn read(path: &Path) -> Result<ScoresDb> {
+    let fd =
+        File::open(path).with_context(|| format!("Failed to open the scores at {:?}", path))?;
+    let mut reader = DbReader(BufReader::new(fd));
+
+    let version = reader.i32()?;
+    let mut beatmaps = vec![];
+    for _ in 0..reader.count()? {
+        let hash = reader.string()?;
+        let mut scores = vec![];
+        for _ in 0..reader.count()? {
+            let mut score = Score {
+                mode: reader.u8()?,
+                version: reader.i32()?,
+                beatmap_hash: reader.string()?,
+                player: reader.string()?,
+                replay_hash: reader.string()?,
+                count_300: reader.u16()?,
+                count_100: reader.u16()?,
+                count_50: reader.u16()?,
+                count_geki: reader.u16()?,
+                count_katu: reader.u16()?,
+                count_miss: reader.u16()?,
+                score: reader.i32()?,
+                max_combo: reader.u16()?,
+                perfect: reader.u8()? != 0,
+                mods: reader.i32()?,
+                life_graph: reader.string()?,
+                timestamp: reader.i64()?,
+                online_id: 0,
+                target_practice_accuracy: None,
+            };
+            reader.skip(4)?; // always -1
+            score.online_id = reader.i64()?;
+            if score.mods & TARGET_PRACTICE != 0 {
+                score.target_practice_accuracy = Some(reader.f64()?);
+            }
+            scores.push(score);
+        }
+        beatmaps.push((hash, scores));
+    }
+
+    Ok(ScoresDb { version, beatmaps })
+}
+
+pub fn write(path: &Path, db: &ScoresDb) -> Result<()> {
+    let fd =
+        File::create(path).with_context(|| format!("Failed to write the scores at {:?}", path))?;
+    let mut writer = DbWriter(BufWriter::new(fd));
+
+    writer.i32(db.version)?;
+    writer.count(db.beatmaps.len())?;
+    for (hash, scores) in &db.beatmaps {
+        writer.string(hash)?;
+        writer.count(scores.len())?;
+        for score in scores {
+            writer.u8(score.mode)?;
+            writer.i32(score.version)?;
+            writer.string(&score.beatmap_hash)?;
+            writer.string(&score.player)?;
+            writer.string(&score.replay_hash)?;
+            writer.u16(score.count_300)?;
+            writer.u16(score.count_100)?;
+            writer.u16(score.count_50)?;
+            writer.u16(score.count_geki)?;
+            writer.u16(score.count_katu)?;
+            writer.u16(score.count_miss)?;
+            writer.i32(score.score)?;
+            writer.u16(score.max_combo)?;
+            writer.u8(score.perfect as u8)?;
+            writer.i32(score.mods)?;
+            writer.string(&score.life_graph)?;
+            writer.i64(score.timestamp)?;
+            writer.i32(-1)?;
+            writer.i64(score.online_id)?;
+            if let Some(accuracy) = score.target_practice_accuracy {
+                writer.f64(accuracy)?;
+            }
+        }
+    }
+    writer.0.flush()?;
+
+    Ok(())
+}
@@ -0,0 +1,90 @@
+use anyhow::Result;
+use dialoguer::{Input, MultiSelect};
+use indicatif::HumanBytes;
+use itertools::Itertools;
+use std::{collections::HashSet, path::Path};
+use walkdir::WalkDir;
+
+use crate::{i18n::tr, library::StableBeatmap, paths};
+
+// Lets the user narrow the beatmaps down to the sets they tick. Sets are told apart by their
+// folder, which also keeps local maps (that all share the same set ID) apart.
+pub fn select_sets(beatmaps: Vec<StableBeatmap>) -> Result<Vec<StableBeatmap>> {
+    let filter: String = Input::new()
+        .with_prompt(tr!("select-filter"))
+        .allow_empty(true)
+        .interact_text()?;
+    let filter = filter.trim().to_lowercase();
+
+    let sets = beatmaps
+        .iter()
+        .sorted_by(|a, b| {
+            a.beatmap_set_id
+                .cmp(&b.beatmap_set_id)
+                .then_with(|| a.folder_name.cmp(&b.folder_name))
+        })
+        .group_by(|bm| bm.folder_name.as_str())
+        .into_iter()
+        .map(|(folder_name, group)| (folder_name, group.collect_vec()))
+        .filter(|(_, group)| {
+            let bm = group[0];
+            filter.is_empty()
+                || bm.beatmap_set_id.to_string() == filter
+                || bm.artist.to_lowercase().contains(&filter)
+                || bm.title.to_lowercase().contains(&filter)
+        })
+        .collect_vec();
+
+    if sets.is_empty() {
+        println!("{}", tr!("select-no-match"));
+        return Ok(vec![]);
+    }
+
+    let items = sets
+        .iter()
+        .map(|(folder_name, group)| {
+            let bm = group[0];
+            let set_id = if bm.beatmap_set_id == u32::MAX {
+                "-".to_string()
+            } else {
+                bm.beatmap_set_id.to_string()
+            };
+
+            let songs_path = paths::extended(&bm.songs_path);
+            let size = folder_size(&paths::resolve(&songs_path, folder_name));
+            tr!(
+                "select-item",
+                set = format!("{:>7}", set_id),
+                artist = bm.artist.as_str(),
+                title = bm.title.as_str(),
+                count = group.len(),
+                size = HumanBytes(size).to_string()
+            )
+        })
+        .collect_vec();
+
+    let selected = MultiSelect::new()
+        .with_prompt(tr!("select-prompt"))
+        .items(&items)
+        .defaults(&vec![true; items.len()])
+        .interact()?;
+
+    let folders: HashSet<&str> = selected.into_iter().map(|i| sets[i].0).collect();
+    let selected = beatmaps
+        .iter()
+        .filter(|bm| folders.contains(bm.folder_name.as_str()))
+        .cloned()
+        .collect_vec();
+
+    Ok(selected)
+}
+
+fn folder_size(path: &Path) -> u64 {
+    WalkDir::new(path)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.metadata().ok())
+        .filter(|metadata| metadata.is_file())
+        .map(|metadata| metadata.len())
+        .sum()
+}
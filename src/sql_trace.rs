@@ -0,0 +1,60 @@
+use anyhow::{Context, Result};
+use once_cell::sync::OnceCell;
+use rusqlite::Connection;
+use std::{fs::File, io::Write, path::Path, sync::Mutex};
+
+// Hashes are cut down to this many characters, which is still enough to tell files apart when
+// replaying the trace
+const HASH_LENGTH: usize = 12;
+
+static TRACE: OnceCell<Mutex<File>> = OnceCell::new();
+
+// Starts recording statements to `path`, for --sql-trace
+pub fn init(path: &Path) -> Result<()> {
+    let file = File::create(path)
+        .with_context(|| format!("Failed to create the SQL trace at {:?}", path))?;
+    let _ = TRACE.set(Mutex::new(file));
+
+    Ok(())
+}
+
+// Records every statement run on the connection, with its parameters filled in, if --sql-trace
+// was given
+pub fn attach(connection: &mut Connection) {
+    if TRACE.get().is_some() {
+        connection.trace(Some(record));
+    }
+}
+
+fn record(sql: &str) {
+    if let Some(file) = TRACE.get() {
+        let mut file = file.lock().unwrap();
+        let _ = writeln!(file, "{};", truncate_hashes(sql));
+    }
+}
+
+// Runs of 32 or more hex digits are MD5 or SHA-256 hashes, which make up most of a trace
+fn truncate_hashes(sql: &str) -> String {
+    let mut ret = String::with_capacity(sql.len());
+    let mut run = String::new();
+    for c in sql.chars() {
+        if c.is_ascii_hexdigit() {
+            run.push(c);
+        } else {
+            push_run(&mut ret, &mut run);
+            ret.push(c);
+        }
+    }
+    push_run(&mut ret, &mut run);
+
+    ret
+}
+
+fn push_run(ret: &mut String, run: &mut String) {
+    if run.len() >= 32 {
+        ret.push_str(&run[..HASH_LENGTH]);
+    } else {
+        ret.push_str(run);
+    }
+    run.clear();
+}
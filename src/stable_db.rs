@@ -0,0 +1,276 @@
+use anyhow::Result;
+use std::{
+    fs::File,
+    io::{self, BufReader, Read, Write},
+    path::Path,
+};
+
+use crate::library::{self, StableBeatmap};
+
+// osu!.db versions where the layout changed
+// https://github.com/ppy/osu/wiki/Legacy-database-file-structure
+const FLOAT_DIFFICULTY: i32 = 20140609;
+const NO_ENTRY_SIZE: i32 = 20191106;
+const FLOAT_STAR_RATINGS: i32 = 20250107;
+
+// What could be read of an osu!.db that libosu couldn't parse
+pub struct LenientDb {
+    pub version: i32,
+    pub beatmaps: Vec<StableBeatmap>,
+    // How many beatmaps the header says there are
+    pub expected: usize,
+}
+
+// A lenient osu!.db reader for versions libosu doesn't know, keeping only what the import needs.
+// Before NO_ENTRY_SIZE every entry starts with its size, so fields added at the end of an entry are
+// skipped over. Otherwise reading stops at the first entry that doesn't parse, keeping the ones
+// before it.
+pub fn read_lenient(path: &Path, songs_path: &Path) -> Result<LenientDb> {
+    let mut reader = DbReader(BufReader::new(File::open(path)?));
+
+    let version = reader.i32()?;
+    reader.i32()?; // folder count
+    reader.skip(1 + 8)?; // account unlocked, unlock date
+    reader.string()?; // player name
+    let expected = reader.i32()?.max(0) as usize;
+
+    // The count comes from the file, so a corrupt one mustn't reserve gigabytes up front
+    let mut beatmaps = Vec::with_capacity(expected.min(1 << 16));
+    for _ in 0..expected {
+        let res = if version < NO_ENTRY_SIZE {
+            reader
+                .entry()
+                .and_then(|entry| DbReader(entry.as_slice()).beatmap(version, songs_path))
+        } else {
+            reader.beatmap(version, songs_path)
+        };
+
+        match res {
+            Ok(beatmap) => beatmaps.push(beatmap),
+            Err(_) => break,
+        }
+    }
+
+    Ok(LenientDb {
+        version,
+        beatmaps,
+        expected,
+    })
+}
+
+// Reads the types osu!stable's .db files are made of, which collection.db and scores.db share
+pub struct DbReader<R>(pub R);
+
+impl<R: Read> DbReader<R> {
+    fn bytes<const N: usize>(&mut self) -> io::Result<[u8; N]> {
+        let mut bytes = [0; N];
+        self.0.read_exact(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    pub fn skip(&mut self, len: u64) -> io::Result<()> {
+        let skipped = io::copy(&mut (&mut self.0).take(len), &mut io::sink())?;
+        if skipped < len {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        Ok(())
+    }
+
+    pub fn u8(&mut self) -> io::Result<u8> {
+        Ok(self.bytes::<1>()?[0])
+    }
+
+    pub fn u16(&mut self) -> io::Result<u16> {
+        Ok(u16::from_le_bytes(self.bytes()?))
+    }
+
+    pub fn i32(&mut self) -> io::Result<i32> {
+        Ok(i32::from_le_bytes(self.bytes()?))
+    }
+
+    pub fn i64(&mut self) -> io::Result<i64> {
+        Ok(i64::from_le_bytes(self.bytes()?))
+    }
+
+    fn f32(&mut self) -> io::Result<f32> {
+        Ok(f32::from_le_bytes(self.bytes()?))
+    }
+
+    pub fn f64(&mut self) -> io::Result<f64> {
+        Ok(f64::from_le_bytes(self.bytes()?))
+    }
+
+    // A count that can't be negative
+    pub fn count(&mut self) -> io::Result<u64> {
+        let count = self.i32()?;
+        if count < 0 {
+            return Err(io::ErrorKind::InvalidData.into());
+        }
+        Ok(count as u64)
+    }
+
+    // 0x00 for an empty string, or 0x0b followed by a ULEB128 length and UTF-8
+    pub fn string(&mut self) -> io::Result<String> {
+        match self.u8()? {
+            0x00 => Ok(String::new()),
+            0x0b => {
+                let mut len = 0u64;
+                let mut shift = 0;
+                loop {
+                    let byte = self.u8()?;
+                    len |= u64::from(byte & 0x7f) << shift;
+                    if byte & 0x80 == 0 {
+                        break;
+                    }
+                    shift += 7;
+                    if shift > 63 {
+                        return Err(io::ErrorKind::InvalidData.into());
+                    }
+                }
+
+                let mut bytes = vec![];
+                (&mut self.0).take(len).read_to_end(&mut bytes)?;
+                if (bytes.len() as u64) < len {
+                    return Err(io::ErrorKind::UnexpectedEof.into());
+                }
+                String::from_utf8(bytes).map_err(|_| io::ErrorKind::InvalidData.into())
+            }
+            _ => Err(io::ErrorKind::InvalidData.into()),
+        }
+    }
+
+    // The bytes of one size-prefixed entry
+    fn entry(&mut self) -> io::Result<Vec<u8>> {
+        let len = self.count()?;
+        let mut entry = vec![];
+        (&mut self.0).take(len).read_to_end(&mut entry)?;
+        if (entry.len() as u64) < len {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        Ok(entry)
+    }
+
+    fn beatmap(&mut self, version: i32, songs_path: &Path) -> io::Result<StableBeatmap> {
+        let artist = self.string()?;
+        self.string()?; // artist (unicode)
+        let title = self.string()?;
+        self.string()?; // title (unicode)
+        let creator = self.string()?;
+        self.string()?; // difficulty name
+        self.string()?; // audio file
+        let hash = self.string()?;
+        let beatmap_file_name = self.string()?;
+        let ranked_status = self.u8()?;
+        self.skip(2 + 2 + 2)?; // circle, slider and spinner counts
+        let modification_date = self.i64()? as u64;
+        // AR, CS, HP and OD
+        self.skip(if version < FLOAT_DIFFICULTY { 4 } else { 4 * 4 })?;
+        self.skip(8)?; // slider velocity
+
+        let mut star_ratings = [0.0; 4];
+        if version >= FLOAT_DIFFICULTY {
+            for star_rating in &mut star_ratings {
+                for _ in 0..self.count()? {
+                    self.skip(1)?;
+                    let mods = self.i32()?;
+                    self.skip(1)?;
+                    let value = if version >= FLOAT_STAR_RATINGS {
+                        f64::from(self.f32()?)
+                    } else {
+                        self.f64()?
+                    };
+                    if mods == 0 {
+                        *star_rating = value;
+                    }
+                }
+            }
+        }
+
+        self.skip(4)?; // drain time
+        let total_time = f64::from(self.i32()?);
+        self.skip(4)?; // preview time
+        let timing_points = self.count()?;
+        self.skip(timing_points * (8 + 8 + 1))?;
+        // Unsubmitted sets have an ID of -1, which ends up as u32::MAX like with libosu
+        let beatmap_id = self.i32()? as u32;
+        let beatmap_set_id = self.i32()? as u32;
+        self.skip(4 + 4 + 2 + 4 + 1)?; // thread ID, grades, local offset, stack leniency, mode
+        let source = self.string()?;
+        let tags = library::split_tags(&self.string()?);
+        self.skip(2)?; // online offset
+        self.string()?; // title font
+        self.skip(1 + 8 + 1)?; // unplayed, last played, osz2
+        let folder_name = self.string()?;
+        // Last online check, then ignore sound/skin, disable storyboard/video and visual override
+        self.skip(8 + 5)?;
+        if version < FLOAT_DIFFICULTY {
+            self.skip(2)?;
+        }
+        self.skip(4 + 1)?; // last modification, mania scroll speed
+
+        Ok(StableBeatmap {
+            beatmap_id,
+            beatmap_set_id,
+            songs_path: songs_path.to_path_buf(),
+            folder_name,
+            beatmap_file_name,
+            artist,
+            title,
+            creator,
+            source,
+            tags,
+            hash,
+            status: ranked_status as i8 - 3,
+            modification_date,
+            total_time,
+            star_ratings,
+
+            db_entry: None,
+        })
+    }
+}
+
+// Writes what `DbReader` reads
+pub struct DbWriter<W>(pub W);
+
+impl<W: Write> DbWriter<W> {
+    pub fn u8(&mut self, value: u8) -> io::Result<()> {
+        self.0.write_all(&[value])
+    }
+
+    pub fn u16(&mut self, value: u16) -> io::Result<()> {
+        self.0.write_all(&value.to_le_bytes())
+    }
+
+    pub fn i32(&mut self, value: i32) -> io::Result<()> {
+        self.0.write_all(&value.to_le_bytes())
+    }
+
+    pub fn i64(&mut self, value: i64) -> io::Result<()> {
+        self.0.write_all(&value.to_le_bytes())
+    }
+
+    pub fn f64(&mut self, value: f64) -> io::Result<()> {
+        self.0.write_all(&value.to_le_bytes())
+    }
+
+    pub fn count(&mut self, count: usize) -> io::Result<()> {
+        self.i32(count.try_into().map_err(|_| io::ErrorKind::InvalidInput)?)
+    }
+
+    // Always 0x0b and the string, even an empty one
+    pub fn string(&mut self, value: &str) -> io::Result<()> {
+        self.u8(0x0b)?;
+        let mut len = value.len();
+        loop {
+            let byte = (len & 0x7f) as u8;
+            len >>= 7;
+            if len == 0 {
+                self.u8(byte)?;
+                break;
+            }
+            self.u8(byte | 0x80)?;
+        }
+        self.0.write_all(value.as_bytes())
+    }
+}
@@ -0,0 +1,154 @@
+use anyhow::{anyhow, Result};
+use indicatif::ProgressBar;
+use rusqlite::{Connection, TransactionBehavior};
+use std::path::Path;
+
+use crate::{
+    bar_prefix, database, i18n::tr, library::StableBeatmap, report::FailureKind, sql_trace, State,
+};
+
+// Tables an import changes, which are copied back once it's done
+const TABLES: [&str; 7] = [
+    "BeatmapDifficulty",
+    "BeatmapMetadata",
+    "BeatmapSetInfo",
+    "BeatmapInfo",
+    "FileInfo",
+    "BeatmapSetFileInfo",
+    "ScoreInfo",
+];
+
+// Imports into an in-memory copy of osu!lazer's database, and only copies the rows that changed
+// back once the import is done and the copy has passed the integrity checks. osu!lazer's database
+// is then only written to for as long as the copy takes, and not at all if something's wrong. The
+// whole database is held in memory meanwhile. Nothing is copied back if osu!lazer's database was
+// written to since the copy was taken, as its changes would be undone.
+pub fn import(state: &State, beatmaps: Vec<StableBeatmap>) -> Result<()> {
+    // Kept open to tell whether anything else wrote to the database in the meantime
+    let lazer = Connection::open(&state.lazer_db_path)?;
+    let version = data_version(&lazer)?;
+    let mut staging = open(&state.lazer_db_path)?;
+
+    // The integrity checks' failures are counted as the import goes
+    let problems = state.report.failures_of(FailureKind::Integrity);
+    crate::import(state, &mut staging, beatmaps)?;
+    if state.report.failures_of(FailureKind::Integrity) > problems {
+        return Err(anyhow!(tr!("error-staged-integrity")));
+    }
+
+    let bar = state
+        .progress_bars
+        .manager
+        .add(ProgressBar::new_spinner())
+        .with_prefix(bar_prefix("bar-database"))
+        .with_message(tr!("copying-staged"))
+        .with_style(state.progress_styles.waiting.clone());
+    bar.tick();
+    copy_back(&mut staging, &state.lazer_db_path, &lazer, version)?;
+    bar.finish_with_message(tr!("done"));
+
+    Ok(())
+}
+
+// An in-memory database with the same schema and rows as osu!lazer's
+fn open(lazer_db_path: &Path) -> Result<Connection> {
+    let mut staging = Connection::open_in_memory()?;
+    sql_trace::attach(&mut staging);
+    attach(&staging, lazer_db_path)?;
+
+    let schema = staging
+        .prepare(
+            "SELECT type, name, sql
+             FROM lazer.sqlite_master
+             WHERE sql NOT NULL
+               AND name NOT LIKE 'sqlite_%'
+             ORDER BY type = 'table' DESC",
+        )?
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+        .collect::<rusqlite::Result<Vec<(String, String, String)>>>()?;
+
+    let tx = staging.transaction()?;
+    for (kind, _, sql) in &schema {
+        if kind == "table" || kind == "index" {
+            tx.execute_batch(sql)?;
+        }
+    }
+    for (kind, name, _) in &schema {
+        if kind == "table" {
+            tx.execute(
+                &format!("INSERT INTO main.\"{0}\" SELECT * FROM lazer.\"{0}\"", name),
+                [],
+            )?;
+        }
+    }
+    // Otherwise AUTOINCREMENT would carry on from the highest ID left, handing out the IDs of rows
+    // osu!lazer deleted again
+    if database::has_table(&tx, "sqlite_sequence")? {
+        tx.execute_batch(
+            "DELETE FROM main.sqlite_sequence;
+             INSERT INTO main.sqlite_sequence SELECT * FROM lazer.sqlite_sequence;",
+        )?;
+    }
+    tx.commit()?;
+    staging.execute_batch("DETACH DATABASE lazer")?;
+
+    Ok(staging)
+}
+
+// Writes the rows that are new or changed in the staging database to osu!lazer's, and removes the
+// ones that are gone from it. That's only the import's changes as long as osu!lazer's database is
+// still as it was copied, which `lazer` is checked for once nothing else can write to it.
+fn copy_back(
+    staging: &mut Connection,
+    lazer_db_path: &Path,
+    lazer: &Connection,
+    version: i64,
+) -> Result<()> {
+    attach(staging, lazer_db_path)?;
+
+    let tx = staging.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    if data_version(lazer)? != version {
+        return Err(anyhow!(tr!("error-staged-changed")));
+    }
+    for table in TABLES {
+        if !database::has_table(&tx, table)? {
+            continue;
+        }
+        tx.execute(
+            &format!(
+                "DELETE FROM lazer.\"{0}\"
+                 WHERE ID NOT IN (SELECT ID FROM main.\"{0}\")",
+                table
+            ),
+            [],
+        )?;
+        tx.execute(
+            &format!(
+                "INSERT OR REPLACE INTO lazer.\"{0}\"
+                 SELECT * FROM main.\"{0}\"
+                 EXCEPT
+                 SELECT * FROM lazer.\"{0}\"",
+                table
+            ),
+            [],
+        )?;
+    }
+    tx.commit()?;
+    staging.execute_batch("DETACH DATABASE lazer")?;
+
+    Ok(())
+}
+
+// Changes whenever another connection commits to the database
+fn data_version(conn: &Connection) -> Result<i64> {
+    Ok(conn.query_row("PRAGMA data_version", [], |row| row.get(0))?)
+}
+
+fn attach(staging: &Connection, lazer_db_path: &Path) -> Result<()> {
+    staging.execute(
+        "ATTACH DATABASE ? AS lazer",
+        [lazer_db_path.to_string_lossy()],
+    )?;
+
+    Ok(())
+}
@@ -0,0 +1,44 @@
+use indicatif::ProgressBar;
+
+use crate::i18n::tr;
+
+// Where the import as a whole is, shown above the other bars. Their stages overlap, so it's at the
+// first one that isn't done yet. The commit at the end gets a step too, as it has no bar of its own
+// and can take a while.
+#[derive(Clone, Copy)]
+pub enum Step {
+    Parsing,
+    Hashing,
+    Writing,
+    Committing,
+}
+
+pub const STEPS: usize = 4;
+
+impl Step {
+    fn id(self) -> &'static str {
+        match self {
+            Self::Parsing => "step-parsing",
+            Self::Hashing => "step-hashing",
+            Self::Writing => "step-writing",
+            Self::Committing => "step-committing",
+        }
+    }
+}
+
+// Moves the bar on to `step`, unless it's past it already, as the stages finish from different
+// threads
+pub fn advance(bar: &ProgressBar, step: Step) {
+    let position = step as u64;
+    if position < bar.position() {
+        return;
+    }
+
+    bar.set_position(position);
+    bar.set_message(tr!(
+        "step",
+        number = position + 1,
+        total = STEPS,
+        name = tr!(step.id())
+    ));
+}
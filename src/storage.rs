@@ -0,0 +1,160 @@
+use std::path::{Path, PathBuf};
+
+use crate::{
+    library::StableInstall,
+    log::{log, Level},
+    options::LinkOptions,
+};
+
+// On a spinning disk, more than a couple of threads reading at once only makes its head seek back
+// and forth, which ends up slower than reading with fewer
+const ROTATIONAL_THREADS: usize = 2;
+
+// How many threads each stage of an import runs with
+#[derive(Clone, Copy)]
+pub struct Threads {
+    // Parsing .osu files
+    pub beatmap: usize,
+    // Listing the sets' files and reading them
+    pub read: usize,
+    // Hashing the files read, which doesn't touch the disk
+    pub hash: usize,
+}
+
+impl Threads {
+    // One per CPU, unless a Songs folder is on a spinning disk for the stages reading from it, and
+    // whatever was given on the command line over either
+    pub fn pick(options: &LinkOptions, songs: Option<&Path>, stable: &[StableInstall]) -> Self {
+        let songs_paths: Vec<PathBuf> = match songs {
+            Some(songs) => vec![songs.to_path_buf()],
+            None => stable
+                .iter()
+                .map(|install| install.songs_path.clone())
+                .collect(),
+        };
+        let rotational = songs_paths
+            .iter()
+            .any(|path| is_rotational(path) == Some(true));
+        let default = if rotational {
+            ROTATIONAL_THREADS
+        } else {
+            num_cpus::get()
+        };
+        log!(
+            Level::Debug,
+            "Songs folders on a spinning disk: {}, defaulting to {} threads",
+            rotational,
+            default
+        );
+
+        // 0 would leave rayon to pick, which is one per CPU all the same
+        Self {
+            beatmap: options.beatmap_threads.unwrap_or(default).max(1),
+            read: options.read_threads.unwrap_or(default).max(1),
+            hash: options.hash_threads.unwrap_or_else(num_cpus::get).max(1),
+        }
+    }
+}
+
+// Whether the disk `path` is on is a spinning one, or None if it can't be told, as for network
+// shares or filesystems spanning several disks
+#[cfg(target_os = "linux")]
+fn is_rotational(path: &Path) -> Option<bool> {
+    use std::{fs, os::unix::fs::MetadataExt};
+
+    // glibc's major() and minor()
+    let dev = fs::metadata(path).ok()?.dev();
+    let major = ((dev >> 8) & 0xfff) | ((dev >> 32) & 0xffff_f000);
+    let minor = (dev & 0xff) | ((dev >> 12) & 0xffff_ff00);
+
+    // A partition's queue is its disk's, one directory up
+    let mut device = fs::canonicalize(format!("/sys/dev/block/{}:{}", major, minor)).ok()?;
+    if device.join("partition").exists() {
+        device.pop();
+    }
+
+    match fs::read_to_string(device.join("queue").join("rotational"))
+        .ok()?
+        .trim()
+    {
+        "1" => Some(true),
+        "0" => Some(false),
+        _ => None,
+    }
+}
+
+// Asks the volume's disk whether it incurs a seek penalty, which only spinning ones do
+#[cfg(target_family = "windows")]
+fn is_rotational(path: &Path) -> Option<bool> {
+    use std::{ffi::OsStr, mem, os::windows::ffi::OsStrExt, ptr};
+    use winapi::{
+        shared::minwindef::{DWORD, MAX_PATH},
+        um::{
+            fileapi::{CreateFileW, GetVolumePathNameW, OPEN_EXISTING},
+            handleapi::{CloseHandle, INVALID_HANDLE_VALUE},
+            ioapiset::DeviceIoControl,
+            winioctl::{
+                PropertyStandardQuery, StorageDeviceSeekPenaltyProperty,
+                DEVICE_SEEK_PENALTY_DESCRIPTOR, IOCTL_STORAGE_QUERY_PROPERTY,
+                STORAGE_PROPERTY_QUERY,
+            },
+            winnt::{FILE_SHARE_READ, FILE_SHARE_WRITE},
+        },
+    };
+
+    let wide = |value: &OsStr| value.encode_wide().chain([0]).collect::<Vec<u16>>();
+
+    let mut volume = [0u16; MAX_PATH + 1];
+    let path = wide(path.as_os_str());
+    if unsafe { GetVolumePathNameW(path.as_ptr(), volume.as_mut_ptr(), volume.len() as DWORD) } == 0
+    {
+        return None;
+    }
+    // C:\ is opened as \\.\C:, while mount points and shares don't have a drive letter to open
+    let volume = String::from_utf16_lossy(&volume);
+    let volume = volume.trim_end_matches('\0').trim_end_matches('\\');
+    let drive = volume.trim_start_matches(r"\\?\");
+    if drive.len() != 2 || !drive.ends_with(':') {
+        return None;
+    }
+    let device = wide(OsStr::new(&format!(r"\\.\{}", drive)));
+
+    unsafe {
+        let handle = CreateFileW(
+            device.as_ptr(),
+            0,
+            FILE_SHARE_READ | FILE_SHARE_WRITE,
+            ptr::null_mut(),
+            OPEN_EXISTING,
+            0,
+            ptr::null_mut(),
+        );
+        if handle == INVALID_HANDLE_VALUE {
+            return None;
+        }
+
+        let mut query: STORAGE_PROPERTY_QUERY = mem::zeroed();
+        query.PropertyId = StorageDeviceSeekPenaltyProperty;
+        query.QueryType = PropertyStandardQuery;
+        let mut descriptor: DEVICE_SEEK_PENALTY_DESCRIPTOR = mem::zeroed();
+        let mut returned: DWORD = 0;
+        let ok = DeviceIoControl(
+            handle,
+            IOCTL_STORAGE_QUERY_PROPERTY,
+            &mut query as *mut _ as *mut _,
+            mem::size_of::<STORAGE_PROPERTY_QUERY>() as DWORD,
+            &mut descriptor as *mut _ as *mut _,
+            mem::size_of::<DEVICE_SEEK_PENALTY_DESCRIPTOR>() as DWORD,
+            &mut returned,
+            ptr::null_mut(),
+        );
+        CloseHandle(handle);
+
+        (ok != 0).then(|| descriptor.IncursSeekPenalty != 0)
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_family = "windows")))]
+fn is_rotational(_path: &Path) -> Option<bool> {
+    None
+}
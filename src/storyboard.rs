@@ -0,0 +1,114 @@
+use libosu::{beatmap::Beatmap, events::Event};
+use std::{fs, path::Path};
+
+// Files referenced by the [Events] section, as lazer stores them on BeatmapMetadata
+#[derive(Default)]
+pub struct EventFiles {
+    pub background: Option<String>,
+    pub video: Option<String>,
+}
+
+impl EventFiles {
+    // Collects the background and video of a difficulty, falling back to the set-wide .osb
+    // storyboard in `set_path` for whichever of the two the .osu doesn't declare itself
+    pub fn collect(beatmap: &Beatmap, set_path: &Path) -> Self {
+        let mut files = Self::from_beatmap(beatmap);
+
+        if files.background.is_none() || files.video.is_none() {
+            if let Some(storyboard) = Self::from_set_storyboard(set_path) {
+                files.background = files.background.or(storyboard.background);
+                files.video = files.video.or(storyboard.video);
+            }
+        }
+
+        files
+    }
+
+    fn from_beatmap(beatmap: &Beatmap) -> Self {
+        let mut files = Self::default();
+
+        for event in &beatmap.events {
+            match event {
+                Event::Background(bg) if files.background.is_none() => {
+                    files.background = Some(clean_filename(&bg.filename));
+                }
+                Event::Video(vid) if files.video.is_none() => {
+                    files.video = Some(clean_filename(&vid.filename));
+                }
+                _ => {}
+            }
+        }
+
+        files
+    }
+
+    fn from_set_storyboard(set_path: &Path) -> Option<Self> {
+        let path = fs::read_dir(set_path)
+            .ok()?
+            .flatten()
+            .map(|entry| entry.path())
+            .find(|path| {
+                path.extension()
+                    .map_or(false, |ext| ext.eq_ignore_ascii_case("osb"))
+            })?;
+        let contents = fs::read_to_string(path).ok()?;
+
+        Some(Self::from_events(&contents))
+    }
+
+    // libosu doesn't parse .osb files, but only the top-level event lines are needed here:
+    //   0,0,"bg.jpg",0,0  /  Background,0,"bg.jpg"
+    //   1,0,"video.mp4"   /  Video,0,"video.mp4"
+    fn from_events(contents: &str) -> Self {
+        let mut files = Self::default();
+        let mut in_events = false;
+
+        for line in contents.lines() {
+            if line.starts_with('[') {
+                in_events = line.trim() == "[Events]";
+                continue;
+            }
+
+            // Indented lines are commands belonging to the previous storyboard object
+            if !in_events
+                || line.starts_with(' ')
+                || line.starts_with('_')
+                || line.starts_with("//")
+            {
+                continue;
+            }
+
+            let mut parts = line.splitn(3, ',');
+            let kind = parts.next().unwrap_or_default().trim();
+            let filename = match parts.nth(1) {
+                Some(rest) => parse_filename(rest),
+                None => continue,
+            };
+
+            match kind {
+                "0" | "Background" if files.background.is_none() => {
+                    files.background = Some(filename)
+                }
+                "1" | "Video" if files.video.is_none() => files.video = Some(filename),
+                _ => {}
+            }
+        }
+
+        files
+    }
+}
+
+fn parse_filename(field: &str) -> String {
+    let field = field.trim();
+    let filename = match field.strip_prefix('"') {
+        Some(quoted) => quoted.split('"').next().unwrap_or_default(),
+        None => field.split(',').next().unwrap_or_default(),
+    };
+
+    clean_filename(filename)
+}
+
+// Same cleanup lazer applies to [Events] filenames
+fn clean_filename(filename: &str) -> String {
+    filename.trim().trim_matches('"').replace('\\', "/")
+}
@@ -0,0 +1,99 @@
+use console::{Style, Term};
+use indicatif::ProgressStyle;
+use once_cell::sync::OnceCell;
+use std::{
+    env,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use crate::config::Config;
+
+const BAR_CHARS: &str = "=> ";
+// Shown instead of BAR_CHARS while a bar's length can still grow
+const GROWING_BAR_CHARS: &str = "-> ";
+const TICK_CHARS: &str = "⠁⠂⠄⡀⢀⠠⠐⠈";
+
+static THEME: OnceCell<Theme> = OnceCell::new();
+static COLOR: AtomicBool = AtomicBool::new(false);
+
+// Progress bar looks set in the config, for terminals that render the defaults badly
+#[derive(Default)]
+struct Theme {
+    bar_template: Option<String>,
+    spinner_template: Option<String>,
+    bar_chars: Option<String>,
+    tick_chars: Option<String>,
+}
+
+// Takes the progress bar looks from the config, and turns colours off when NO_COLOR is set
+// (https://no-color.org) or messages aren't going to a terminal
+pub fn init(config: &Config) {
+    let no_color = env::var_os("NO_COLOR").map_or(false, |value| !value.is_empty());
+    if no_color {
+        console::set_colors_enabled(false);
+    }
+    COLOR.store(!no_color && Term::stderr().is_term(), Ordering::Relaxed);
+
+    let _ = THEME.set(Theme {
+        bar_template: config.bar_template.clone(),
+        spinner_template: config.spinner_template.clone(),
+        bar_chars: config.bar_chars.clone(),
+        tick_chars: config.tick_chars.clone(),
+    });
+}
+
+fn theme() -> &'static Theme {
+    THEME.get_or_init(Theme::default)
+}
+
+// The template of the import's progress bars, unless the config has its own
+pub fn bar_template(default: String) -> String {
+    theme().bar_template.clone().unwrap_or(default)
+}
+
+pub fn spinner_template(default: &str) -> String {
+    theme()
+        .spinner_template
+        .clone()
+        .unwrap_or_else(|| default.to_string())
+}
+
+pub fn bar_style(template: &str) -> ProgressStyle {
+    ProgressStyle::default_bar()
+        .template(template)
+        .progress_chars(theme().bar_chars.as_deref().unwrap_or(BAR_CHARS))
+}
+
+pub fn growing_bar_style(template: &str) -> ProgressStyle {
+    ProgressStyle::default_bar()
+        .template(template)
+        .progress_chars(theme().bar_chars.as_deref().unwrap_or(GROWING_BAR_CHARS))
+}
+
+pub fn spinner_style(template: &str) -> ProgressStyle {
+    ProgressStyle::default_spinner()
+        .tick_chars(theme().tick_chars.as_deref().unwrap_or(TICK_CHARS))
+        .template(template)
+}
+
+pub fn warning(message: &str) -> String {
+    paint(Style::new().yellow(), message)
+}
+
+pub fn error(message: &str) -> String {
+    paint(Style::new().red(), message)
+}
+
+pub fn success(message: &str) -> String {
+    paint(Style::new().green(), message)
+}
+
+// Messages go to the terminal through stdout, stderr and the progress bars, so whether to colour
+// them is decided once in `init` rather than per stream
+fn paint(style: Style, message: &str) -> String {
+    if COLOR.load(Ordering::Relaxed) {
+        style.force_styling(true).apply_to(message).to_string()
+    } else {
+        message.to_string()
+    }
+}
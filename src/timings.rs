@@ -0,0 +1,121 @@
+use cpu_time::ThreadTime;
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use crate::i18n::tr;
+
+// Stages of an import, in the order they run. Some of them overlap, since the pipeline runs them
+// on separate threads.
+#[derive(Clone, Copy)]
+pub enum Stage {
+    StableDb,
+    LazerQuery,
+    Parsing,
+    Walking,
+    Reading,
+    Hashing,
+    Inserting,
+    Linking,
+    Commit,
+}
+
+const STAGES: [(Stage, &str); 9] = [
+    (Stage::StableDb, "timing-stable-db"),
+    (Stage::LazerQuery, "timing-lazer-query"),
+    (Stage::Parsing, "timing-parsing"),
+    (Stage::Walking, "timing-walking"),
+    (Stage::Reading, "timing-reading"),
+    (Stage::Hashing, "timing-hashing"),
+    (Stage::Inserting, "timing-inserting"),
+    (Stage::Linking, "timing-linking"),
+    (Stage::Commit, "timing-commit"),
+];
+
+#[derive(Clone, Copy, Default)]
+struct Span {
+    first: Option<Instant>,
+    last: Option<Instant>,
+    // Summed over every call, so it goes past the wall-clock time for stages running on several
+    // threads at once
+    busy: Duration,
+    cpu: Duration,
+}
+
+// Where the time of an import goes, for `--timings`. Does nothing unless enabled.
+#[derive(Clone, Default)]
+pub struct Timings {
+    enabled: bool,
+    spans: Arc<Mutex<[Span; STAGES.len()]>>,
+}
+
+impl Timings {
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            ..Self::default()
+        }
+    }
+
+    pub fn time<T>(&self, stage: Stage, f: impl FnOnce() -> T) -> T {
+        if !self.enabled {
+            return f();
+        }
+
+        let start = Instant::now();
+        let cpu = ThreadTime::now();
+        let res = f();
+        let cpu = cpu.elapsed();
+        let end = Instant::now();
+
+        let mut spans = self.spans.lock().unwrap();
+        let span = &mut spans[stage as usize];
+        span.first = Some(span.first.map_or(start, |first| first.min(start)));
+        span.last = Some(span.last.map_or(end, |last| last.max(end)));
+        span.busy += end - start;
+        span.cpu += cpu;
+
+        res
+    }
+
+    pub fn print(&self) {
+        if !self.enabled {
+            return;
+        }
+
+        let spans = self.spans.lock().unwrap();
+        let width = STAGES
+            .iter()
+            .map(|(_, id)| tr!(id).chars().count())
+            .max()
+            .unwrap_or(0);
+        let seconds = |duration: Duration| format!("{:.2}s", duration.as_secs_f64());
+
+        println!();
+        println!(
+            "{:width$} {:>10} {:>10} {:>10}",
+            tr!("timing-stage"),
+            tr!("timing-wall"),
+            tr!("timing-busy"),
+            tr!("timing-cpu"),
+            width = width
+        );
+        for (stage, id) in STAGES {
+            let span = spans[stage as usize];
+            let wall = match (span.first, span.last) {
+                (Some(first), Some(last)) => last - first,
+                _ => continue,
+            };
+
+            println!(
+                "{:width$} {:>10} {:>10} {:>10}",
+                tr!(id),
+                seconds(wall),
+                seconds(span.busy),
+                seconds(span.cpu),
+                width = width
+            );
+        }
+    }
+}
@@ -0,0 +1,40 @@
+use anyhow::{anyhow, Result};
+use std::{
+    fs,
+    io::{stdout, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{get_lazer_path, i18n::tr, options::Options, wait_for_input};
+
+fn backup_path(lazer_db_path: &Path) -> PathBuf {
+    lazer_db_path.with_file_name("client.db.osu-link-backup")
+}
+
+// Copies client.db aside before anything is written to it. Only the latest import is kept.
+pub fn back_up(lazer_db_path: &Path) -> Result<()> {
+    fs::copy(lazer_db_path, backup_path(lazer_db_path))?;
+
+    Ok(())
+}
+
+pub fn undo(options: &Options) -> Result<()> {
+    let lazer_db_path = get_lazer_path(options)?.join("client.db");
+    let backup_path = backup_path(&lazer_db_path);
+    if !backup_path.exists() {
+        return Err(anyhow!(tr!("error-no-backup")));
+    }
+
+    println!("{}", tr!("close-games"));
+    print!("{}", tr!("press-enter-to-continue"));
+    stdout().flush()?;
+    wait_for_input()?;
+
+    fs::copy(&backup_path, &lazer_db_path)?;
+    fs::remove_file(&backup_path)?;
+
+    // The links in files/ are left behind, but nothing refers to them anymore
+    println!("{}", tr!("undo-done"));
+
+    Ok(())
+}
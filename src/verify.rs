@@ -0,0 +1,131 @@
+use anyhow::Result;
+use indicatif::ProgressBar;
+use rand::seq::SliceRandom;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashSet,
+    fs::File,
+    io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    exit::ExitCode, get_lazer_path, i18n::tr, open_lazer_db, options::Options, paths, theme,
+};
+
+// Looks for files in lazer's store that have gone missing. `exists` follows links, so links to
+// beatmaps that were since deleted or moved in osu!stable are reported too.
+// With `deep`, files are hashed again as well, since a linked file edited in osu!stable changes
+// lazer's copy along with it.
+pub fn verify(options: &Options, deep: bool, sample: Option<usize>) -> Result<ExitCode> {
+    let lazer_path = get_lazer_path(options)?;
+    let db_connection = open_lazer_db(&lazer_path.join("client.db"))?;
+    let lazer_path = paths::extended(&lazer_path);
+
+    let mut statement = db_connection.prepare(
+        "SELECT i.Hash, f.Filename, s.OnlineBeatmapSetID
+         FROM BeatmapSetFileInfo f
+         JOIN FileInfo i ON i.ID = f.FileInfoID
+         JOIN BeatmapSetInfo s ON s.ID = f.BeatmapSetInfoID
+         WHERE s.DeletePending = 0",
+    )?;
+    let rows = statement.query_map([], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, Option<u32>>(2)?,
+        ))
+    })?;
+
+    let mut checked = 0;
+    let mut missing = 0;
+    // (store path, hash, set, file) of each file that's there, once per hash
+    let mut present = vec![];
+    let mut seen = HashSet::new();
+    for row in rows {
+        let (hash, filename, set_id) = row?;
+        checked += 1;
+        let set = set_id.map_or("-".to_string(), |id| id.to_string());
+
+        let path = paths::store_file(&lazer_path, &hash);
+        if !path.exists() {
+            missing += 1;
+            println!("{}", tr!("verify-missing", set = set, file = filename));
+        } else if deep && seen.insert(hash.clone()) {
+            present.push((path, hash, set, filename));
+        }
+    }
+
+    let summary = tr!("verify-summary", checked = checked, missing = missing);
+    if missing > 0 {
+        println!("{}", theme::warning(&summary));
+    } else {
+        println!("{}", theme::success(&summary));
+    }
+
+    let changed = if deep {
+        if let Some(sample) = sample {
+            present = present
+                .choose_multiple(&mut rand::thread_rng(), sample)
+                .cloned()
+                .collect();
+        }
+        rehash(&present)?
+    } else {
+        0
+    };
+
+    if missing + changed > 0 {
+        Ok(ExitCode::PartialSuccess)
+    } else {
+        Ok(ExitCode::Success)
+    }
+}
+
+// Returns how many files no longer match their hash
+fn rehash(files: &[(PathBuf, String, String, String)]) -> Result<usize> {
+    let bar = ProgressBar::new(files.len() as u64)
+        .with_prefix(tr!("bar-hashing-store"))
+        .with_style(theme::bar_style(
+            "{prefix} [{wide_bar}] {percent:>3}% {pos:>8}/{len:8} {eta_precise}",
+        ));
+
+    let mut changed = 0;
+    for (path, hash, set, filename) in files {
+        bar.inc(1);
+
+        // Unreadable files are left to the check for missing ones
+        match hash_file(path) {
+            Ok(actual) if actual != *hash => {
+                changed += 1;
+                bar.println(theme::warning(&tr!(
+                    "verify-changed",
+                    set = set.as_str(),
+                    file = filename.as_str()
+                )));
+            }
+            _ => {}
+        }
+    }
+    bar.finish_and_clear();
+
+    let summary = tr!(
+        "verify-deep-summary",
+        hashed = files.len(),
+        changed = changed
+    );
+    if changed > 0 {
+        println!("{}", theme::warning(&summary));
+    } else {
+        println!("{}", theme::success(&summary));
+    }
+
+    Ok(changed)
+}
+
+pub fn hash_file(path: &Path) -> io::Result<String> {
+    let mut hash = Sha256::new();
+    io::copy(&mut File::open(path)?, &mut hash)?;
+
+    Ok(format!("{:x}", hash.finalize()))
+}
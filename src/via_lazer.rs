@@ -0,0 +1,83 @@
+use anyhow::Result;
+use indicatif::ProgressBar;
+use itertools::Itertools;
+use std::{env, fs, process};
+
+use crate::{
+    detect, exit::ExitCode, export, i18n::tr, library::StableBeatmap, paths, report::FailureKind,
+    theme, State,
+};
+
+// Windows fails to start a process past 32767 characters of arguments, with some room left for
+// the executable's path
+const MAX_ARGS_LEN: usize = 30000;
+
+// Imports through osu!lazer itself, for versions of osu!lazer whose database osu-link can't write
+// to: each set is packaged into an .osz, and osu!lazer is started with them to import (and then
+// delete) them like any other. Much slower than linking, and the files take up space twice.
+pub fn import(state: &State, beatmaps: Vec<StableBeatmap>) -> Result<ExitCode> {
+    let folders = beatmaps
+        .iter()
+        .map(|bm| (bm.songs_path.as_path(), bm.folder_name.as_str()))
+        .unique()
+        .collect_vec();
+
+    let output = env::temp_dir().join("osu-link");
+    fs::create_dir_all(&output)?;
+
+    let bar = ProgressBar::new(folders.len() as u64)
+        .with_prefix(tr!("bar-packaging"))
+        .with_style(theme::bar_style(
+            "{prefix} [{wide_bar}] {percent:>3}% {pos:>8}/{len:8}",
+        ));
+    let mut osz_paths = vec![];
+    for (songs_path, folder_name) in folders {
+        let set_path = paths::resolve(&paths::extended(songs_path), folder_name);
+        let osz_path = output.join(format!("{}.osz", folder_name));
+
+        match export::package_stable_folder(&set_path, &osz_path) {
+            Ok(()) => osz_paths.push(osz_path),
+            Err(e) => {
+                state.report.error(
+                    &bar,
+                    tr!("error-exporting", name = folder_name, error = e.to_string()),
+                );
+                state.report.add_failure(FailureKind::Io, Some(folder_name));
+                let _ = fs::remove_file(&osz_path);
+            }
+        }
+        bar.inc(1);
+    }
+    bar.finish();
+
+    let args_len: usize = osz_paths
+        .iter()
+        .map(|path| path.as_os_str().len() + 3)
+        .sum();
+    match detect::lazer_executable(state.options.link.lazer_exe.as_deref()) {
+        Some(exe) if !osz_paths.is_empty() && args_len < MAX_ARGS_LEN => {
+            // osu!lazer hands the files over to the running instance if there is one
+            process::Command::new(&exe).args(&osz_paths).spawn()?;
+            println!("{}", tr!("via-lazer-started", count = osz_paths.len()));
+        }
+        _ if !osz_paths.is_empty() => println!(
+            "{}",
+            tr!(
+                "via-lazer-drag",
+                count = osz_paths.len(),
+                path = format!("{:?}", output)
+            )
+        ),
+        _ => {}
+    }
+
+    for line in state.report.breakdown() {
+        println!("{}", theme::warning(&line));
+    }
+
+    Ok(if state.report.failures() > 0 {
+        ExitCode::PartialSuccess
+    } else {
+        ExitCode::Success
+    })
+}
@@ -0,0 +1,215 @@
+-- An empty osu!lazer client.db as of 20210912144011_AddSamplesMatchPlaybackRate, as EF Core creates
+-- it. Only the last migration is recorded, which is all osu-link checks.
+CREATE TABLE "__EFMigrationsHistory" (
+    "MigrationId" TEXT NOT NULL CONSTRAINT "PK___EFMigrationsHistory" PRIMARY KEY,
+    "ProductVersion" TEXT NOT NULL
+);
+
+CREATE TABLE "BeatmapDifficulty" (
+    "ID" INTEGER NOT NULL CONSTRAINT "PK_BeatmapDifficulty" PRIMARY KEY AUTOINCREMENT,
+    "ApproachRate" REAL NOT NULL,
+    "CircleSize" REAL NOT NULL,
+    "DrainRate" REAL NOT NULL,
+    "OverallDifficulty" REAL NOT NULL,
+    "SliderMultiplier" REAL NOT NULL,
+    "SliderTickRate" REAL NOT NULL
+);
+
+CREATE TABLE "BeatmapMetadata" (
+    "ID" INTEGER NOT NULL CONSTRAINT "PK_BeatmapMetadata" PRIMARY KEY AUTOINCREMENT,
+    "Artist" TEXT NULL,
+    "ArtistUnicode" TEXT NULL,
+    "AudioFile" TEXT NULL,
+    "Author" TEXT NULL,
+    "BackgroundFile" TEXT NULL,
+    "PreviewTime" INTEGER NOT NULL,
+    "Source" TEXT NULL,
+    "Tags" TEXT NULL,
+    "Title" TEXT NULL,
+    "TitleUnicode" TEXT NULL,
+    "VideoFile" TEXT NULL,
+    "AuthorID" INTEGER NOT NULL DEFAULT 0
+);
+
+CREATE TABLE "FileInfo" (
+    "ID" INTEGER NOT NULL CONSTRAINT "PK_FileInfo" PRIMARY KEY AUTOINCREMENT,
+    "Hash" TEXT NULL,
+    "ReferenceCount" INTEGER NOT NULL
+);
+
+CREATE TABLE "RulesetInfo" (
+    "ID" INTEGER NULL CONSTRAINT "PK_RulesetInfo" PRIMARY KEY AUTOINCREMENT,
+    "Available" INTEGER NOT NULL,
+    "InstantiationInfo" TEXT NULL,
+    "Name" TEXT NULL,
+    "ShortName" TEXT NULL
+);
+
+CREATE TABLE "Settings" (
+    "ID" INTEGER NOT NULL CONSTRAINT "PK_Settings" PRIMARY KEY AUTOINCREMENT,
+    "Key" TEXT NOT NULL,
+    "RulesetID" INTEGER NULL,
+    "Value" TEXT NULL,
+    "Variant" INTEGER NULL,
+    "SkinInfoID" INTEGER NULL
+);
+
+CREATE TABLE "KeyBinding" (
+    "ID" INTEGER NOT NULL CONSTRAINT "PK_KeyBinding" PRIMARY KEY AUTOINCREMENT,
+    "Action" INTEGER NOT NULL,
+    "Keys" TEXT NULL,
+    "RulesetID" INTEGER NULL,
+    "Variant" INTEGER NULL
+);
+
+CREATE TABLE "SkinInfo" (
+    "ID" INTEGER NOT NULL CONSTRAINT "PK_SkinInfo" PRIMARY KEY AUTOINCREMENT,
+    "Creator" TEXT NULL,
+    "DeletePending" INTEGER NOT NULL,
+    "Hash" TEXT NULL,
+    "Name" TEXT NULL,
+    "InstantiationInfo" TEXT NULL
+);
+
+CREATE TABLE "BeatmapSetInfo" (
+    "ID" INTEGER NOT NULL CONSTRAINT "PK_BeatmapSetInfo" PRIMARY KEY AUTOINCREMENT,
+    "DeletePending" INTEGER NOT NULL,
+    "Hash" TEXT NULL,
+    "MetadataID" INTEGER NULL,
+    "OnlineBeatmapSetID" INTEGER NULL,
+    "Protected" INTEGER NOT NULL,
+    "Status" INTEGER NOT NULL DEFAULT 0,
+    "DateAdded" TEXT NOT NULL DEFAULT '0001-01-01 00:00:00+00:00',
+    CONSTRAINT "FK_BeatmapSetInfo_BeatmapMetadata_MetadataID" FOREIGN KEY ("MetadataID")
+        REFERENCES "BeatmapMetadata" ("ID") ON DELETE RESTRICT
+);
+
+CREATE TABLE "BeatmapInfo" (
+    "ID" INTEGER NOT NULL CONSTRAINT "PK_BeatmapInfo" PRIMARY KEY AUTOINCREMENT,
+    "AudioLeadIn" REAL NOT NULL,
+    "BaseDifficultyID" INTEGER NOT NULL,
+    "BeatDivisor" INTEGER NOT NULL,
+    "BeatmapSetInfoID" INTEGER NOT NULL,
+    "Countdown" INTEGER NOT NULL,
+    "DistanceSpacing" REAL NOT NULL,
+    "GridSize" INTEGER NOT NULL,
+    "Hash" TEXT NULL,
+    "Hidden" INTEGER NOT NULL,
+    "LetterboxInBreaks" INTEGER NOT NULL,
+    "MD5Hash" TEXT NULL,
+    "MetadataID" INTEGER NULL,
+    "OnlineBeatmapID" INTEGER NULL,
+    "Path" TEXT NULL,
+    "RulesetID" INTEGER NOT NULL,
+    "SpecialStyle" INTEGER NOT NULL,
+    "StackLeniency" REAL NOT NULL,
+    "StarDifficulty" REAL NOT NULL,
+    "StoredBookmarks" TEXT NULL,
+    "TimelineZoom" REAL NOT NULL,
+    "Version" TEXT NULL,
+    "WidescreenStoryboard" INTEGER NOT NULL,
+    "Status" INTEGER NOT NULL DEFAULT 0,
+    "BPM" REAL NOT NULL DEFAULT 0.0,
+    "Length" REAL NOT NULL DEFAULT 0.0,
+    "EpilepsyWarning" INTEGER NOT NULL DEFAULT 0,
+    "CountdownOffset" INTEGER NOT NULL DEFAULT 0,
+    "SamplesMatchPlaybackRate" INTEGER NOT NULL DEFAULT 0,
+    CONSTRAINT "FK_BeatmapInfo_BeatmapDifficulty_BaseDifficultyID" FOREIGN KEY ("BaseDifficultyID")
+        REFERENCES "BeatmapDifficulty" ("ID") ON DELETE CASCADE,
+    CONSTRAINT "FK_BeatmapInfo_BeatmapSetInfo_BeatmapSetInfoID" FOREIGN KEY ("BeatmapSetInfoID")
+        REFERENCES "BeatmapSetInfo" ("ID") ON DELETE CASCADE,
+    CONSTRAINT "FK_BeatmapInfo_BeatmapMetadata_MetadataID" FOREIGN KEY ("MetadataID")
+        REFERENCES "BeatmapMetadata" ("ID") ON DELETE RESTRICT,
+    CONSTRAINT "FK_BeatmapInfo_RulesetInfo_RulesetID" FOREIGN KEY ("RulesetID")
+        REFERENCES "RulesetInfo" ("ID") ON DELETE CASCADE
+);
+
+CREATE TABLE "BeatmapSetFileInfo" (
+    "ID" INTEGER NOT NULL CONSTRAINT "PK_BeatmapSetFileInfo" PRIMARY KEY AUTOINCREMENT,
+    "BeatmapSetInfoID" INTEGER NOT NULL,
+    "FileInfoID" INTEGER NOT NULL,
+    "Filename" TEXT NOT NULL,
+    CONSTRAINT "FK_BeatmapSetFileInfo_BeatmapSetInfo_BeatmapSetInfoID" FOREIGN KEY ("BeatmapSetInfoID")
+        REFERENCES "BeatmapSetInfo" ("ID") ON DELETE CASCADE,
+    CONSTRAINT "FK_BeatmapSetFileInfo_FileInfo_FileInfoID" FOREIGN KEY ("FileInfoID")
+        REFERENCES "FileInfo" ("ID") ON DELETE CASCADE
+);
+
+CREATE TABLE "SkinFileInfo" (
+    "ID" INTEGER NOT NULL CONSTRAINT "PK_SkinFileInfo" PRIMARY KEY AUTOINCREMENT,
+    "FileInfoID" INTEGER NOT NULL,
+    "Filename" TEXT NOT NULL,
+    "SkinInfoID" INTEGER NOT NULL,
+    CONSTRAINT "FK_SkinFileInfo_FileInfo_FileInfoID" FOREIGN KEY ("FileInfoID")
+        REFERENCES "FileInfo" ("ID") ON DELETE CASCADE,
+    CONSTRAINT "FK_SkinFileInfo_SkinInfo_SkinInfoID" FOREIGN KEY ("SkinInfoID")
+        REFERENCES "SkinInfo" ("ID") ON DELETE CASCADE
+);
+
+CREATE TABLE "ScoreInfo" (
+    "ID" INTEGER NOT NULL CONSTRAINT "PK_ScoreInfo" PRIMARY KEY AUTOINCREMENT,
+    "Accuracy" TEXT NOT NULL,
+    "BeatmapInfoID" INTEGER NOT NULL,
+    "Combo" INTEGER NOT NULL,
+    "Date" TEXT NOT NULL,
+    "DeletePending" INTEGER NOT NULL,
+    "Hash" TEXT NULL,
+    "MaxCombo" INTEGER NOT NULL,
+    "Mods" TEXT NULL,
+    "OnlineScoreID" INTEGER NULL,
+    "PP" REAL NULL,
+    "Rank" INTEGER NOT NULL,
+    "RulesetID" INTEGER NOT NULL,
+    "Statistics" TEXT NULL,
+    "TotalScore" INTEGER NOT NULL,
+    "User" TEXT NULL,
+    "UserID" INTEGER NULL,
+    CONSTRAINT "FK_ScoreInfo_BeatmapInfo_BeatmapInfoID" FOREIGN KEY ("BeatmapInfoID")
+        REFERENCES "BeatmapInfo" ("ID") ON DELETE CASCADE,
+    CONSTRAINT "FK_ScoreInfo_RulesetInfo_RulesetID" FOREIGN KEY ("RulesetID")
+        REFERENCES "RulesetInfo" ("ID") ON DELETE CASCADE
+);
+
+CREATE TABLE "ScoreFileInfo" (
+    "ID" INTEGER NOT NULL CONSTRAINT "PK_ScoreFileInfo" PRIMARY KEY AUTOINCREMENT,
+    "FileInfoID" INTEGER NOT NULL,
+    "Filename" TEXT NOT NULL,
+    "ScoreInfoID" INTEGER NULL,
+    CONSTRAINT "FK_ScoreFileInfo_FileInfo_FileInfoID" FOREIGN KEY ("FileInfoID")
+        REFERENCES "FileInfo" ("ID") ON DELETE CASCADE,
+    CONSTRAINT "FK_ScoreFileInfo_ScoreInfo_ScoreInfoID" FOREIGN KEY ("ScoreInfoID")
+        REFERENCES "ScoreInfo" ("ID") ON DELETE RESTRICT
+);
+
+CREATE INDEX "IX_BeatmapInfo_BaseDifficultyID" ON "BeatmapInfo" ("BaseDifficultyID");
+CREATE INDEX "IX_BeatmapInfo_BeatmapSetInfoID" ON "BeatmapInfo" ("BeatmapSetInfoID");
+CREATE INDEX "IX_BeatmapInfo_Hash" ON "BeatmapInfo" ("Hash");
+CREATE INDEX "IX_BeatmapInfo_MD5Hash" ON "BeatmapInfo" ("MD5Hash");
+CREATE INDEX "IX_BeatmapInfo_MetadataID" ON "BeatmapInfo" ("MetadataID");
+CREATE UNIQUE INDEX "IX_BeatmapInfo_OnlineBeatmapID" ON "BeatmapInfo" ("OnlineBeatmapID");
+CREATE INDEX "IX_BeatmapInfo_RulesetID" ON "BeatmapInfo" ("RulesetID");
+CREATE INDEX "IX_BeatmapSetFileInfo_BeatmapSetInfoID" ON "BeatmapSetFileInfo" ("BeatmapSetInfoID");
+CREATE INDEX "IX_BeatmapSetFileInfo_FileInfoID" ON "BeatmapSetFileInfo" ("FileInfoID");
+CREATE INDEX "IX_BeatmapSetInfo_DeletePending" ON "BeatmapSetInfo" ("DeletePending");
+CREATE UNIQUE INDEX "IX_BeatmapSetInfo_Hash" ON "BeatmapSetInfo" ("Hash");
+CREATE INDEX "IX_BeatmapSetInfo_MetadataID" ON "BeatmapSetInfo" ("MetadataID");
+CREATE UNIQUE INDEX "IX_BeatmapSetInfo_OnlineBeatmapSetID" ON "BeatmapSetInfo" ("OnlineBeatmapSetID");
+CREATE UNIQUE INDEX "IX_FileInfo_Hash" ON "FileInfo" ("Hash");
+CREATE INDEX "IX_FileInfo_ReferenceCount" ON "FileInfo" ("ReferenceCount");
+CREATE INDEX "IX_KeyBinding_Action" ON "KeyBinding" ("Action");
+CREATE INDEX "IX_KeyBinding_Variant" ON "KeyBinding" ("Variant");
+CREATE UNIQUE INDEX "IX_RulesetInfo_Available" ON "RulesetInfo" ("Available", "ID");
+CREATE UNIQUE INDEX "IX_RulesetInfo_InstantiationInfo" ON "RulesetInfo" ("InstantiationInfo");
+CREATE UNIQUE INDEX "IX_RulesetInfo_ShortName" ON "RulesetInfo" ("ShortName");
+CREATE INDEX "IX_ScoreFileInfo_FileInfoID" ON "ScoreFileInfo" ("FileInfoID");
+CREATE INDEX "IX_ScoreFileInfo_ScoreInfoID" ON "ScoreFileInfo" ("ScoreInfoID");
+CREATE INDEX "IX_ScoreInfo_BeatmapInfoID" ON "ScoreInfo" ("BeatmapInfoID");
+CREATE UNIQUE INDEX "IX_ScoreInfo_OnlineScoreID" ON "ScoreInfo" ("OnlineScoreID");
+CREATE INDEX "IX_ScoreInfo_RulesetID" ON "ScoreInfo" ("RulesetID");
+CREATE INDEX "IX_Settings_RulesetID_Variant" ON "Settings" ("RulesetID", "Variant");
+CREATE INDEX "IX_SkinFileInfo_FileInfoID" ON "SkinFileInfo" ("FileInfoID");
+CREATE INDEX "IX_SkinFileInfo_SkinInfoID" ON "SkinFileInfo" ("SkinInfoID");
+CREATE INDEX "IX_SkinInfo_DeletePending" ON "SkinInfo" ("DeletePending");
+
+INSERT INTO "__EFMigrationsHistory" ("MigrationId", "ProductVersion")
+VALUES ('20210912144011_AddSamplesMatchPlaybackRate', '5.0.9');